@@ -0,0 +1,1653 @@
+//! # AST Compiler
+//!
+//! * Lowers a type-checked `frontend::Module` into a Kese IR module
+//! * Top-level statements are collected into an entry function `@main` returning `i32`,
+//!   every `func` declaration becomes an IR function of its own and calls to it
+//!   become `call` instructions
+//! * Variables are kept in SSA form: every binding maps to the `ValueID` currently
+//!   holding its value, and bindings that differ between incoming edges are joined
+//!   through block parameters
+//! * Struct values are kept as one `ValueID` per scalar field, nested in field order
+//! * Enum values are kept as a `u32` tag followed by the payload fields of every
+//!   variant, inactive payloads hold zeroes. `match` tests the tag and lowers into
+//!   a chain of `br` instructions, one per refutable arm
+//! * Loops jump back to a header block whose parameters carry every variable the
+//!   loop body assigns, `break` edges are joined after the loop like `if` arms
+//! * With `with_stack_locals`, `mut` variables declared with a value live in stack
+//!   slots instead, one per scalar. Their binding holds the slot addresses, reads
+//!   load from them and assignments store to them
+//! * String literals are interned into read-only globals. Strings are passed and
+//!   returned as their address and length, `++`, `==` and `!=` on them and the
+//!   `len` and `slice` builtins call into the string runtime, which is linked into
+//!   the module when used
+//! * Structs and enums cross function boundaries in memory laid out as the type
+//!   registry says. Arguments are copied into a stack slot of the caller and passed
+//!   as its address, and results are written through an address the caller passes
+//!   ahead of the other arguments. Only the payload of the active variant is written,
+//!   so the other payloads of an enum read back from memory hold whatever is there
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+use crate::frontend::{self, ASTNode, MatchArm, Node, Param, Pattern, TypeRegistry};
+use crate::global::{is_arithmetic, ECode, Error, Span};
+use super::ir::{
+    entities::{BlockID, Function, Type, ValueID},
+    prelude::*
+};
+use super::runtime::{self, StringAlloc};
+
+/// A lowered value, structs hold their fields in layout order and enums
+/// hold their tag followed by the fields of each variant in declaration order.
+/// Strings are structs named `string` holding their `ptr` address and `u64` length in bytes
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Scalar(ValueID),
+    Struct(String, Vec<Value>),
+    Enum(String, Vec<Value>)
+}
+
+impl Value {
+    /// Appends every scalar held by this value in field order
+    fn scalars(&self, out: &mut Vec<ValueID>) {
+        match self {
+            Value::Scalar(value) => out.push(*value),
+            Value::Struct(_, fields) | Value::Enum(_, fields) => {
+                fields.iter().for_each(|f| f.scalars(out))
+            }
+        }
+    }
+
+    fn string(address: ValueID, len: ValueID) -> Value {
+        Value::Struct("string".to_string(), vec![Value::Scalar(address), Value::Scalar(len)])
+    }
+
+    fn is_string(&self) -> bool {
+        matches!(self, Value::Struct(name, _) if name == "string")
+    }
+
+    /// A value shaped like this one holding the next scalars of `values`
+    fn rebuild(&self, values: &mut impl Iterator<Item = ValueID>) -> Value {
+        match self {
+            Value::Scalar(_) => Value::Scalar(values.next().expect("too few scalars to rebuild value")),
+            Value::Struct(name, fields) => Value::Struct(name.clone(), fields.iter().map(|f| f.rebuild(values)).collect()),
+            Value::Enum(name, fields) => Value::Enum(name.clone(), fields.iter().map(|f| f.rebuild(values)).collect())
+        }
+    }
+}
+
+/// IR type of a scalar frontend type, `None` for aggregates and types
+/// that cannot be compiled yet
+fn scalar_type(t: &frontend::Type) -> Option<Type> {
+    Some(match t {
+        frontend::Type::Int8 => types::I8,
+        frontend::Type::Int16 => types::I16,
+        frontend::Type::Int32 => types::I32,
+        frontend::Type::Int64 => types::I64,
+        frontend::Type::UInt8 => types::U8,
+        frontend::Type::UInt16 => types::U16,
+        frontend::Type::UInt32 => types::U32,
+        frontend::Type::UInt64 => types::U64,
+        frontend::Type::Float32 => types::F32,
+        frontend::Type::Float64 => types::F64,
+        frontend::Type::Boolean => types::BOOL,
+        _ => return None
+    })
+}
+
+/// Nodes evaluated as part of `node`, the body of a function declaration is not
+fn children(node: &Node) -> Vec<&Node> {
+    match &node.ast_repr {
+        ASTNode::Mutation { value, .. } | ASTNode::FieldMutation { value, .. }
+        | ASTNode::DeclarationWithValue { value, .. } => vec![value],
+        ASTNode::BinOp { lhs, rhs, .. } => vec![lhs, rhs],
+        ASTNode::UnaOp { operand, .. } => vec![operand],
+        ASTNode::If { condition, then_body, else_body } => vec![condition, then_body, else_body],
+        ASTNode::Block(stmts) => stmts.iter().collect(),
+        ASTNode::Statement(inner) | ASTNode::Loop(inner) => vec![inner],
+        ASTNode::Call { args, .. } | ASTNode::EnumVariant { args, .. } => args.iter().collect(),
+        ASTNode::Return(value) | ASTNode::Break(value) => value.iter().map(|v| v.as_ref()).collect(),
+        ASTNode::StructLit { fields, .. } => fields.iter().map(|(_, v)| v).collect(),
+        ASTNode::FieldAccess { object, .. } | ASTNode::Cast { value: object, .. } => vec![object],
+        ASTNode::Match { scrutinee, arms } => {
+            std::iter::once(scrutinee.as_ref()).chain(arms.iter().map(|arm| &arm.body)).collect()
+        },
+        ASTNode::While { condition, body } => vec![condition, body],
+        ASTNode::IntLit(_) | ASTNode::FloatLit(_) | ASTNode::StringLit(_) | ASTNode::Bool(_)
+        | ASTNode::Identifier(_) | ASTNode::Declaration { .. } | ASTNode::Function { .. }
+        | ASTNode::StructDecl { .. } | ASTNode::EnumDecl { .. } | ASTNode::Continue => vec![]
+    }
+}
+
+/// Names of the variables assigned anywhere within `node`
+fn assigned_names(node: &Node, names: &mut BTreeSet<String>) {
+    if let ASTNode::Mutation { name, .. } | ASTNode::FieldMutation { name, .. } = &node.ast_repr {
+        names.insert(name.0.clone());
+    }
+    for child in children(node) {
+        assigned_names(child, names);
+    }
+}
+
+/// Contents of the string literals within `node`, function bodies included
+fn string_literals<'a>(node: &'a Node, literals: &mut Vec<&'a str>) {
+    match &node.ast_repr {
+        ASTNode::StringLit(value) => literals.push(value),
+        ASTNode::Function { body, .. } => string_literals(body, literals),
+        _ => {}
+    }
+    for child in children(node) {
+        string_literals(child, literals);
+    }
+}
+
+/// A struct or enum declaration whose field types may not be known yet
+enum PendingType {
+    Struct(Vec<(String, String)>),
+    Enum(Vec<(String, Vec<String>)>)
+}
+
+type Scopes = Vec<HashMap<String, Option<Value>>>;
+
+/// An edge flowing into a join block, carrying the state of the
+/// predecessor at the point it jumps. Edges that returned carry no block
+struct Edge {
+    block: Option<BlockBuilder>,
+    scopes: Scopes,
+    value: Option<Value>
+}
+
+/// An enclosing loop. `carried` names the variables passed to the header,
+/// as the scope depth holding them and their name
+struct LoopFrame {
+    header: BlockID,
+    carried: Vec<(usize, String)>,
+    depth: usize,
+    breaks: Vec<Edge>
+}
+
+/// Lowering state of the function currently being built. `current` is
+/// `None` once the function has returned on the path being lowered
+struct FunctionState {
+    builder: FunctionBuilder,
+    current: Option<BlockBuilder>,
+    scopes: Scopes,
+    return_ty: Type,
+    loops: Vec<LoopFrame>,
+    /// Scalar types of variables declared without a value, the value
+    /// first assigned to them is lowered as this type
+    annotations: HashMap<String, Type>,
+    /// Types stored at the stack slot addresses of `mut` variables, keyed by address
+    homes: HashMap<usize, Type>,
+    /// Where a struct or enum result is written, passed by the caller
+    sret: Option<ValueID>
+}
+
+impl FunctionState {
+    fn new(mut builder: FunctionBuilder) -> Self {
+        let entry = builder.create_block();
+        let return_ty = builder.function.sig.returns.first().copied().unwrap_or(types::VOID);
+        Self {
+            builder,
+            current: Some(entry),
+            scopes: vec![HashMap::new()],
+            return_ty,
+            loops: Vec::new(),
+            annotations: HashMap::new(),
+            homes: HashMap::new(),
+            sret: None
+        }
+    }
+
+    fn is_terminated(&self) -> bool {
+        self.current.is_none()
+    }
+
+    fn ins(&self) -> InstBuilder {
+        self.current
+            .as_ref()
+            .expect("no block to insert instructions into")
+            .ins()
+    }
+
+    /// Finishes the current block and continues lowering into `block`
+    fn switch_to(&mut self, block: BlockBuilder) {
+        if let Some(previous) = self.current.replace(block) {
+            self.builder.eat_block(previous);
+        }
+    }
+
+    /// Detaches the current block so it can be wired into a join later
+    fn take_edge(&mut self, value: Option<Value>) -> Edge {
+        Edge {
+            block: self.current.take(),
+            scopes: self.scopes.clone(),
+            value
+        }
+    }
+
+    fn declare(&mut self, name: &str, value: Option<Value>) {
+        self.scopes
+            .last_mut()
+            .expect("no scope to declare into")
+            .insert(name.to_string(), value);
+    }
+
+    fn lookup(&self, name: &str) -> Option<Option<Value>> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).cloned())
+    }
+
+    fn assign(&mut self, name: &str, value: Value) -> bool {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(slot) = scope.get_mut(name) {
+                *slot = Some(value);
+                return true
+            }
+        }
+        false
+    }
+
+    /// Whether `value` holds the stack slot addresses of a variable
+    fn is_home(&self, value: &Value) -> bool {
+        let mut scalars = Vec::new();
+        value.scalars(&mut scalars);
+        scalars.first().is_some_and(|first| self.homes.contains_key(&first.0))
+    }
+
+    /// Gives each scalar of `value` a stack slot of its own and stores it there
+    fn spill(&mut self, value: &Value) -> Value {
+        let mut scalars = Vec::new();
+        value.scalars(&mut scalars);
+        let mut addresses = Vec::new();
+        for scalar in scalars {
+            let slot = self.builder.create_stack_slot(scalar.1.bytes(), scalar.1.bytes());
+            let address = self.ins().stack_addr(slot);
+            self.ins().store(scalar, address);
+            self.homes.insert(address.0, scalar.1);
+            addresses.push(address);
+        }
+        value.rebuild(&mut addresses.into_iter())
+    }
+
+    /// The current value of a binding, loaded from its slots if it has any
+    fn read(&mut self, value: Value) -> Value {
+        if !self.is_home(&value) {
+            return value
+        }
+        let mut addresses = Vec::new();
+        value.scalars(&mut addresses);
+        let loaded: Vec<ValueID> = addresses
+            .into_iter()
+            .map(|address| {
+                let ty = self.homes[&address.0];
+                self.ins().load(ty, address)
+            })
+            .collect();
+        value.rebuild(&mut loaded.into_iter())
+    }
+
+    /// Assigns `value` to the variable `name`, storing it if the variable has slots
+    fn write(&mut self, name: &str, value: Value) -> bool {
+        match self.lookup(name) {
+            Some(Some(home)) if self.is_home(&home) => {
+                let (mut addresses, mut scalars) = (Vec::new(), Vec::new());
+                home.scalars(&mut addresses);
+                value.scalars(&mut scalars);
+                for (address, scalar) in addresses.into_iter().zip(scalars) {
+                    self.ins().store(scalar, address);
+                }
+                true
+            },
+            _ => self.assign(name, value)
+        }
+    }
+
+    /// Returns `value` from the function and ends the current block
+    fn ret(&mut self, value: Option<Value>) {
+        match value {
+            Some(value) if self.return_ty != types::VOID => {
+                let mut scalars = Vec::new();
+                value.scalars(&mut scalars);
+                self.ins().ret_values(&scalars);
+            },
+            _ => self.ins().ret_void()
+        }
+        if let Some(block) = self.current.take() {
+            self.builder.eat_block(block);
+        }
+    }
+
+    /// Scalars of the variables carried by `frame`, in header parameter order
+    fn carried_args(&self, frame: &LoopFrame) -> Vec<ValueID> {
+        let mut args = Vec::new();
+        for (depth, name) in &frame.carried {
+            if let Some(Some(value)) = self.scopes[*depth].get(name) {
+                value.scalars(&mut args);
+            }
+        }
+        args
+    }
+
+    /// Jumps back to the header of the innermost loop
+    fn continue_loop(&mut self) {
+        let frame = self.loops.last().expect("`continue` outside of a loop");
+        let args = self.carried_args(frame);
+        let call = BlockCall { block: frame.header, args };
+        self.ins().jmp(call);
+        if let Some(block) = self.current.take() {
+            self.builder.eat_block(block);
+        }
+    }
+
+    /// Leaves the innermost loop, the edge is joined once the loop is lowered
+    fn break_loop(&mut self, value: Option<Value>) {
+        let mut edge = self.take_edge(value);
+        let frame = self.loops.last_mut().expect("`break` outside of a loop");
+        edge.scopes.truncate(frame.depth);
+        frame.breaks.push(edge);
+    }
+
+    fn finish(mut self) -> Function {
+        if let Some(block) = self.current.take() {
+            self.builder.eat_block(block);
+        }
+        self.builder.build()
+    }
+}
+
+pub struct ASTCompiler {
+    ast: frontend::Module,
+    src: String,
+    path: String,
+    type_registry: TypeRegistry,
+    /// Signatures of every `func` declaration and of the string runtime, known
+    /// before any body is lowered
+    signatures: HashMap<String, FunctionSignature>,
+    /// Result type of every `func` returning a struct or enum, which it writes
+    /// to memory provided by the caller
+    returned_in_memory: HashMap<String, frontend::Type>,
+    /// Alias of the read-only global holding each string literal, interned before any body is lowered
+    strings: HashMap<String, String>,
+    stack_locals: bool,
+    string_alloc: StringAlloc
+}
+
+impl ASTCompiler {
+    pub fn new(ast: frontend::Module, src: String, path: String) -> Self {
+        let mut compiler = Self {
+            ast,
+            src,
+            path,
+            type_registry: TypeRegistry::new(),
+            signatures: HashMap::new(),
+            returned_in_memory: HashMap::new(),
+            strings: HashMap::new(),
+            stack_locals: false,
+            string_alloc: StringAlloc::default()
+        };
+        compiler.register_types();
+        compiler
+    }
+
+    /// Keeps `mut` variables in stack slots rather than in SSA values
+    pub fn with_stack_locals(mut self) -> Self {
+        self.stack_locals = true;
+        self
+    }
+
+    /// Picks where the strings built at runtime get their memory, the heap by default
+    pub fn with_string_alloc(mut self, alloc: StringAlloc) -> Self {
+        self.string_alloc = alloc;
+        self
+    }
+
+    /// Defines the layout of every struct and enum, the program is type checked
+    /// so each round defines at least one type whose field types are known
+    fn register_types(&mut self) {
+        let mut pending: Vec<(String, PendingType)> = self.ast.0
+            .iter()
+            .map(|node| match &node.ast_repr {
+                ASTNode::Statement(inner) => inner,
+                _ => node
+            })
+            .filter_map(|node| match &node.ast_repr {
+                ASTNode::StructDecl { name, fields } => Some((
+                    name.0.clone(),
+                    PendingType::Struct(fields.iter().map(|(f, t)| (f.0.clone(), t.0.clone())).collect())
+                )),
+                ASTNode::EnumDecl { name, variants } => Some((
+                    name.0.clone(),
+                    PendingType::Enum(variants
+                        .iter()
+                        .map(|(v, types)| (v.0.clone(), types.iter().map(|t| t.0.clone()).collect()))
+                        .collect())
+                )),
+                _ => None
+            })
+            .collect();
+
+        while !pending.is_empty() {
+            let before = pending.len();
+            pending.retain(|(name, decl)| {
+                match decl {
+                    PendingType::Struct(fields) => {
+                        let types: Option<Vec<(String, frontend::Type)>> = fields
+                            .iter()
+                            .map(|(f, t)| self.type_registry.get(t).map(|t| (f.clone(), t)))
+                            .collect();
+                        let Some(types) = types else { return true };
+                        self.type_registry.define_struct(name, types);
+                    },
+                    PendingType::Enum(variants) => {
+                        let types: Option<Vec<(String, Vec<frontend::Type>)>> = variants
+                            .iter()
+                            .map(|(v, types)| {
+                                let types: Option<Vec<frontend::Type>> = types
+                                    .iter()
+                                    .map(|t| self.type_registry.get(t))
+                                    .collect();
+                                types.map(|types| (v.clone(), types))
+                            })
+                            .collect();
+                        let Some(types) = types else { return true };
+                        self.type_registry.define_enum(name, types);
+                    }
+                }
+                false
+            });
+            if pending.len() == before {
+                break
+            }
+        }
+    }
+
+    /// Lowers the whole program into a fresh IR module named after the source file
+    pub fn compile_module(&mut self) -> Result<Module, Error> {
+        let name = Path::new(&self.path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "main".to_string());
+
+        let ast = self.ast.0.clone();
+        let functions: Vec<&Node> = ast
+            .iter()
+            .map(|node| match &node.ast_repr {
+                ASTNode::Statement(inner) => inner,
+                _ => node
+            })
+            .filter(|node| matches!(node.ast_repr, ASTNode::Function { .. }))
+            .collect();
+        // Functions can be called before they are declared
+        self.signatures.extend(runtime::signatures());
+        for node in &functions {
+            if let ASTNode::Function { name, params, return_type, .. } = &node.ast_repr {
+                let sig = self.signature(params, return_type)?;
+                self.signatures.insert(name.0.clone(), sig);
+                if let Some(t) = return_type.as_ref().and_then(|t| self.aggregate(&t.0)) {
+                    self.returned_in_memory.insert(name.0.clone(), t);
+                }
+            }
+        }
+
+        let mut context = Context::new();
+        let module = context.create_module(&name);
+        {
+            let mut builder = module.builder();
+            let mut literals: Vec<&str> = Vec::new();
+            for node in &ast {
+                string_literals(node, &mut literals);
+            }
+            for literal in literals {
+                self.strings.insert(literal.to_string(), builder.intern_string(literal));
+            }
+
+            let sig = FunctionSignature::new()
+                .with_return_ty(types::I32);
+
+            let mut state = FunctionState::new(builder.create_function("main", sig));
+            for node in &self.ast.0 {
+                self.lower_node(&mut state, node)?;
+            }
+            if !state.is_terminated() {
+                let exit_code = state.ins().i32const(0);
+                state.ins().ret(exit_code);
+            }
+            builder.eat_function(state.finish());
+
+            for node in functions {
+                if let ASTNode::Function { name, params, body, .. } = &node.ast_repr {
+                    let function = self.lower_function(&builder, name, params, body)?;
+                    builder.eat_function(function);
+                }
+            }
+            builder.build();
+        }
+        runtime::link(module, self.string_alloc);
+
+        Ok(module.clone())
+    }
+
+    fn error(&self, code: ECode, details: String, span: Span) -> Error {
+        Error {
+            code,
+            details,
+            span,
+            src: self.src.clone(),
+            path: self.path.clone(),
+            note: None,
+            help: None
+        }
+    }
+
+    /// Maps a type annotation onto the IR type holding its values
+    fn ir_type(&self, t: &(String, Span)) -> Result<Type, Error> {
+        if t.0 == "unit" {
+            return Ok(types::VOID)
+        }
+        self.type_registry.get(&t.0).as_ref().and_then(scalar_type).ok_or_else(|| self.error(
+            ECode::Unsupported,
+            format!("values of type `{}` cannot be compiled yet", t.0),
+            t.1
+        ))
+    }
+
+    /// The struct or enum type named `name`, if it is one
+    fn aggregate(&self, name: &str) -> Option<frontend::Type> {
+        (self.type_registry.layout(name).is_some() || self.type_registry.enum_layout(name).is_some())
+            .then(|| frontend::Type::Alias(name.to_string()))
+    }
+
+    /// IR types a value of type `t` is passed and returned as, strings take their
+    /// address and length and structs and enums the address of their memory
+    fn abi_types(&self, t: &(String, Span)) -> Result<Vec<Type>, Error> {
+        if self.type_registry.get(&t.0) == Some(frontend::Type::String) {
+            return Ok(vec![types::PTR, types::U64])
+        }
+        if self.aggregate(&t.0).is_some() {
+            return Ok(vec![types::PTR])
+        }
+        Ok(vec![self.ir_type(t)?])
+    }
+
+    fn signature(&self, params: &[Param], return_type: &Option<(String, Span)>) -> Result<FunctionSignature, Error> {
+        let mut param_types: Vec<Type> = Vec::new();
+        for param in params {
+            let types = self.abi_types(&param.type_)?;
+            if types == [types::VOID] {
+                return Err(self.error(
+                    ECode::Unsupported,
+                    "parameters of type `unit` cannot be compiled yet".to_string(),
+                    param.type_.1
+                ))
+            }
+            param_types.extend(types);
+        }
+        let returns = match return_type {
+            // The caller passes where to write the result ahead of the arguments
+            Some(t) if self.aggregate(&t.0).is_some() => {
+                param_types.insert(0, types::PTR);
+                vec![types::VOID]
+            },
+            Some(t) => self.abi_types(t)?,
+            None => vec![types::VOID]
+        };
+        let sig = FunctionSignature::new().with_params(param_types);
+        Ok(match returns[..] {
+            [ty] => sig.with_return_ty(ty),
+            _ => sig.with_returns(returns)
+        })
+    }
+
+    fn lower_function(&self, builder: &Builder, name: &(String, Span), params: &[Param], body: &Node) -> Result<Function, Error> {
+        let sig = self.signatures[&name.0].clone();
+        let return_ty = sig.returns.first().copied().unwrap_or(types::VOID);
+
+        // Function parameters take the first value ids, strings take two
+        let mut state = FunctionState::new(builder.create_function(&name.0, &sig));
+        let mut ids = sig.params.iter().enumerate().map(|(i, ty)| ValueID(i, *ty));
+        if self.returned_in_memory.contains_key(&name.0) {
+            state.sret = ids.next();
+        }
+        for param in params {
+            let id = ids.next().expect("every parameter has a type");
+            let value = match self.type_registry.get(&param.type_.0) {
+                Some(frontend::Type::String) => Value::string(id, ids.next().expect("strings take two parameters")),
+                Some(t @ frontend::Type::Alias(_)) => self.load_value(&mut state, &t, id, 0),
+                _ => Value::Scalar(id)
+            };
+            state.declare(&param.name.0, Some(value));
+        }
+
+        let expected = Some(return_ty).filter(|t| *t != types::VOID);
+        let value = self.lower_expecting(&mut state, body, expected)?;
+        if !state.is_terminated() {
+            if return_ty != types::VOID || state.sret.is_some() {
+                self.expect_value(value.clone(), body.span)?;
+            }
+            self.ret(&mut state, value);
+        }
+        Ok(state.finish())
+    }
+
+    fn expect_value(&self, value: Option<Value>, span: Span) -> Result<Value, Error> {
+        value.ok_or_else(|| self.error(
+            ECode::MismatchedTypes,
+            "expected a value, found an expression of type `unit`".to_string(),
+            span
+        ))
+    }
+
+    fn expect_scalar(&self, value: Value, span: Span) -> Result<ValueID, Error> {
+        match value {
+            Value::Scalar(value) => Ok(value),
+            Value::Struct(name, _) if name == "string" => Err(self.error(
+                ECode::Unsupported,
+                "values of type `string` cannot be used here yet".to_string(),
+                span
+            )),
+            Value::Struct(name, _) => Err(self.error(
+                ECode::Unsupported,
+                format!("values of struct type `{}` cannot be used here yet", name),
+                span
+            )),
+            Value::Enum(name, _) => Err(self.error(
+                ECode::Unsupported,
+                format!("values of enum type `{}` cannot be used here yet", name),
+                span
+            ))
+        }
+    }
+
+    /// Scalars `value` is passed as, strings pass their address and length and
+    /// structs and enums are copied to the stack and pass its address
+    fn abi_values(&self, st: &mut FunctionState, value: Value) -> Vec<ValueID> {
+        match value {
+            Value::Scalar(value) => vec![value],
+            value if value.is_string() => {
+                let mut scalars = Vec::new();
+                value.scalars(&mut scalars);
+                scalars
+            },
+            Value::Struct(ref name, _) | Value::Enum(ref name, _) => {
+                let address = self.stack_memory(st, &frontend::Type::Alias(name.clone()));
+                self.store_value(st, &value, address, 0);
+                vec![address]
+            }
+        }
+    }
+
+    /// Address of a new stack slot sized and aligned for values of type `t`
+    fn stack_memory(&self, st: &mut FunctionState, t: &frontend::Type) -> ValueID {
+        let (size, align) = self.type_registry.size_align(t).expect("aggregates have a layout");
+        let slot = st.builder.create_stack_slot(size as u32, align as u32);
+        st.ins().stack_addr(slot)
+    }
+
+    /// `offset` bytes past `address`
+    fn offset_address(&self, st: &mut FunctionState, address: ValueID, offset: usize) -> ValueID {
+        if offset == 0 {
+            return address
+        }
+        let mut ins = st.ins();
+        let base = ins.bitcast(address, types::U64);
+        let offset = ins.u64const(offset as u64);
+        let sum = ins.iadd(base, offset);
+        ins.bitcast(sum, types::PTR)
+    }
+
+    /// Writes `value` to `offset` bytes past `address` as its layout places it.
+    /// Enums write their tag, then branch to write the payload of the active variant
+    fn store_value(&self, st: &mut FunctionState, value: &Value, address: ValueID, offset: usize) {
+        match value {
+            Value::Scalar(scalar) => {
+                let at = self.offset_address(st, address, offset);
+                st.ins().store(*scalar, at);
+            },
+            Value::Struct(name, fields) if name == "string" => {
+                self.store_value(st, &fields[0], address, offset);
+                self.store_value(st, &fields[1], address, offset + 8);
+            },
+            Value::Struct(name, fields) => {
+                let layout = self.type_registry.layout(name).expect("struct layouts are registered before lowering");
+                for (field, value) in layout.fields.iter().zip(fields) {
+                    self.store_value(st, value, address, offset + field.offset);
+                }
+            },
+            Value::Enum(name, parts) => {
+                let layout = self.type_registry.enum_layout(name).expect("enum layouts are registered before lowering");
+                self.store_value(st, &parts[0], address, offset);
+                let Value::Scalar(tag) = parts[0] else { unreachable!("enum tags are scalars") };
+
+                let mut payload = parts[1..].iter();
+                for (index, variant) in layout.variants.iter().enumerate() {
+                    let fields: Vec<&Value> = payload.by_ref().take(variant.fields.len()).collect();
+                    if fields.is_empty() {
+                        continue
+                    }
+                    let store_block = st.builder.create_block();
+                    let next_block = st.builder.create_block();
+                    let index = st.ins().u32const(index as u32);
+                    let active = st.ins().icmp(tag, index, CmpPred::eq());
+                    st.ins().br(active, store_block.call(&[]), next_block.call(&[]));
+
+                    st.switch_to(store_block);
+                    for (field, value) in variant.fields.iter().zip(fields) {
+                        self.store_value(st, value, address, offset + field.offset);
+                    }
+                    st.ins().jmp(next_block.call(&[]));
+                    st.switch_to(next_block);
+                }
+            }
+        }
+    }
+
+    /// Reads a value of type `t` from `offset` bytes past `address`, as `store_value` wrote it
+    fn load_value(&self, st: &mut FunctionState, t: &frontend::Type, address: ValueID, offset: usize) -> Value {
+        match t {
+            frontend::Type::String => {
+                let at = self.offset_address(st, address, offset);
+                let data = st.ins().load(types::PTR, at);
+                let at = self.offset_address(st, address, offset + 8);
+                let len = st.ins().load(types::U64, at);
+                Value::string(data, len)
+            },
+            frontend::Type::Alias(name) => {
+                if let Some(layout) = self.type_registry.layout(name) {
+                    let fields = layout.fields
+                        .iter()
+                        .map(|f| self.load_value(st, &f.type_, address, offset + f.offset))
+                        .collect();
+                    return Value::Struct(name.clone(), fields)
+                }
+                let layout = self.type_registry.enum_layout(name).expect("enum layouts are registered before lowering");
+                let at = self.offset_address(st, address, offset);
+                let mut parts = vec![Value::Scalar(st.ins().load(types::U32, at))];
+                for field in layout.variants.iter().flat_map(|v| &v.fields) {
+                    parts.push(self.load_value(st, &field.type_, address, offset + field.offset));
+                }
+                Value::Enum(name.clone(), parts)
+            },
+            t => {
+                let ty = scalar_type(t).expect("type checked values have an IR type");
+                let at = self.offset_address(st, address, offset);
+                Value::Scalar(st.ins().load(ty, at))
+            }
+        }
+    }
+
+    /// Returns `value`, writing it to the caller's memory if the function returns a struct or enum
+    fn ret(&self, st: &mut FunctionState, value: Option<Value>) {
+        if let (Some(address), Some(value)) = (st.sret, &value) {
+            self.store_value(st, value, address, 0);
+        }
+        st.ret(value);
+    }
+
+    /// A value of type `t` with every scalar set to zero
+    fn zero_value(&self, st: &mut FunctionState, t: &frontend::Type, span: Span) -> Result<Value, Error> {
+        let mut ins = st.ins();
+        let value = match t {
+            frontend::Type::Int8 => ins.i8const(0),
+            frontend::Type::Int16 => ins.i16const(0),
+            frontend::Type::Int32 => ins.i32const(0),
+            frontend::Type::Int64 => ins.i64const(0),
+            frontend::Type::UInt8 => ins.u8const(0),
+            frontend::Type::UInt16 => ins.u16const(0),
+            frontend::Type::UInt32 => ins.u32const(0),
+            frontend::Type::UInt64 => ins.u64const(0),
+            frontend::Type::Float32 => ins.f32const(0.0),
+            frontend::Type::Float64 => ins.f64const(0.0),
+            frontend::Type::Boolean => ins.bool_(false),
+            frontend::Type::Alias(name) => {
+                if let Some(layout) = self.type_registry.layout(name) {
+                    let fields = layout.fields
+                        .iter()
+                        .map(|f| self.zero_value(st, &f.type_, span))
+                        .collect::<Result<Vec<Value>, Error>>()?;
+                    return Ok(Value::Struct(name.clone(), fields))
+                }
+                if let Some(layout) = self.type_registry.enum_layout(name) {
+                    let mut parts = vec![Value::Scalar(st.ins().u32const(0))];
+                    for field in layout.variants.iter().flat_map(|v| &v.fields) {
+                        parts.push(self.zero_value(st, &field.type_, span)?);
+                    }
+                    return Ok(Value::Enum(name.clone(), parts))
+                }
+                return Err(self.error(
+                    ECode::Unsupported,
+                    format!("values of type `{}` cannot be compiled yet", name),
+                    span
+                ))
+            },
+            _ => return Err(self.error(
+                ECode::Unsupported,
+                format!("values of type `{}` cannot be compiled yet", t),
+                span
+            ))
+        };
+        Ok(Value::Scalar(value))
+    }
+
+    /// Index of the first payload field of `variant` within a lowered enum
+    fn payload_start(&self, name: &str, variant: usize) -> usize {
+        let layout = self.type_registry
+            .enum_layout(name)
+            .expect("enum layouts are registered before lowering");
+        1 + layout.variants[..variant].iter().map(|v| v.fields.len()).sum::<usize>()
+    }
+
+    fn int_const(&self, st: &mut FunctionState, ty: Type, n: i64) -> ValueID {
+        let mut ins = st.ins();
+        match ty {
+            types::I8 => ins.i8const(n as i8),
+            types::I16 => ins.i16const(n as i16),
+            types::I64 => ins.i64const(n),
+            types::U8 => ins.u8const(n as u8),
+            types::U16 => ins.u16const(n as u16),
+            types::U32 => ins.u32const(n as u32),
+            types::U64 => ins.u64const(n as u64),
+            _ => ins.i32const(n as i32)
+        }
+    }
+
+    /// Emits the tests deciding whether `value` matches `pattern`, if asked
+    /// for, and collects the values bound by it
+    fn lower_pattern(
+        &self,
+        st: &mut FunctionState,
+        pattern: &Pattern,
+        value: &Value,
+        mut tests: Option<&mut Vec<ValueID>>,
+        bindings: &mut Vec<(String, Value)>
+    ) -> Result<(), Error> {
+        match pattern {
+            Pattern::Wildcard(_) => {},
+            Pattern::Binding(name, _) => bindings.push((name.clone(), value.clone())),
+            Pattern::Int(..) | Pattern::Bool(..) if tests.is_none() => {},
+            Pattern::Int(n, span) => {
+                let value = self.expect_scalar(value.clone(), *span)?;
+                let expected = self.int_const(st, value.1, *n as i64);
+                let test = st.ins().icmp(value, expected, CmpPred::eq());
+                tests.iter_mut().for_each(|tests| tests.push(test));
+            },
+            Pattern::Bool(b, span) => {
+                let value = self.expect_scalar(value.clone(), *span)?;
+                let test = if *b { value } else { st.ins().bnot(value) };
+                tests.iter_mut().for_each(|tests| tests.push(test));
+            },
+            Pattern::Variant { enum_name, variant, fields, .. } => {
+                let Value::Enum(_, parts) = value else {
+                    return Err(self.error(
+                        ECode::MismatchedTypes,
+                        format!("expected a value of enum `{}`", enum_name.0),
+                        pattern.span()
+                    ))
+                };
+                let index = self.type_registry
+                    .enum_layout(&enum_name.0)
+                    .and_then(|layout| layout.variant(&variant.0))
+                    .map(|(i, _)| i)
+                    .ok_or_else(|| self.error(
+                        ECode::UndefinedIdentifier,
+                        format!("no variant `{}` on enum `{}`", variant.0, enum_name.0),
+                        variant.1
+                    ))?;
+
+                if let Some(tests) = tests.as_deref_mut() {
+                    let tag = self.expect_scalar(parts[0].clone(), enum_name.1)?;
+                    let expected = st.ins().u32const(index as u32);
+                    tests.push(st.ins().icmp(tag, expected, CmpPred::eq()));
+                }
+
+                let start = self.payload_start(&enum_name.0, index);
+                for (i, field) in fields.iter().enumerate() {
+                    self.lower_pattern(st, field, &parts[start + i], tests.as_deref_mut(), bindings)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Lowers `loop` and `while` bodies. The header receives the variables
+    /// assigned in the loop as parameters and is re-entered by `continue`
+    /// and the end of the body, a `while` condition that fails breaks out
+    fn lower_loop(&self, st: &mut FunctionState, condition: Option<&Node>, body: &Node) -> Result<Option<Value>, Error> {
+        let mut names = BTreeSet::new();
+        if let Some(condition) = condition {
+            assigned_names(condition, &mut names);
+        }
+        assigned_names(body, &mut names);
+
+        let mut carried: Vec<(usize, String)> = Vec::new();
+        for name in names {
+            let depth = st.scopes.iter().rposition(|scope| scope.contains_key(&name));
+            // Variables kept in stack slots are stored to, never rebound
+            let bound = |d: &usize| matches!(st.scopes[*d].get(&name), Some(Some(value)) if !st.is_home(value));
+            if let Some(depth) = depth.filter(bound) {
+                carried.push((depth, name));
+            }
+        }
+
+        let mut header = st.builder.create_block();
+        let mut frame = LoopFrame { header: header.id(), carried, depth: st.scopes.len(), breaks: Vec::new() };
+        let args = st.carried_args(&frame);
+        for arg in &args {
+            header = header.with_param(arg.1);
+        }
+        st.ins().jmp(header.call(&args));
+
+        let mut params = header.params().iter().map(|p| ValueID::from(*p)).collect::<Vec<_>>().into_iter();
+        for (depth, name) in &frame.carried {
+            let slot = st.scopes[*depth].get_mut(name).unwrap();
+            *slot = slot.as_ref().map(|value| value.rebuild(&mut params));
+        }
+        st.switch_to(header);
+
+        if let Some(condition) = condition {
+            let value = self.lower_node(st, condition)?;
+            if st.is_terminated() { return Ok(None) }
+            let value = self.expect_value(value, condition.span)?;
+            let value = self.expect_scalar(value, condition.span)?;
+
+            let body_block = st.builder.create_block();
+            let exit_block = st.builder.create_block();
+            st.ins().br(value, body_block.call(&[]), exit_block.call(&[]));
+            st.switch_to(body_block);
+            frame.breaks.push(Edge { block: Some(exit_block), scopes: st.scopes.clone(), value: None });
+        }
+
+        st.loops.push(frame);
+        let result = self.lower_node(st, body);
+        if result.is_ok() && !st.is_terminated() {
+            st.continue_loop();
+        }
+        let frame = st.loops.pop().unwrap();
+        result?;
+
+        let value = self.join(st, frame.breaks);
+        Ok(if condition.is_some() { None } else { value })
+    }
+
+    /// Lowers the arms of a `match` into a chain of tests, each refutable arm
+    /// branches to its body or on to the tests of the next arm
+    fn lower_match(&self, st: &mut FunctionState, value: Value, arms: &[MatchArm], expected: Option<Type>) -> Result<Option<Value>, Error> {
+        let scopes = st.scopes.clone();
+        let mut edges: Vec<Edge> = Vec::new();
+
+        for (i, arm) in arms.iter().enumerate() {
+            // The last arm matches whatever is left, the checker proved the match exhaustive
+            let last = i + 1 == arms.len();
+            let mut tests: Vec<ValueID> = Vec::new();
+            let mut bindings: Vec<(String, Value)> = Vec::new();
+            self.lower_pattern(st, &arm.pattern, &value, (!last).then_some(&mut tests), &mut bindings)?;
+
+            let mut next = None;
+            if !tests.is_empty() {
+                let mut condition = tests[0];
+                for test in &tests[1..] {
+                    condition = st.ins().band(condition, *test);
+                }
+                let arm_block = st.builder.create_block();
+                let next_block = st.builder.create_block();
+                st.ins().br(condition, arm_block.call(&[]), next_block.call(&[]));
+                st.switch_to(arm_block);
+                next = Some(next_block);
+            }
+
+            st.scopes = scopes.clone();
+            st.scopes.push(bindings.into_iter().map(|(name, value)| (name, Some(value))).collect());
+            // Later arms take the type of the first one
+            let hint = expected.or_else(|| edges.iter().find_map(|edge| match edge.value {
+                Some(Value::Scalar(v)) => Some(v.1),
+                _ => None
+            }));
+            let body = self.lower_expecting(st, &arm.body, hint)?;
+            st.scopes.pop();
+            edges.push(st.take_edge(body));
+
+            match next {
+                Some(block) => st.current = Some(block),
+                None => break
+            }
+        }
+
+        st.scopes = scopes;
+        Ok(self.join(st, edges))
+    }
+
+    /// Index of `field` within the lowered fields of `value`
+    fn field_index(&self, value: &Value, field: &(String, Span)) -> Result<usize, Error> {
+        let index = match value {
+            Value::Struct(name, _) => self.type_registry
+                .layout(name)
+                .and_then(|layout| layout.field(&field.0))
+                .map(|(i, _)| i),
+            Value::Scalar(_) | Value::Enum(..) => None
+        };
+        index.ok_or_else(|| self.error(
+            ECode::UndefinedIdentifier,
+            format!("no field `{}` on this value", field.0),
+            field.1
+        ))
+    }
+
+    /// Rebuilds `value` with the field at the end of `path` replaced
+    fn with_field(&self, value: Value, path: &[(String, Span)], new: Value) -> Result<Value, Error> {
+        let Some((field, rest)) = path.split_first() else { return Ok(new) };
+        let index = self.field_index(&value, field)?;
+        let Value::Struct(name, mut fields) = value else { unreachable!() };
+        let inner = fields[index].clone();
+        fields[index] = self.with_field(inner, rest, new)?;
+        Ok(Value::Struct(name, fields))
+    }
+
+    fn lower_node(&self, st: &mut FunctionState, node: &Node) -> Result<Option<Value>, Error> {
+        self.lower_expecting(st, node, None)
+    }
+
+    /// Lowers `node` where a value of type `expected` is wanted, numeric
+    /// literals take that type like they do in the type checker
+    fn lower_expecting(&self, st: &mut FunctionState, node: &Node, expected: Option<Type>) -> Result<Option<Value>, Error> {
+        // Code following a `return` is unreachable and never lowered
+        if st.is_terminated() {
+            return Ok(None)
+        }
+        match &node.ast_repr {
+            ASTNode::IntLit(i) => {
+                let ty = expected.filter(|t| t.is_int()).unwrap_or(types::I32);
+                Ok(Some(Value::Scalar(self.int_const(st, ty, *i as i64))))
+            },
+            ASTNode::FloatLit(f) if expected == Some(types::F32) => {
+                Ok(Some(Value::Scalar(st.ins().f32const(*f as f32))))
+            },
+            ASTNode::FloatLit(f) => Ok(Some(Value::Scalar(st.ins().f64const(*f)))),
+            ASTNode::StringLit(value) => {
+                let address = st.ins().global_addr(&self.strings[value]);
+                let len = st.ins().u64const(value.len() as u64);
+                Ok(Some(Value::Struct("string".to_string(), vec![Value::Scalar(address), Value::Scalar(len)])))
+            },
+            ASTNode::Bool(b) => Ok(Some(Value::Scalar(st.ins().bool_(*b)))),
+            ASTNode::Identifier(name) => match st.lookup(name) {
+                Some(Some(value)) => Ok(Some(st.read(value))),
+                Some(None) => Err(self.error(
+                    ECode::UndefinedIdentifier,
+                    format!("`{}` is used before being assigned", name),
+                    node.span
+                )),
+                None => Err(self.error(
+                    ECode::UndefinedIdentifier,
+                    format!("cannot find `{}` in scope", name),
+                    node.span
+                ))
+            },
+            ASTNode::BinOp { lhs, rhs, op } if matches!(&*op.0, "&&" | "||") => {
+                let left = self.lower_node(st, lhs)?;
+                if st.is_terminated() { return Ok(None) }
+                let left = self.expect_value(left, lhs.span)?;
+                let left = self.expect_scalar(left, lhs.span)?;
+
+                // When the left operand decides the result it is the result,
+                // the right operand is only evaluated otherwise
+                let rhs_block = st.builder.create_block();
+                let short_block = st.builder.create_block();
+                if op.0 == "&&" {
+                    st.ins().br(left, rhs_block.call(&[]), short_block.call(&[]));
+                } else {
+                    st.ins().br(left, short_block.call(&[]), rhs_block.call(&[]));
+                }
+
+                let scopes = st.scopes.clone();
+                st.switch_to(rhs_block);
+                let right = self.lower_node(st, rhs)?;
+                let rhs_edge = st.take_edge(right);
+
+                st.scopes = scopes;
+                st.switch_to(short_block);
+                let short_edge = st.take_edge(Some(Value::Scalar(left)));
+
+                Ok(self.join(st, vec![rhs_edge, short_edge]))
+            },
+            ASTNode::BinOp { lhs, rhs, op } => {
+                // Literals take the type of the other operand, so a literal
+                // on the left is lowered after the right operand
+                let hint = if is_arithmetic(&op.0) { expected } else { None };
+                let swapped = lhs.is_literal() && !rhs.is_literal();
+                let (first, second) = if swapped { (rhs, lhs) } else { (lhs, rhs) };
+
+                let value = self.lower_expecting(st, first, hint)?;
+                if st.is_terminated() { return Ok(None) }
+                let value = self.expect_value(value, first.span)?;
+                if value.is_string() {
+                    let other = self.lower_node(st, second)?;
+                    if st.is_terminated() { return Ok(None) }
+                    let other = self.expect_value(other, second.span)?;
+                    let (left, right) = if swapped { (other, value) } else { (value, other) };
+                    return self.lower_string_binop(st, op, left, right).map(Some)
+                }
+                let first_value = self.expect_scalar(value, first.span)?;
+                let value = self.lower_expecting(st, second, Some(first_value.1))?;
+                if st.is_terminated() { return Ok(None) }
+                let value = self.expect_value(value, second.span)?;
+                let second_value = self.expect_scalar(value, second.span)?;
+
+                let (left, right) = if swapped { (second_value, first_value) } else { (first_value, second_value) };
+                self.lower_binop(st, op, left, right).map(|v| Some(Value::Scalar(v)))
+            },
+            ASTNode::UnaOp { operand, op } => {
+                // Negative literals are emitted as a single constant
+                if let ("-", ASTNode::IntLit(i)) = (&*op.0, &operand.ast_repr) {
+                    let ty = expected.filter(|t| t.is_signed()).unwrap_or(types::I32);
+                    return Ok(Some(Value::Scalar(self.int_const(st, ty, -*i as i64))))
+                }
+                let value = self.lower_expecting(st, operand, expected)?;
+                if st.is_terminated() { return Ok(None) }
+                let value = self.expect_value(value, operand.span)?;
+                let value = self.expect_scalar(value, operand.span)?;
+                self.lower_unaop(st, op, value).map(|v| Some(Value::Scalar(v)))
+            },
+            ASTNode::If { condition, then_body, else_body } => {
+                let condition = self.lower_node(st, condition)?;
+                if st.is_terminated() { return Ok(None) }
+                let condition = self.expect_value(condition, node.span)?;
+                let condition = self.expect_scalar(condition, node.span)?;
+
+                let then_block = st.builder.create_block();
+                let else_block = st.builder.create_block();
+                st.ins().br(condition, then_block.call(&[]), else_block.call(&[]));
+
+                let scopes = st.scopes.clone();
+
+                st.switch_to(then_block);
+                let then_value = self.lower_expecting(st, then_body, expected)?;
+                let then_ty = match &then_value {
+                    Some(Value::Scalar(v)) => Some(v.1),
+                    _ => None
+                };
+                let then_edge = st.take_edge(then_value);
+
+                st.scopes = scopes;
+                st.switch_to(else_block);
+                let else_value = self.lower_expecting(st, else_body, expected.or(then_ty))?;
+                let else_edge = st.take_edge(else_value);
+
+                Ok(self.join(st, vec![then_edge, else_edge]))
+            },
+            ASTNode::Declaration { name, type_, .. } => {
+                match &type_.0 {
+                    frontend::ParseType::Determined(t) => match self.type_registry.get(t).as_ref().and_then(scalar_type) {
+                        Some(ty) => st.annotations.insert(name.0.clone(), ty),
+                        None => st.annotations.remove(&name.0)
+                    },
+                    frontend::ParseType::Inferred => st.annotations.remove(&name.0)
+                };
+                st.declare(&name.0, None);
+                Ok(None)
+            },
+            ASTNode::DeclarationWithValue { name, value, type_, mutability } => {
+                let declared = match &type_.0 {
+                    frontend::ParseType::Determined(t) => self.type_registry.get(t).as_ref().and_then(scalar_type),
+                    frontend::ParseType::Inferred => None
+                };
+                let value = self.lower_expecting(st, value, declared)?;
+                if st.is_terminated() { return Ok(None) }
+                let value = self.expect_value(value, node.span)?;
+                let mut scalars = Vec::new();
+                value.scalars(&mut scalars);
+                let value = if self.stack_locals && *mutability && scalars.iter().all(|v| v.1 != types::VOID) {
+                    st.spill(&value)
+                } else {
+                    value
+                };
+                st.declare(&name.0, Some(value));
+                Ok(None)
+            },
+            ASTNode::Block(stmts) => {
+                st.scopes.push(HashMap::new());
+                let mut value = None;
+                for (i, stmt) in stmts.iter().enumerate() {
+                    let hint = if i + 1 == stmts.len() { expected } else { None };
+                    value = self.lower_expecting(st, stmt, hint)?;
+                }
+                st.scopes.pop();
+                Ok(value)
+            },
+            ASTNode::Statement(inner) => {
+                self.lower_node(st, inner)?;
+                Ok(None)
+            },
+            ASTNode::Mutation { name, value } => {
+                let target = match st.lookup(&name.0) {
+                    Some(Some(Value::Scalar(v))) => Some(st.homes.get(&v.0).copied().unwrap_or(v.1)),
+                    Some(None) => st.annotations.get(&name.0).copied(),
+                    _ => None
+                };
+                let value = self.lower_expecting(st, value, target)?;
+                if st.is_terminated() { return Ok(None) }
+                let value = self.expect_value(value, node.span)?;
+                if !st.write(&name.0, value) {
+                    return Err(self.error(
+                        ECode::UndefinedIdentifier,
+                        format!("cannot find `{}` in scope", name.0),
+                        name.1
+                    ))
+                }
+                Ok(None)
+            },
+            // Lowered on their own by `compile_module`
+            ASTNode::Function { .. } => Ok(None),
+            ASTNode::Call { callee, args } => {
+                // Builtins call into the runtime unless a `func` takes their name
+                let callee = match runtime::builtin(&callee.0) {
+                    Some(alias) if !self.signatures.contains_key(&callee.0) => &(alias.to_string(), callee.1),
+                    _ => callee
+                };
+                let sig = self.signatures.get(&callee.0).ok_or_else(|| self.error(
+                    ECode::UndefinedIdentifier,
+                    format!("cannot find function `{}` in scope", callee.0),
+                    callee.1
+                ))?;
+                let mut values: Vec<ValueID> = Vec::new();
+                let result_in_memory = self.returned_in_memory.get(&callee.0);
+                if let Some(t) = result_in_memory {
+                    values.push(self.stack_memory(st, t));
+                }
+                for arg in args {
+                    let value = self.lower_expecting(st, arg, sig.params.get(values.len()).copied())?;
+                    if st.is_terminated() { return Ok(None) }
+                    let value = self.expect_value(value, arg.span)?;
+                    values.extend(self.abi_values(st, value));
+                }
+                let results = st.ins().call(&callee.0, sig, &values);
+                if let Some(t) = result_in_memory {
+                    return Ok(Some(self.load_value(st, t, values[0], 0)))
+                }
+                Ok(match results[..] {
+                    [] => None,
+                    [result] => Some(Value::Scalar(result)),
+                    [address, len] => Some(Value::string(address, len)),
+                    _ => unreachable!("functions return a scalar or a string")
+                })
+            },
+            ASTNode::Return(value) => {
+                let value = match value {
+                    Some(value) => {
+                        let expected = Some(st.return_ty).filter(|t| *t != types::VOID);
+                        let lowered = self.lower_expecting(st, value, expected)?;
+                        if st.is_terminated() { return Ok(None) }
+                        if st.return_ty != types::VOID || st.sret.is_some() {
+                            self.expect_value(lowered.clone(), value.span)?;
+                        }
+                        lowered
+                    },
+                    None => None
+                };
+                self.ret(st, value);
+                Ok(None)
+            },
+            ASTNode::StructDecl { .. } => Ok(None),
+            ASTNode::StructLit { name, fields } => {
+                let mut values: HashMap<&str, Value> = HashMap::new();
+                for (field, value) in fields {
+                    let expected = self.type_registry
+                        .layout(&name.0)
+                        .and_then(|layout| layout.field(&field.0))
+                        .and_then(|(_, f)| scalar_type(&f.type_));
+                    let lowered = self.lower_expecting(st, value, expected)?;
+                    if st.is_terminated() { return Ok(None) }
+                    values.insert(&field.0, self.expect_value(lowered, value.span)?);
+                }
+
+                let layout = self.type_registry.layout(&name.0).ok_or_else(|| self.error(
+                    ECode::UndefinedIdentifier,
+                    format!("cannot find struct `{}` in scope", name.0),
+                    name.1
+                ))?;
+                let mut ordered: Vec<Value> = Vec::new();
+                for field in &layout.fields {
+                    ordered.push(values.remove(&*field.name).ok_or_else(|| self.error(
+                        ECode::MismatchedTypes,
+                        format!("missing field `{}` in initializer of `{}`", field.name, name.0),
+                        node.span
+                    ))?);
+                }
+                Ok(Some(Value::Struct(name.0.clone(), ordered)))
+            },
+            ASTNode::FieldAccess { object, field } => {
+                let value = self.lower_node(st, object)?;
+                if st.is_terminated() { return Ok(None) }
+                let value = self.expect_value(value, object.span)?;
+                let index = self.field_index(&value, field)?;
+                let Value::Struct(_, fields) = value else { unreachable!() };
+                Ok(Some(fields[index].clone()))
+            },
+            ASTNode::FieldMutation { name, fields, value } => {
+                let mut target = st.lookup(&name.0).flatten();
+                for field in fields {
+                    target = target.and_then(|t| {
+                        let index = self.field_index(&t, field).ok()?;
+                        let Value::Struct(_, fields) = t else { return None };
+                        fields.into_iter().nth(index)
+                    });
+                }
+                let target = match target {
+                    Some(Value::Scalar(v)) => Some(st.homes.get(&v.0).copied().unwrap_or(v.1)),
+                    _ => None
+                };
+                let value = self.lower_expecting(st, value, target)?;
+                if st.is_terminated() { return Ok(None) }
+                let value = self.expect_value(value, node.span)?;
+                let current = match st.lookup(&name.0) {
+                    Some(Some(current)) => st.read(current),
+                    _ => return Err(self.error(
+                        ECode::UndefinedIdentifier,
+                        format!("`{}` is used before being assigned", name.0),
+                        name.1
+                    ))
+                };
+                let updated = self.with_field(current, fields, value)?;
+                st.write(&name.0, updated);
+                Ok(None)
+            },
+            ASTNode::EnumDecl { .. } => Ok(None),
+            ASTNode::EnumVariant { enum_name, variant, args } => {
+                let payload: Vec<Option<Type>> = self.type_registry
+                    .enum_layout(&enum_name.0)
+                    .and_then(|layout| layout.variant(&variant.0))
+                    .map(|(_, v)| v.fields.iter().map(|f| scalar_type(&f.type_)).collect())
+                    .unwrap_or_default();
+                let mut values: Vec<Value> = Vec::new();
+                for (i, arg) in args.iter().enumerate() {
+                    let lowered = self.lower_expecting(st, arg, payload.get(i).copied().flatten())?;
+                    if st.is_terminated() { return Ok(None) }
+                    values.push(self.expect_value(lowered, arg.span)?);
+                }
+
+                let layout = self.type_registry.enum_layout(&enum_name.0).cloned().ok_or_else(|| self.error(
+                    ECode::UndefinedIdentifier,
+                    format!("cannot find enum `{}` in scope", enum_name.0),
+                    enum_name.1
+                ))?;
+                let index = layout.variant(&variant.0).map(|(i, _)| i).ok_or_else(|| self.error(
+                    ECode::UndefinedIdentifier,
+                    format!("no variant `{}` on enum `{}`", variant.0, enum_name.0),
+                    variant.1
+                ))?;
+
+                let mut parts = vec![Value::Scalar(st.ins().u32const(index as u32))];
+                for (i, v) in layout.variants.iter().enumerate() {
+                    if i == index {
+                        parts.append(&mut values);
+                        continue
+                    }
+                    for field in &v.fields {
+                        parts.push(self.zero_value(st, &field.type_, node.span)?);
+                    }
+                }
+                Ok(Some(Value::Enum(enum_name.0.clone(), parts)))
+            },
+            ASTNode::Match { scrutinee, arms } => {
+                let value = self.lower_node(st, scrutinee)?;
+                if st.is_terminated() { return Ok(None) }
+                let value = self.expect_value(value, scrutinee.span)?;
+                self.lower_match(st, value, arms, expected)
+            },
+            ASTNode::While { condition, body } => self.lower_loop(st, Some(condition), body),
+            ASTNode::Loop(body) => self.lower_loop(st, None, body),
+            ASTNode::Break(value) => {
+                let value = match value {
+                    Some(value) => {
+                        // Later breaks take the type of the first one
+                        let hint = st.loops.last().and_then(|frame| frame.breaks.iter().find_map(|edge| match edge.value {
+                            Some(Value::Scalar(v)) => Some(v.1),
+                            _ => None
+                        }));
+                        let lowered = self.lower_expecting(st, value, hint)?;
+                        if st.is_terminated() { return Ok(None) }
+                        lowered
+                    },
+                    None => None
+                };
+                st.break_loop(value);
+                Ok(None)
+            },
+            ASTNode::Continue => {
+                st.continue_loop();
+                Ok(None)
+            },
+            ASTNode::Cast { value, type_ } => {
+                let to = self.ir_type(type_)?;
+                let lowered = self.lower_node(st, value)?;
+                if st.is_terminated() { return Ok(None) }
+                let lowered = self.expect_value(lowered, value.span)?;
+                let lowered = self.expect_scalar(lowered, value.span)?;
+                Ok(Some(Value::Scalar(Self::lower_cast(st, lowered, to))))
+            }
+        }
+    }
+
+    /// Converts `value` to `to`, integers are extended by the signedness
+    /// of their own type
+    fn lower_cast(st: &mut FunctionState, value: ValueID, to: Type) -> ValueID {
+        let from = value.1;
+        let mut ins = st.ins();
+        if from == to {
+            value
+        } else if from.is_float() && to.is_float() {
+            ins.fcvt(value, to)
+        } else if from.is_float() {
+            if to.is_signed() { ins.ftosi(value, to) } else { ins.ftoui(value, to) }
+        } else if to.is_float() {
+            if from.is_signed() { ins.sitof(value, to) } else { ins.uitof(value, to) }
+        } else if to.bits() > from.bits() {
+            if from.is_signed() { ins.sext(value, to) } else { ins.uext(value, to) }
+        } else if to.bits() < from.bits() {
+            ins.trunc(value, to)
+        } else {
+            ins.bitcast(value, to)
+        }
+    }
+
+    fn lower_binop(&self, st: &mut FunctionState, op: &(String, Span), left: ValueID, right: ValueID) -> Result<ValueID, Error> {
+        let ty = left.1;
+        let mut ins = st.ins();
+        let value = match &*op.0 {
+            "+" if ty.is_int() => ins.iadd(left, right),
+            "+" if ty.is_float() => ins.fadd(left, right),
+            "-" if ty.is_int() => ins.isub(left, right),
+            "-" if ty.is_float() => ins.fsub(left, right),
+            "*" if ty.is_int() => ins.imul(left, right),
+            "*" if ty.is_float() => ins.fmul(left, right),
+            "/" if ty.is_signed() => ins.sdiv(left, right),
+            "/" if ty.is_unsigned() => ins.udiv(left, right),
+            "/" if ty.is_float() => ins.fdiv(left, right),
+            "%" if ty.is_signed() => ins.srem(left, right),
+            "%" if ty.is_unsigned() => ins.urem(left, right),
+            "%" if ty.is_float() => ins.frem(left, right),
+            "&" => ins.band(left, right),
+            "|" => ins.bor(left, right),
+            "^" => ins.bxor(left, right),
+            "<<" => ins.ishl(left, right),
+            ">>" if ty.is_signed() => ins.sshr(left, right),
+            ">>" => ins.ushr(left, right),
+            "==" | "!=" | ">" | "<" | ">=" | "<=" => {
+                let predicate = match (&*op.0, ty.is_unsigned()) {
+                    ("==", _) => CmpPred::eq(),
+                    ("!=", _) => CmpPred::ne(),
+                    (">", false) => CmpPred::sgt(),
+                    ("<", false) => CmpPred::slt(),
+                    (">=", false) => CmpPred::sge(),
+                    ("<=", false) => CmpPred::sle(),
+                    (">", true) => CmpPred::ugt(),
+                    ("<", true) => CmpPred::ult(),
+                    (">=", true) => CmpPred::uge(),
+                    (_, true) => CmpPred::ule(),
+                    _ => unreachable!()
+                };
+                if ty.is_float() {
+                    ins.fcmp(left, right, predicate)
+                } else {
+                    ins.icmp(left, right, predicate)
+                }
+            },
+            _ => return Err(self.error(
+                ECode::Unsupported,
+                format!("operator `{}` on type `{}` cannot be compiled yet", op.0, ty),
+                op.1
+            ))
+        };
+        Ok(value)
+    }
+
+    /// Operators on strings, which call into the string runtime
+    fn lower_string_binop(&self, st: &mut FunctionState, op: &(String, Span), left: Value, right: Value) -> Result<Value, Error> {
+        let mut args = Vec::new();
+        left.scalars(&mut args);
+        right.scalars(&mut args);
+        match &*op.0 {
+            "++" => {
+                let results = st.ins().call(runtime::STR_CONCAT, &self.signatures[runtime::STR_CONCAT], &args);
+                Ok(Value::string(results[0], results[1]))
+            },
+            "==" | "!=" => {
+                let equal = st.ins().call(runtime::STR_EQ, &self.signatures[runtime::STR_EQ], &args)[0];
+                if op.0 == "==" {
+                    Ok(Value::Scalar(equal))
+                } else {
+                    Ok(Value::Scalar(st.ins().bnot(equal)))
+                }
+            },
+            _ => Err(self.error(
+                ECode::Unsupported,
+                format!("operator `{}` on type `string` cannot be compiled yet", op.0),
+                op.1
+            ))
+        }
+    }
+
+    fn lower_unaop(&self, st: &mut FunctionState, op: &(String, Span), value: ValueID) -> Result<ValueID, Error> {
+        let ty = value.1;
+        let mut ins = st.ins();
+        let value = match &*op.0 {
+            "+" => value,
+            "-" if ty.is_int() => ins.ineg(value),
+            "-" if ty.is_float() => ins.fneg(value),
+            "!" => ins.bnot(value),
+            _ => return Err(self.error(
+                ECode::Unsupported,
+                format!("operator `{}` on type `{}` cannot be compiled yet", op.0, ty),
+                op.1
+            ))
+        };
+        Ok(value)
+    }
+
+    /// Merges `edges` into a new block, passing every binding whose value
+    /// differs between the edges (and the expression value, if all edges
+    /// produce one) as a block parameter
+    fn join(&self, st: &mut FunctionState, mut edges: Vec<Edge>) -> Option<Value> {
+        edges.retain(|edge| edge.block.is_some());
+        if edges.is_empty() {
+            return None
+        }
+        if edges.len() == 1 {
+            let edge = edges.pop().unwrap();
+            st.current = edge.block;
+            st.scopes = edge.scopes;
+            return edge.value
+        }
+
+        let mut block = st.builder.create_block();
+        let mut scopes = edges[0].scopes.clone();
+        let mut args: Vec<Vec<ValueID>> = vec![Vec::new(); edges.len()];
+
+        let values: Vec<Option<Value>> = edges.iter().map(|e| e.value.clone()).collect();
+        let value;
+        (block, value) = self.merge(block, &values, &mut args);
+
+        for (depth, scope) in scopes.iter_mut().enumerate() {
+            let mut names: Vec<String> = scope.keys().cloned().collect();
+            names.sort();
+            for name in names {
+                let values: Vec<Option<Value>> = edges
+                    .iter()
+                    .map(|e| e.scopes[depth].get(&name).cloned().flatten())
+                    .collect();
+                let merged;
+                (block, merged) = self.merge(block, &values, &mut args);
+                scope.insert(name, merged);
+            }
+        }
+
+        for (edge, args) in edges.into_iter().zip(args) {
+            let edge_block = edge.block.unwrap();
+            edge_block.ins().jmp(block.call(&args));
+            st.builder.eat_block(edge_block);
+        }
+        st.current = Some(block);
+        st.scopes = scopes;
+        value
+    }
+
+    /// Picks the value seen through a join: shared values pass straight
+    /// through, differing values of one type become new block parameters,
+    /// one per scalar field of a struct
+    fn merge(&self, block: BlockBuilder, values: &[Option<Value>], args: &mut [Vec<ValueID>]) -> (BlockBuilder, Option<Value>) {
+        let Some(Some(first)) = values.first() else { return (block, None) };
+        if values.iter().all(|v| v.as_ref() == Some(first)) {
+            return (block, Some(first.clone()))
+        }
+
+        match first {
+            Value::Struct(name, fields) | Value::Enum(name, fields) => {
+                let same_shape = values.iter().all(|v| match (v, first) {
+                    (Some(Value::Struct(n, _)), Value::Struct(..))
+                    | (Some(Value::Enum(n, _)), Value::Enum(..)) => n == name,
+                    _ => false
+                });
+                if !same_shape {
+                    return (block, None)
+                }
+                let mut block = block;
+                let mut merged: Vec<Value> = Vec::new();
+                for i in 0..fields.len() {
+                    let field_values: Vec<Option<Value>> = values
+                        .iter()
+                        .map(|v| match v {
+                            Some(Value::Struct(_, fields) | Value::Enum(_, fields)) => Some(fields[i].clone()),
+                            _ => None
+                        })
+                        .collect();
+                    let field;
+                    (block, field) = self.merge(block, &field_values, args);
+                    let Some(field) = field else { return (block, None) };
+                    merged.push(field);
+                }
+                let merged = match first {
+                    Value::Struct(..) => Value::Struct(name.clone(), merged),
+                    _ => Value::Enum(name.clone(), merged)
+                };
+                (block, Some(merged))
+            },
+            Value::Scalar(first) => {
+                let scalars: Option<Vec<ValueID>> = values
+                    .iter()
+                    .map(|v| match v {
+                        Some(Value::Scalar(v)) if v.1 == first.1 => Some(*v),
+                        _ => None
+                    })
+                    .collect();
+                let Some(scalars) = scalars else { return (block, None) };
+
+                let block = block.with_param(first.1);
+                for (args, value) in args.iter_mut().zip(scalars) {
+                    args.push(value);
+                }
+                let param = *block.params().last().unwrap();
+                (block, Some(Value::Scalar(param.into())))
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::backend::ir::entities::*;
+
+/// Successors and predecessors of every block of a function, together with
+/// the blocks reachable from the entry block in reverse postorder
+#[derive(Debug, Clone, Default)]
+pub struct ControlFlowGraph {
+    entry: Option<BlockID>,
+    successors: HashMap<BlockID, Vec<BlockID>>,
+    predecessors: HashMap<BlockID, Vec<BlockID>>,
+    reverse_postorder: Vec<BlockID>,
+    rpo_numbers: HashMap<BlockID, usize>
+}
+
+impl ControlFlowGraph {
+    pub fn new(function: &Function) -> Self {
+        let mut successors: HashMap<BlockID, Vec<BlockID>> = HashMap::new();
+        let mut predecessors: HashMap<BlockID, Vec<BlockID>> = HashMap::new();
+        for block in &function.blocks {
+            predecessors.entry(block.id).or_default();
+        }
+
+        for block in &function.blocks {
+            // A branch taking the same block on both paths is a single edge
+            let mut targets: Vec<BlockID> = Vec::new();
+            for call in block.insts.last().map(|inst| inst.targets()).unwrap_or_default() {
+                if !targets.contains(&call.block) {
+                    targets.push(call.block);
+                }
+            }
+            for target in &targets {
+                predecessors.entry(*target).or_default().push(block.id);
+            }
+            successors.insert(block.id, targets);
+        }
+
+        let entry = function.blocks.first().map(|block| block.id);
+        let mut visited: HashSet<BlockID> = HashSet::new();
+        let mut postorder: Vec<BlockID> = Vec::new();
+        let mut stack: Vec<(BlockID, Vec<BlockID>)> = Vec::new();
+        if let Some(entry) = entry {
+            visited.insert(entry);
+            stack.push((entry, successors[&entry].clone()));
+        }
+        while let Some((block, pending)) = stack.last_mut() {
+            if let Some(next) = pending.pop() {
+                // Calls to undefined blocks are left to the verifier
+                if let Some(targets) = successors.get(&next) {
+                    if visited.insert(next) {
+                        stack.push((next, targets.clone()));
+                    }
+                }
+            } else {
+                postorder.push(*block);
+                stack.pop();
+            }
+        }
+        postorder.reverse();
+
+        let rpo_numbers = postorder.iter().enumerate().map(|(i, b)| (*b, i)).collect();
+        Self {
+            entry,
+            successors,
+            predecessors,
+            reverse_postorder: postorder,
+            rpo_numbers
+        }
+    }
+
+    pub fn entry(&self) -> Option<BlockID> {
+        self.entry
+    }
+
+    pub fn successors(&self, block: BlockID) -> &[BlockID] {
+        self.successors.get(&block).map_or(&[], |s| s.as_slice())
+    }
+
+    /// Every block jumping to `block`, including unreachable ones
+    pub fn predecessors(&self, block: BlockID) -> &[BlockID] {
+        self.predecessors.get(&block).map_or(&[], |p| p.as_slice())
+    }
+
+    /// The blocks reachable from the entry block, each one after all of its
+    /// predecessors except along back edges
+    pub fn reverse_postorder(&self) -> &[BlockID] {
+        &self.reverse_postorder
+    }
+
+    /// Position of `block` in the reverse postorder, `None` if unreachable
+    pub fn rpo_number(&self, block: BlockID) -> Option<usize> {
+        self.rpo_numbers.get(&block).copied()
+    }
+
+    pub fn is_reachable(&self, block: BlockID) -> bool {
+        self.rpo_numbers.contains_key(&block)
+    }
+}
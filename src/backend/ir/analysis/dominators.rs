@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use crate::backend::ir::entities::*;
+
+use super::ControlFlowGraph;
+
+/// Immediate dominators of the reachable blocks, computed with the
+/// iterative algorithm of Cooper, Harvey and Kennedy, along with the
+/// dominance frontier of each block
+#[derive(Debug, Clone, Default)]
+pub struct DominatorTree {
+    idoms: HashMap<BlockID, BlockID>,
+    children: HashMap<BlockID, Vec<BlockID>>,
+    frontiers: HashMap<BlockID, Vec<BlockID>>,
+    entry: Option<BlockID>
+}
+
+impl DominatorTree {
+    pub fn new(cfg: &ControlFlowGraph) -> Self {
+        let rpo = cfg.reverse_postorder();
+        let Some(&entry) = rpo.first() else { return Self::default() };
+
+        // Immediate dominators by reverse postorder number, the entry is its own
+        let mut idom: Vec<Option<usize>> = vec![None; rpo.len()];
+        idom[0] = Some(0);
+        let intersect = |idom: &[Option<usize>], mut a: usize, mut b: usize| {
+            while a != b {
+                while a > b {
+                    a = idom[a].expect("processed blocks have a dominator");
+                }
+                while b > a {
+                    b = idom[b].expect("processed blocks have a dominator");
+                }
+            }
+            a
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, block) in rpo.iter().enumerate().skip(1) {
+                let mut new_idom: Option<usize> = None;
+                for pred in cfg.predecessors(*block) {
+                    let Some(p) = cfg.rpo_number(*pred) else { continue };
+                    if idom[p].is_none() {
+                        continue
+                    }
+                    new_idom = Some(match new_idom {
+                        Some(current) => intersect(&idom, p, current),
+                        None => p
+                    });
+                }
+                if new_idom != idom[i] {
+                    idom[i] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut idoms: HashMap<BlockID, BlockID> = HashMap::new();
+        let mut children: HashMap<BlockID, Vec<BlockID>> = HashMap::new();
+        for (i, block) in rpo.iter().enumerate().skip(1) {
+            if let Some(d) = idom[i] {
+                idoms.insert(*block, rpo[d]);
+                children.entry(rpo[d]).or_default().push(*block);
+            }
+        }
+
+        // A join point is in the frontier of every block between each of its
+        // predecessors and its immediate dominator
+        let mut frontiers: HashMap<BlockID, Vec<BlockID>> = HashMap::new();
+        for block in rpo {
+            let preds: Vec<BlockID> = cfg.predecessors(*block)
+                .iter()
+                .copied()
+                .filter(|p| cfg.is_reachable(*p))
+                .collect();
+            if preds.len() < 2 {
+                continue
+            }
+            let Some(&dominator) = idoms.get(block) else { continue };
+            for pred in preds {
+                let mut runner = pred;
+                while runner != dominator {
+                    let frontier = frontiers.entry(runner).or_default();
+                    if !frontier.contains(block) {
+                        frontier.push(*block);
+                    }
+                    match idoms.get(&runner) {
+                        Some(next) => runner = *next,
+                        None => break
+                    }
+                }
+            }
+        }
+
+        Self { idoms, children, frontiers, entry: Some(entry) }
+    }
+
+    /// The closest strict dominator of `block`, `None` for the entry block
+    /// and unreachable blocks
+    pub fn idom(&self, block: BlockID) -> Option<BlockID> {
+        self.idoms.get(&block).copied()
+    }
+
+    /// Whether every path from the entry to `b` goes through `a`, a block
+    /// dominates itself
+    pub fn dominates(&self, a: BlockID, b: BlockID) -> bool {
+        if !self.is_reachable(b) {
+            return false
+        }
+        let mut current = b;
+        loop {
+            if current == a {
+                return true
+            }
+            match self.idom(current) {
+                Some(next) => current = next,
+                None => return false
+            }
+        }
+    }
+
+    pub fn strictly_dominates(&self, a: BlockID, b: BlockID) -> bool {
+        a != b && self.dominates(a, b)
+    }
+
+    /// Blocks immediately dominated by `block`
+    pub fn children(&self, block: BlockID) -> &[BlockID] {
+        self.children.get(&block).map_or(&[], |c| c.as_slice())
+    }
+
+    /// Blocks where the dominance of `block` ends, the join points that
+    /// need block parameters for values defined in `block`
+    pub fn frontier(&self, block: BlockID) -> &[BlockID] {
+        self.frontiers.get(&block).map_or(&[], |f| f.as_slice())
+    }
+
+    pub fn root(&self) -> Option<BlockID> {
+        self.entry
+    }
+
+    fn is_reachable(&self, block: BlockID) -> bool {
+        self.entry == Some(block) || self.idoms.contains_key(&block)
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::backend::ir::entities::*;
+
+use super::{ControlFlowGraph, DominatorTree};
+
+/// A natural loop, the blocks that reach one of its back edges without
+/// going through the header
+#[derive(Debug, Clone, PartialEq)]
+pub struct Loop {
+    pub header: BlockID,
+    /// Blocks jumping back to the header
+    pub latches: Vec<BlockID>,
+    /// Every block of the loop, header first, in reverse postorder
+    pub blocks: Vec<BlockID>,
+    /// Index of the innermost loop containing this one
+    pub parent: Option<usize>
+}
+
+/// The natural loops of a function, outer loops before the loops they contain
+#[derive(Debug, Clone, Default)]
+pub struct LoopAnalysis {
+    loops: Vec<Loop>,
+    depths: HashMap<BlockID, usize>
+}
+
+impl LoopAnalysis {
+    pub fn new(cfg: &ControlFlowGraph, dominators: &DominatorTree) -> Self {
+        let mut loops: Vec<Loop> = Vec::new();
+        let mut bodies: Vec<HashSet<BlockID>> = Vec::new();
+
+        // Headers dominate their loops so they come first in reverse postorder,
+        // back edges sharing a header form a single loop
+        for header in cfg.reverse_postorder() {
+            let latches: Vec<BlockID> = cfg.predecessors(*header)
+                .iter()
+                .copied()
+                .filter(|pred| dominators.dominates(*header, *pred))
+                .collect();
+            if latches.is_empty() {
+                continue
+            }
+
+            let mut body: HashSet<BlockID> = HashSet::from([*header]);
+            let mut worklist = latches.clone();
+            while let Some(block) = worklist.pop() {
+                if body.insert(block) {
+                    worklist.extend(cfg.predecessors(block).iter().filter(|p| cfg.is_reachable(**p)));
+                }
+            }
+
+            let blocks = cfg.reverse_postorder()
+                .iter()
+                .copied()
+                .filter(|b| body.contains(b))
+                .collect();
+            let parent = bodies.iter().rposition(|outer| outer.contains(header));
+            loops.push(Loop { header: *header, latches, blocks, parent });
+            bodies.push(body);
+        }
+
+        let mut depths: HashMap<BlockID, usize> = HashMap::new();
+        for body in &bodies {
+            for block in body {
+                *depths.entry(*block).or_default() += 1;
+            }
+        }
+
+        Self { loops, depths }
+    }
+
+    pub fn loops(&self) -> &[Loop] {
+        &self.loops
+    }
+
+    /// Number of loops containing `block`, 0 outside of any loop
+    pub fn depth(&self, block: BlockID) -> usize {
+        self.depths.get(&block).copied().unwrap_or(0)
+    }
+
+    pub fn is_header(&self, block: BlockID) -> bool {
+        self.loops.iter().any(|l| l.header == block)
+    }
+
+    /// The innermost loop containing `block`
+    pub fn innermost(&self, block: BlockID) -> Option<&Loop> {
+        self.loops.iter().rev().find(|l| l.blocks.contains(&block))
+    }
+}
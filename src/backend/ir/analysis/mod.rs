@@ -0,0 +1,96 @@
+//! # Kese's IR's Analysis Module
+//! 
+//! ## Introduces:
+//! * Control Flow Graphs
+//! * Dominator Trees and Dominance Frontiers
+//! * Loop Detection
+//! 
+//! Analyses are cached per function by `AnalysisCache`, which recomputes
+//! them once a pass changes the blocks or the edges between them
+//! 
+
+use std::collections::HashMap;
+
+use crate::backend::ir::entities::*;
+
+pub mod cfg;
+pub mod dominators;
+pub mod loops;
+
+pub use cfg::*;
+pub use dominators::*;
+pub use loops::*;
+
+/// The analyses of one function, along with the edges they were computed from
+struct FunctionAnalyses {
+    edges: Vec<(BlockID, Vec<BlockID>)>,
+    cfg: ControlFlowGraph,
+    dominators: Option<DominatorTree>,
+    loops: Option<LoopAnalysis>
+}
+
+#[derive(Default)]
+pub struct AnalysisCache {
+    functions: HashMap<String, FunctionAnalyses>
+}
+
+impl AnalysisCache {
+    pub fn new() -> Self {
+        Self { functions: HashMap::new() }
+    }
+
+    /// Every analysis depends only on the blocks and their terminators'
+    /// targets, so changes anywhere else keep the cached results valid
+    fn edges(function: &Function) -> Vec<(BlockID, Vec<BlockID>)> {
+        function.blocks
+            .iter()
+            .map(|block| {
+                let targets = block.insts
+                    .last()
+                    .map(|inst| inst.targets().iter().map(|call| call.block).collect())
+                    .unwrap_or_default();
+                (block.id, targets)
+            })
+            .collect()
+    }
+
+    fn analyses(&mut self, function: &Function) -> &mut FunctionAnalyses {
+        let edges = Self::edges(function);
+        let stale = self.functions
+            .get(&function.alias)
+            .is_none_or(|cached| cached.edges != edges);
+        if stale {
+            self.functions.insert(function.alias.clone(), FunctionAnalyses {
+                edges,
+                cfg: ControlFlowGraph::new(function),
+                dominators: None,
+                loops: None
+            });
+        }
+        self.functions.get_mut(&function.alias).expect("analyses were just inserted")
+    }
+
+    pub fn cfg(&mut self, function: &Function) -> &ControlFlowGraph {
+        &self.analyses(function).cfg
+    }
+
+    pub fn dominators(&mut self, function: &Function) -> &DominatorTree {
+        let analyses = self.analyses(function);
+        analyses.dominators.get_or_insert_with(|| DominatorTree::new(&analyses.cfg))
+    }
+
+    pub fn loops(&mut self, function: &Function) -> &LoopAnalysis {
+        let analyses = self.analyses(function);
+        let dominators = analyses.dominators.get_or_insert_with(|| DominatorTree::new(&analyses.cfg));
+        analyses.loops.get_or_insert_with(|| LoopAnalysis::new(&analyses.cfg, dominators))
+    }
+
+    /// Drops the results for `function`
+    pub fn invalidate(&mut self, function: &str) {
+        self.functions.remove(function);
+    }
+
+    pub fn clear(&mut self) {
+        self.functions.clear();
+    }
+}
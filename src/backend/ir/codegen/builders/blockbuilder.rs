@@ -14,7 +14,7 @@ impl BlockBuilder {
     }
 
     pub fn id(&self) -> BlockID {
-        self.block.id.clone()
+        self.block.id
     }
     pub fn with_param(mut self, param: Type) -> Self {
         self.block.params.push(ParamID(*self.next_id.borrow(), param));
@@ -22,9 +22,12 @@ impl BlockBuilder {
         self
     }
     pub fn call(&self, args: &[ValueID]) -> BlockCall {
-        BlockCall { block: self.block.id.clone(), args: args.to_vec() }
+        BlockCall { block: self.block.id, args: args.to_vec() }
     }
     pub fn get_param(&self, index: usize) -> ParamID {
         self.block.params[index]
     }
+    pub fn params(&self) -> &[ParamID] {
+        &self.block.params
+    }
 }
\ No newline at end of file
@@ -1,13 +1,15 @@
 use crate::backend::ir::prelude::{Module, FunctionSignature};
 
-use super::super::super::entities::Function;
+use super::super::super::entities::{ExternFunction, Function, GlobalData, GlobalInit};
 use super::*;
 use std::{rc::Rc, cell::RefCell};
 
 #[derive(Debug)]
 pub struct Builder<'a> {
     pub(crate) module: &'a mut Module,
-    pub(crate) functions: Vec<Function>
+    pub(crate) functions: Vec<Function>,
+    pub(crate) externs: Vec<ExternFunction>,
+    pub(crate) globals: Vec<GlobalData>
 }
 
 impl<'a> Builder<'a> {
@@ -15,6 +17,8 @@ impl<'a> Builder<'a> {
         Self {
             module,
             functions: Vec::new(),
+            externs: Vec::new(),
+            globals: Vec::new()
         }
     }
 
@@ -23,18 +27,48 @@ impl<'a> Builder<'a> {
             function: Function {
                 alias: name.to_string(),
                 blocks: Vec::new(),
-                sig: sig.clone().into()
+                sig: sig.clone().into(),
+                stack_slots: Vec::new()
             },
             next_id: Rc::from(RefCell::new(sig.into().params.len())),
             next_block_id: 0usize,
         }
     }
 
+    /// Declares a function defined outside the module, callable like the module's own
+    pub fn declare_extern(&mut self, name: &str, sig: impl Into<FunctionSignature>) {
+        self.externs.push(ExternFunction { alias: name.to_string(), sig: sig.into() });
+    }
+
+    /// Defines global data holding `bytes`, its address is taken with `global_addr`
+    pub fn define_global(&mut self, name: &str, bytes: &[u8], mutable: bool, align: u32) {
+        self.globals.push(GlobalData { alias: name.to_string(), init: GlobalInit::Bytes(bytes.to_vec()), mutable, align });
+    }
+
+    /// Places `value` in read-only data and returns the alias of the global
+    /// holding it, strings with the same bytes share one global
+    pub fn intern_string(&mut self, value: &str) -> String {
+        let globals = self.module.globals.iter().chain(&self.globals);
+        if let Some(global) = globals.clone().find(|g| !g.mutable && matches!(&g.init, GlobalInit::Bytes(bytes) if bytes == value.as_bytes())) {
+            return global.alias.clone()
+        }
+
+        let taken: Vec<&str> = globals.map(|g| g.alias.as_str()).collect();
+        let alias = (0..)
+            .map(|i| format!("str{}", i))
+            .find(|alias| !taken.contains(&alias.as_str()))
+            .expect("there is always an unused alias");
+        self.define_global(&alias, value.as_bytes(), false, 1);
+        alias
+    }
+
     pub fn eat_function(&mut self, function: Function) {            
         self.functions.push(function);
     }
 
     pub fn build(self) {
-        self.module.functions.extend(self.functions)
+        self.module.functions.extend(self.functions);
+        self.module.externs.extend(self.externs);
+        self.module.globals.extend(self.globals)
     }
 }
\ No newline at end of file
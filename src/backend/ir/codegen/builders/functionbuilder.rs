@@ -24,6 +24,12 @@ impl FunctionBuilder {
         }
     }
     
+    /// Reserves `size` bytes of the stack frame aligned to `align`, a power of two
+    pub fn create_stack_slot(&mut self, size: u32, align: u32) -> StackSlot {
+        self.function.stack_slots.push(StackSlotData { size, align });
+        StackSlot(self.function.stack_slots.len() - 1)
+    }
+    
     pub fn eat_block(&mut self, block_builder: BlockBuilder) {
         let mut block = block_builder.block;
         block.insts = Rc::try_unwrap(block_builder.insts)
@@ -284,37 +284,37 @@ impl InstBuilder {
         self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::FRem { left: l, right: r }});
         value
     }
-    pub fn lsh(&mut self, n: ValueID) -> ValueID {
+    pub fn ishl(&mut self, l: ValueID, r: ValueID) -> ValueID {
         let id = {
             let mut next_id = self.next_id.borrow_mut();
             let id = *next_id;
             *next_id += 1;
             id
         };
-        let value = ValueID(id, n.1);
-        self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::Lsh(n)});
+        let value = ValueID(id, l.1);
+        self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::IShl { left: l, right: r }});
         value
     }
-    pub fn lrsh(&mut self, n: ValueID) -> ValueID {
+    pub fn ushr(&mut self, l: ValueID, r: ValueID) -> ValueID {
         let id = {
             let mut next_id = self.next_id.borrow_mut();
             let id = *next_id;
             *next_id += 1;
             id
         };
-        let value = ValueID(id, n.1);
-        self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::LRsh(n)});
+        let value = ValueID(id, l.1);
+        self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::UShr { left: l, right: r }});
         value
     }
-    pub fn arsh(&mut self, n: ValueID) -> ValueID {
+    pub fn sshr(&mut self, l: ValueID, r: ValueID) -> ValueID {
         let id = {
             let mut next_id = self.next_id.borrow_mut();
             let id = *next_id;
             *next_id += 1;
             id
         };
-        let value = ValueID(id, n.1);
-        self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::ARsh(n)});
+        let value = ValueID(id, l.1);
+        self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::SShr { left: l, right: r }});
         value
     }
     pub fn bnot(&mut self, n: ValueID) -> ValueID {
@@ -350,6 +350,17 @@ impl InstBuilder {
         self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::BAnd { left: l, right: r }});
         value
     }
+    pub fn bxor(&mut self, l: ValueID, r: ValueID) -> ValueID {
+        let id = {
+            let mut next_id = self.next_id.borrow_mut();
+            let id = *next_id;
+            *next_id += 1;
+            id
+        };
+        let value = ValueID(id, l.1);
+        self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::BXor { left: l, right: r }});
+        value
+    }
     pub fn ineg(&mut self, n: ValueID) -> ValueID {
         let id = {
             let mut next_id = self.next_id.borrow_mut();
@@ -394,9 +405,175 @@ impl InstBuilder {
         self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::FCmp { predicate, left: l, right: r }});
         value
     }
+    pub fn sext(&mut self, n: ValueID, ty: Type) -> ValueID {
+        let id = {
+            let mut next_id = self.next_id.borrow_mut();
+            let id = *next_id;
+            *next_id += 1;
+            id
+        };
+        let value = ValueID(id, ty);
+        self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::SExt(n, ty)});
+        value
+    }
+    pub fn uext(&mut self, n: ValueID, ty: Type) -> ValueID {
+        let id = {
+            let mut next_id = self.next_id.borrow_mut();
+            let id = *next_id;
+            *next_id += 1;
+            id
+        };
+        let value = ValueID(id, ty);
+        self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::UExt(n, ty)});
+        value
+    }
+    pub fn trunc(&mut self, n: ValueID, ty: Type) -> ValueID {
+        let id = {
+            let mut next_id = self.next_id.borrow_mut();
+            let id = *next_id;
+            *next_id += 1;
+            id
+        };
+        let value = ValueID(id, ty);
+        self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::Trunc(n, ty)});
+        value
+    }
+    pub fn bitcast(&mut self, n: ValueID, ty: Type) -> ValueID {
+        let id = {
+            let mut next_id = self.next_id.borrow_mut();
+            let id = *next_id;
+            *next_id += 1;
+            id
+        };
+        let value = ValueID(id, ty);
+        self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::Bitcast(n, ty)});
+        value
+    }
+    pub fn fcvt(&mut self, n: ValueID, ty: Type) -> ValueID {
+        let id = {
+            let mut next_id = self.next_id.borrow_mut();
+            let id = *next_id;
+            *next_id += 1;
+            id
+        };
+        let value = ValueID(id, ty);
+        self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::FCvt(n, ty)});
+        value
+    }
+    pub fn sitof(&mut self, n: ValueID, ty: Type) -> ValueID {
+        let id = {
+            let mut next_id = self.next_id.borrow_mut();
+            let id = *next_id;
+            *next_id += 1;
+            id
+        };
+        let value = ValueID(id, ty);
+        self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::SIToF(n, ty)});
+        value
+    }
+    pub fn uitof(&mut self, n: ValueID, ty: Type) -> ValueID {
+        let id = {
+            let mut next_id = self.next_id.borrow_mut();
+            let id = *next_id;
+            *next_id += 1;
+            id
+        };
+        let value = ValueID(id, ty);
+        self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::UIToF(n, ty)});
+        value
+    }
+    pub fn ftosi(&mut self, n: ValueID, ty: Type) -> ValueID {
+        let id = {
+            let mut next_id = self.next_id.borrow_mut();
+            let id = *next_id;
+            *next_id += 1;
+            id
+        };
+        let value = ValueID(id, ty);
+        self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::FToSI(n, ty)});
+        value
+    }
+    pub fn ftoui(&mut self, n: ValueID, ty: Type) -> ValueID {
+        let id = {
+            let mut next_id = self.next_id.borrow_mut();
+            let id = *next_id;
+            *next_id += 1;
+            id
+        };
+        let value = ValueID(id, ty);
+        self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::FToUI(n, ty)});
+        value
+    }
+    pub fn stack_addr(&mut self, slot: StackSlot) -> ValueID {
+        let id = {
+            let mut next_id = self.next_id.borrow_mut();
+            let id = *next_id;
+            *next_id += 1;
+            id
+        };
+        let value = ValueID(id, Type::Ptr);
+        self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::StackAddr(slot) });
+        value
+    }
+    /// Address of the module's global data called `alias`
+    pub fn global_addr(&mut self, alias: &str) -> ValueID {
+        let id = {
+            let mut next_id = self.next_id.borrow_mut();
+            let id = *next_id;
+            *next_id += 1;
+            id
+        };
+        let value = ValueID(id, Type::Ptr);
+        self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::GlobalAddr(alias.to_string()) });
+        value
+    }
+    /// Loads a `ty` from `address`, which is aligned to the size of `ty`
+    pub fn load(&mut self, ty: Type, address: ValueID) -> ValueID {
+        self.load_aligned(ty, address, ty.bytes())
+    }
+    pub fn load_aligned(&mut self, ty: Type, address: ValueID, align: u32) -> ValueID {
+        let id = {
+            let mut next_id = self.next_id.borrow_mut();
+            let id = *next_id;
+            *next_id += 1;
+            id
+        };
+        let value = ValueID(id, ty);
+        self.block.borrow_mut().push(Inst::Assign { dest: value, op: Op::Load { ty, address, align } });
+        value
+    }
+    /// Stores `value` at `address`, which is aligned to the size of the value
+    pub fn store(&mut self, value: ValueID, address: ValueID) {
+        self.store_aligned(value, address, value.1.bytes());
+    }
+    pub fn store_aligned(&mut self, value: ValueID, address: ValueID, align: u32) {
+        self.block.borrow_mut().push(Inst::Store { value, address, align });
+    }
+
+    /// Calls `callee`, which has the signature `sig`, and returns one value per returned type
+    pub fn call(&mut self, callee: &str, sig: &FunctionSignature, args: &[ValueID]) -> Vec<ValueID> {
+        let results: Vec<ValueID> = sig.returns
+            .iter()
+            .map(|ty| {
+                let mut next_id = self.next_id.borrow_mut();
+                let id = *next_id;
+                *next_id += 1;
+                ValueID(id, *ty)
+            })
+            .collect();
+        self.block.borrow_mut().push(Inst::Call { results: results.clone(), callee: callee.to_string(), args: args.to_vec() });
+        results
+    }
+
     pub fn ret<V: Into<ValueID>>(&mut self, value: V) {
         let value_id: ValueID = value.into();
-        self.block.borrow_mut().push(Inst::Ret(value_id));
+        self.block.borrow_mut().push(Inst::Ret(vec![value_id]));
+    }
+    pub fn ret_values(&mut self, values: &[ValueID]) {
+        self.block.borrow_mut().push(Inst::Ret(values.to_vec()));
+    }
+    pub fn ret_void(&mut self) {
+        self.block.borrow_mut().push(Inst::Ret(Vec::new()));
     }
     pub fn jmp(&mut self, id: BlockCall) {
         self.block.borrow_mut().push(Inst::Jmp(id));
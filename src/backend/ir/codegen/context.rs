@@ -1,4 +1,5 @@
-use crate::backend::ir::{entities::{BlockID, ValueID}, inst::BlockCall};
+use crate::backend::ir::{entities::{BlockID, ExternFunction, FunctionSignature, GlobalData, StackSlot, ValueID}, inst::BlockCall, parser::IRParser};
+use crate::global::Error;
 
 use super::{builders::Builder, super::entities::Function};
 
@@ -10,7 +11,9 @@ pub struct Context {
 #[derive(Debug, Clone)]
 pub struct Module {
     name: String,
-    pub(crate) functions: Vec<Function>
+    pub(crate) functions: Vec<Function>,
+    pub(crate) externs: Vec<ExternFunction>,
+    pub(crate) globals: Vec<GlobalData>
 }
 
 impl Context {
@@ -21,40 +24,82 @@ impl Context {
     pub fn create_module(&mut self, name: &str) -> &mut Module {
         self.modules.push(Module {
             name: name.to_string(),
-            functions: Vec::new()
+            functions: Vec::new(),
+            externs: Vec::new(),
+            globals: Vec::new()
         });
         self.modules.last_mut().unwrap()
     }
 
+    /// Creates a module from textual IR, as printed by `Module::display`
+    pub fn parse_module(&mut self, name: &str, src: &str, path: &str) -> Result<&mut Module, Error> {
+        let (externs, globals, functions) = IRParser::new(src, path).parse()?;
+        let module = self.create_module(name);
+        module.functions = functions;
+        module.externs = externs;
+        module.globals = globals;
+        Ok(module)
+    }
+
     pub fn modules(&self) -> Vec<Module> {
         self.modules.clone()
     }
 }
 
 impl Module {
-    pub fn builder(&mut self) -> Builder {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Signature of the function or extern called `alias`
+    pub fn signature(&self, alias: &str) -> Option<&FunctionSignature> {
+        self.functions
+            .iter()
+            .map(|f| (&f.alias, &f.sig))
+            .chain(self.externs.iter().map(|e| (&e.alias, &e.sig)))
+            .find_map(|(name, sig)| (name == alias).then_some(sig))
+    }
+
+    /// The global data called `alias`
+    pub fn global(&self, alias: &str) -> Option<&GlobalData> {
+        self.globals.iter().find(|g| g.alias == alias)
+    }
+
+    pub fn builder(&mut self) -> Builder<'_> {
         Builder::new(self)
     }
 
     pub fn display(&self) -> String {
         let mut output: String = String::new();
-        for function in &self.functions {
+        for external in &self.externs {
+            output.push_str(&format!("{}\n", external));
+        }
+        for global in &self.globals {
+            output.push_str(&format!("{}\n", global));
+        }
+        for (i, function) in self.functions.iter().enumerate() {
+            if i > 0 {
+                output.push('\n');
+            }
             let mut args = String::new();
             let params = function.sig.params.clone();
             for (id, arg_type) in params.iter().enumerate() {
                 args.push_str(&format!("{} %{}", arg_type, id));
-                if id <= params.len() {
+                if id + 1 < params.len() {
                     args.push_str(", ")
                 }
             }
-            output.push_str(&format!("func {} : @{}({}) {{\n", function.sig.return_ty, function.alias, args));
+            output.push_str(&format!("func {} : @{}({}) {{\n", function.sig.display_returns(), function.alias, args));
+            for (id, slot) in function.stack_slots.iter().enumerate() {
+                output.push_str(&format!("  {} = {}\n", StackSlot(id), slot));
+            }
             for block in &function.blocks {
                 output.push_str(&format!("{}({}):\n", block.id, block.params.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(", ")));
                 for inst in &block.insts {
                     output.push_str(&format!("  {}\n", inst))
                 }
             }
-            output.push_str("}");
+            output.push('}');
         }
 
         output
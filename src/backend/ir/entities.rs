@@ -29,6 +29,51 @@ impl Const {
         }
     }
 
+    pub fn ty(&self) -> Type {
+        match self {
+            Self::I8(_) => Type::I8,
+            Self::I16(_) => Type::I16,
+            Self::I32(_) => Type::I32,
+            Self::I64(_) => Type::I64,
+            Self::U8(_) => Type::U8,
+            Self::U16(_) => Type::U16,
+            Self::U32(_) => Type::U32,
+            Self::U64(_) => Type::U64,
+            Self::F32(_) => Type::F32,
+            Self::F64(_) => Type::F64,
+            Self::Bool(_) => Type::Bool,
+            Self::Void => Type::Void,
+        }
+    }
+
+    /// The zero of `ty`, `None` for `ptr` which has no constants
+    pub fn zero(ty: Type) -> Option<Self> {
+        Some(match ty {
+            Type::I8 => Self::I8(0),
+            Type::I16 => Self::I16(0),
+            Type::I32 => Self::I32(0),
+            Type::I64 => Self::I64(0),
+            Type::U8 => Self::U8(0),
+            Type::U16 => Self::U16(0),
+            Type::U32 => Self::U32(0),
+            Type::U64 => Self::U64(0),
+            Type::F32 => Self::F32(0.0),
+            Type::F64 => Self::F64(0.0),
+            Type::Bool => Self::Bool(0),
+            Type::Void => Self::Void,
+            Type::Ptr => return None,
+        })
+    }
+
+    /// Whether the constant is an integer zero
+    pub fn is_zero(&self) -> bool {
+        matches!(
+            self,
+            Self::I8(0) | Self::I16(0) | Self::I32(0) | Self::I64(0)
+            | Self::U8(0) | Self::U16(0) | Self::U32(0) | Self::U64(0)
+        )
+    }
+
     pub(crate) fn get_value(&self) -> String {
         match self {
             Self::I8(i) => i.to_string(),
@@ -59,9 +104,46 @@ pub enum Type {
     U8, U16, U32, U64,
     F32, F64,
     Bool,
+    /// An address, 64 bits wide like the pointers of every target the
+    /// lowerer supports
+    Ptr,
     Void,
 }
 
+impl Type {
+    pub fn is_int(&self) -> bool {
+        self.is_signed() || self.is_unsigned()
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(self, Self::I8 | Self::I16 | Self::I32 | Self::I64)
+    }
+
+    pub fn is_unsigned(&self) -> bool {
+        matches!(self, Self::U8 | Self::U16 | Self::U32 | Self::U64)
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, Self::F32 | Self::F64)
+    }
+
+    /// Width of the type in bits, `bool` counts as a byte and `void` as nothing
+    pub fn bits(&self) -> u32 {
+        match self {
+            Self::I8 | Self::U8 | Self::Bool => 8,
+            Self::I16 | Self::U16 => 16,
+            Self::I32 | Self::U32 | Self::F32 => 32,
+            Self::I64 | Self::U64 | Self::F64 | Self::Ptr => 64,
+            Self::Void => 0,
+        }
+    }
+
+    /// Size of the type in memory, also its natural alignment
+    pub fn bytes(&self) -> u32 {
+        self.bits() / 8
+    }
+}
+
 impl fmt::Display for Type {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -76,6 +158,7 @@ impl fmt::Display for Type {
             Self::F32 => write!(f, "f32"),
             Self::F64 => write!(f, "f64"),
             Self::Bool => write!(f, "bool"),
+            Self::Ptr => write!(f, "ptr"),
             Self::Void => write!(f, "void"),
         }
     }
@@ -111,24 +194,50 @@ impl From<ValueID> for ParamID {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Function {
     pub(crate) alias: String,
     pub(crate) blocks: Vec<Block>,
-    pub(crate) sig: FunctionSignature
+    pub(crate) sig: FunctionSignature,
+    /// Indexed by `StackSlot`
+    pub(crate) stack_slots: Vec<StackSlotData>
+}
+
+/// A region of the function's stack frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StackSlot(pub usize);
+
+impl fmt::Display for StackSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ss{}", self.0)
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackSlotData {
+    pub size: u32,
+    /// A power of two
+    pub align: u32
+}
+
+impl fmt::Display for StackSlotData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "stack_slot {} align {}", self.size, self.align)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Block {
     pub(crate) id: BlockID,
     pub(crate) insts: Vec<Inst>,
     pub(crate) params: Vec<ParamID>
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct FunctionSignature {
     pub(crate) params: Vec<Type>,
-    pub(crate) return_ty: Type
+    /// Empty for functions returning `void`
+    pub(crate) returns: Vec<Type>
 }
 
 impl From<&FunctionSignature> for FunctionSignature {
@@ -147,7 +256,7 @@ impl FunctionSignature {
     pub fn new() -> Self {
         Self {
             params: Vec::new(),
-            return_ty: Type::Void
+            returns: Vec::new()
         }
     }
 
@@ -161,13 +270,91 @@ impl FunctionSignature {
         self
     }
 
+    /// Returns a single value, or nothing for `void`
     pub fn with_return_ty(mut self, return_ty: Type) -> Self {
-        self.return_ty = return_ty;
+        self.returns = if return_ty == Type::Void { Vec::new() } else { vec![return_ty] };
         self
     }
+
+    pub fn with_returns(mut self, returns: Vec<Type>) -> Self {
+        self.returns = returns;
+        self
+    }
+
+    /// The returned types as written in textual IR, `void` when there are none
+    pub fn display_returns(&self) -> String {
+        if self.returns.is_empty() {
+            Type::Void.to_string()
+        } else {
+            self.returns.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(", ")
+        }
+    }
+}
+
+/// A function defined outside the module, resolved when linking
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExternFunction {
+    pub(crate) alias: String,
+    pub(crate) sig: FunctionSignature
+}
+
+impl fmt::Display for ExternFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let params: Vec<String> = self.sig.params.iter().map(|t| t.to_string()).collect();
+        write!(f, "extern {} : @{}({})", self.sig.display_returns(), self.alias, params.join(", "))
+    }
 }
 
+/// The initial contents of global data
 #[derive(Debug, Clone, PartialEq)]
+pub enum GlobalInit {
+    Bytes(Vec<u8>),
+    /// This many zero bytes, written `zeroed <size>`, only the size is kept
+    Zeroed(u32)
+}
+
+/// Bytes placed in the object file, read-only data goes into `.rodata`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobalData {
+    pub(crate) alias: String,
+    pub(crate) init: GlobalInit,
+    pub(crate) mutable: bool,
+    /// A power of two
+    pub(crate) align: u32
+}
+
+impl GlobalData {
+    pub fn size(&self) -> usize {
+        match &self.init {
+            GlobalInit::Bytes(bytes) => bytes.len(),
+            GlobalInit::Zeroed(size) => *size as usize
+        }
+    }
+}
+
+impl fmt::Display for GlobalData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.mutable { "mutable" } else { "readonly" };
+        let data = match &self.init {
+            GlobalInit::Bytes(data) => data,
+            GlobalInit::Zeroed(size) => {
+                return write!(f, "global @{} = {} zeroed {} align {}", self.alias, kind, size, self.align)
+            }
+        };
+
+        // Printable ASCII besides `"` and `\` is kept as is, every other byte is written `\xx` in hex
+        let mut bytes = String::new();
+        for byte in data {
+            match byte {
+                0x20..=0x7e if *byte != b'"' && *byte != b'\\' => bytes.push(*byte as char),
+                _ => bytes.push_str(&format!("\\{:02x}", byte)),
+            }
+        }
+        write!(f, "global @{} = {} \"{}\" align {}", self.alias, kind, bytes, self.align)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct BlockID(pub usize);
 
 impl fmt::Display for BlockID {
@@ -5,6 +5,7 @@ use super::entities::*;
 #[derive(Debug, Clone, PartialEq)]
 pub enum Op {
     Const(Const),
+    /// Integer arithmetic wraps around in two's complement
     IAdd {
         left: ValueID, right: ValueID
     },
@@ -14,6 +15,8 @@ pub enum Op {
     IMul {
         left: ValueID, right: ValueID
     },
+    /// Division and remainder trap on a zero divisor, `sdiv` also traps
+    /// on `MIN / -1` while `srem` gives 0 there
     SDiv {
         left: ValueID, right: ValueID
     },
@@ -41,7 +44,16 @@ pub enum Op {
     FRem {
         left: ValueID, right: ValueID
     },
-    Lsh(ValueID), LRsh(ValueID), ARsh(ValueID),
+    /// Shifts take the amount modulo the bit width of the shifted type
+    IShl {
+        left: ValueID, right: ValueID
+    },
+    UShr {
+        left: ValueID, right: ValueID
+    },
+    SShr {
+        left: ValueID, right: ValueID
+    },
     BNot(ValueID), 
     BOr {
         left: ValueID, right: ValueID
@@ -49,10 +61,24 @@ pub enum Op {
     BAnd {
         left: ValueID, right: ValueID
     },
+    BXor {
+        left: ValueID, right: ValueID
+    },
     INeg(ValueID),
     FNeg(ValueID),
     ICmp { predicate: CmpPred, left: ValueID, right: ValueID },
     FCmp { predicate: CmpPred, left: ValueID, right: ValueID },
+    /// Conversions carry the type they produce
+    SExt(ValueID, Type), UExt(ValueID, Type), Trunc(ValueID, Type),
+    Bitcast(ValueID, Type), FCvt(ValueID, Type),
+    SIToF(ValueID, Type), UIToF(ValueID, Type),
+    FToSI(ValueID, Type), FToUI(ValueID, Type),
+    /// Address of a stack slot of the current function
+    StackAddr(StackSlot),
+    /// Reads a `ty` from memory, `align` is a power of two the address is known to be a multiple of
+    Load { ty: Type, address: ValueID, align: u32 },
+    /// Address of the module's global data called by the alias
+    GlobalAddr(String),
 }
 
 impl fmt::Display for Op {
@@ -73,18 +99,121 @@ impl fmt::Display for Op {
             Self::FDiv { left, right } => write!(f, "fdiv {} {}", left, right),
             Self::FRem { left, right } => write!(f, "frem {} {}", left, right),
 
-            Self::Lsh(i) => write!(f, "lsh {}", i),
-            Self::LRsh(i) => write!(f, "lrsh {}", i),
-            Self::ARsh(i) => write!(f, "arsh {}", i),
+            Self::IShl { left, right } => write!(f, "ishl {} {}", left, right),
+            Self::UShr { left, right } => write!(f, "ushr {} {}", left, right),
+            Self::SShr { left, right } => write!(f, "sshr {} {}", left, right),
             Self::BNot(i) => write!(f, "bnot {}", i),
             Self::BOr { left, right } => write!(f, "bor {} {}", left, right),
             Self::BAnd { left, right } => write!(f, "band {} {}", left, right),
+            Self::BXor { left, right } => write!(f, "bxor {} {}", left, right),
             Self::INeg(i) => write!(f, "ineg {}", i),
             Self::FNeg(i) => write!(f, "fneg {}", i),
 
             Self::ICmp { predicate, left, right } => write!(f, "icmp {} {} {}", predicate, left, right),
             Self::FCmp { predicate, left, right } => write!(f, "fcmp {} {} {}", predicate, left, right),
-            _ => todo!()
+
+            Self::SExt(n, ty) => write!(f, "sext {} {}", ty, n),
+            Self::UExt(n, ty) => write!(f, "uext {} {}", ty, n),
+            Self::Trunc(n, ty) => write!(f, "trunc {} {}", ty, n),
+            Self::Bitcast(n, ty) => write!(f, "bitcast {} {}", ty, n),
+            Self::FCvt(n, ty) => write!(f, "fcvt {} {}", ty, n),
+            Self::SIToF(n, ty) => write!(f, "sitof {} {}", ty, n),
+            Self::UIToF(n, ty) => write!(f, "uitof {} {}", ty, n),
+            Self::FToSI(n, ty) => write!(f, "ftosi {} {}", ty, n),
+            Self::FToUI(n, ty) => write!(f, "ftoui {} {}", ty, n),
+
+            Self::StackAddr(slot) => write!(f, "stack_addr {}", slot),
+            Self::Load { ty, address, align } => write!(f, "load {} {} align {}", ty, address, align),
+            Self::GlobalAddr(alias) => write!(f, "global_addr @{}", alias),
+        }
+    }
+}
+
+impl Op {
+    /// Values read by the operation
+    pub fn operands(&self) -> Vec<ValueID> {
+        match self {
+            Self::Const(_) | Self::StackAddr(_) | Self::GlobalAddr(_) => vec![],
+            Self::IAdd { left, right } | Self::ISub { left, right }
+            | Self::IMul { left, right } | Self::SDiv { left, right }
+            | Self::UDiv { left, right } | Self::SRem { left, right }
+            | Self::URem { left, right } | Self::FAdd { left, right }
+            | Self::FSub { left, right } | Self::FMul { left, right }
+            | Self::FDiv { left, right } | Self::FRem { left, right }
+            | Self::BOr { left, right } | Self::BAnd { left, right } | Self::BXor { left, right }
+            | Self::IShl { left, right } | Self::UShr { left, right } | Self::SShr { left, right }
+            | Self::ICmp { left, right, .. } | Self::FCmp { left, right, .. } => vec![*left, *right],
+            Self::BNot(n) | Self::INeg(n) | Self::FNeg(n) => vec![*n],
+            Self::SExt(n, _) | Self::UExt(n, _) | Self::Trunc(n, _)
+            | Self::Bitcast(n, _) | Self::FCvt(n, _) | Self::SIToF(n, _)
+            | Self::UIToF(n, _) | Self::FToSI(n, _) | Self::FToUI(n, _) => vec![*n],
+            Self::Load { address, .. } => vec![*address],
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut ValueID> {
+        match self {
+            Self::Const(_) | Self::StackAddr(_) | Self::GlobalAddr(_) => vec![],
+            Self::IAdd { left, right } | Self::ISub { left, right }
+            | Self::IMul { left, right } | Self::SDiv { left, right }
+            | Self::UDiv { left, right } | Self::SRem { left, right }
+            | Self::URem { left, right } | Self::FAdd { left, right }
+            | Self::FSub { left, right } | Self::FMul { left, right }
+            | Self::FDiv { left, right } | Self::FRem { left, right }
+            | Self::BOr { left, right } | Self::BAnd { left, right } | Self::BXor { left, right }
+            | Self::IShl { left, right } | Self::UShr { left, right } | Self::SShr { left, right }
+            | Self::ICmp { left, right, .. } | Self::FCmp { left, right, .. } => vec![left, right],
+            Self::BNot(n) | Self::INeg(n) | Self::FNeg(n) => vec![n],
+            Self::SExt(n, _) | Self::UExt(n, _) | Self::Trunc(n, _)
+            | Self::Bitcast(n, _) | Self::FCvt(n, _) | Self::SIToF(n, _)
+            | Self::UIToF(n, _) | Self::FToSI(n, _) | Self::FToUI(n, _) => vec![n],
+            Self::Load { address, .. } => vec![address],
+        }
+    }
+
+    /// Type of the value produced by the operation, derived from its operands
+    pub fn result_type(&self) -> Type {
+        match self {
+            Self::Const(c) => c.ty(),
+            Self::ICmp { .. } | Self::FCmp { .. } => Type::Bool,
+            Self::StackAddr(_) | Self::GlobalAddr(_) => Type::Ptr,
+            Self::Load { ty, .. } => *ty,
+            _ => match self.conversion() {
+                Some((_, ty)) => ty,
+                None => self.operands()[0].1
+            },
+        }
+    }
+
+    /// The converted value and the type it is converted to
+    pub fn conversion(&self) -> Option<(ValueID, Type)> {
+        match self {
+            Self::SExt(n, ty) | Self::UExt(n, ty) | Self::Trunc(n, ty)
+            | Self::Bitcast(n, ty) | Self::FCvt(n, ty) | Self::SIToF(n, ty)
+            | Self::UIToF(n, ty) | Self::FToSI(n, ty) | Self::FToUI(n, ty) => Some((*n, *ty)),
+            _ => None
+        }
+    }
+
+    /// Whether a conversion may turn a value of type `from` into `to`,
+    /// extensions widen, truncations narrow and bitcasts keep the width.
+    /// Bitcasts also go between `ptr` and 64 bit integers, for address arithmetic
+    pub fn converts(&self, from: Type, to: Type) -> bool {
+        let int = |t: Type| t.is_int() || t == Type::Bool;
+        match self {
+            Self::SExt(..) => from.is_signed() && to.is_int() && to.bits() > from.bits(),
+            Self::UExt(..) => (from.is_unsigned() || from == Type::Bool) && to.is_int() && to.bits() > from.bits(),
+            Self::Trunc(..) => from.is_int() && to.is_int() && to.bits() < from.bits(),
+            Self::Bitcast(..) if from == Type::Ptr || to == Type::Ptr => {
+                (from.is_int() || to.is_int()) && to.bits() == from.bits()
+            },
+            Self::Bitcast(..) => int(from) && to.is_int() && to.bits() == from.bits() && from != to,
+            Self::FCvt(..) => from.is_float() && to.is_float() && from != to,
+            Self::SIToF(..) => from.is_signed() && to.is_float(),
+            Self::UIToF(..) => from.is_unsigned() && to.is_float(),
+            Self::FToSI(..) => from.is_float() && to.is_signed(),
+            Self::FToUI(..) => from.is_float() && to.is_unsigned(),
+            _ => false
         }
     }
 }
@@ -139,7 +268,13 @@ impl fmt::Display for BlockCall {
 #[derive(Debug, Clone, PartialEq)]
 pub enum Inst {
     Assign { dest: ValueID, op: Op },
-    Ret(ValueID),
+    /// Writes `value` to memory, `align` as for `Op::Load`
+    Store { value: ValueID, address: ValueID, align: u32 },
+    /// Calls a function of the module or an extern by its alias, defining
+    /// one result per returned value
+    Call { results: Vec<ValueID>, callee: String, args: Vec<ValueID> },
+    /// Returns every value the signature returns, none from `void` functions
+    Ret(Vec<ValueID>),
     Jmp(BlockCall),
     Branch {
         condition: ValueID,
@@ -148,11 +283,89 @@ pub enum Inst {
     }
 }
 
+impl Inst {
+    /// Values read by the instruction, including block call arguments
+    pub fn uses(&self) -> Vec<ValueID> {
+        match self {
+            Self::Assign { op, .. } => op.operands(),
+            Self::Store { value, address, .. } => vec![*value, *address],
+            Self::Call { args, .. } => args.clone(),
+            Self::Ret(values) => values.clone(),
+            Self::Jmp(call) => call.args.clone(),
+            Self::Branch { condition, true_path, false_path } => {
+                let mut uses = vec![*condition];
+                uses.extend(true_path.args.iter().copied());
+                uses.extend(false_path.args.iter().copied());
+                uses
+            }
+        }
+    }
+
+    pub fn uses_mut(&mut self) -> Vec<&mut ValueID> {
+        match self {
+            Self::Assign { op, .. } => op.operands_mut(),
+            Self::Store { value, address, .. } => vec![value, address],
+            Self::Call { args, .. } => args.iter_mut().collect(),
+            Self::Ret(values) => values.iter_mut().collect(),
+            Self::Jmp(call) => call.args.iter_mut().collect(),
+            Self::Branch { condition, true_path, false_path } => {
+                let mut uses = vec![condition];
+                uses.extend(true_path.args.iter_mut());
+                uses.extend(false_path.args.iter_mut());
+                uses
+            }
+        }
+    }
+
+    pub fn is_terminator(&self) -> bool {
+        !matches!(self, Self::Assign { .. } | Self::Store { .. } | Self::Call { .. })
+    }
+
+    /// Values defined by the instruction
+    pub fn results(&self) -> Vec<ValueID> {
+        match self {
+            Self::Assign { dest, .. } => vec![*dest],
+            Self::Call { results, .. } => results.clone(),
+            _ => vec![]
+        }
+    }
+
+    /// Block calls made by a terminator
+    pub fn targets(&self) -> Vec<&BlockCall> {
+        match self {
+            Self::Jmp(call) => vec![call],
+            Self::Branch { true_path, false_path, .. } => vec![true_path, false_path],
+            _ => vec![]
+        }
+    }
+    pub fn targets_mut(&mut self) -> Vec<&mut BlockCall> {
+        match self {
+            Self::Jmp(call) => vec![call],
+            Self::Branch { true_path, false_path, .. } => vec![true_path, false_path],
+            _ => vec![]
+        }
+    }
+}
+
 impl fmt::Display for Inst {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Assign { dest, op } => write!(f, "{} = {}", dest, op),
-            Self::Ret(r) => write!(f, "ret {}", r),
+            Self::Store { value, address, align } => write!(f, "store {} {} align {}", value, address, align),
+            Self::Call { results, callee, args } => {
+                let list = |values: &[ValueID]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ");
+                let types = if results.is_empty() {
+                    Type::Void.to_string()
+                } else {
+                    results.iter().map(|v| v.1.to_string()).collect::<Vec<_>>().join(", ")
+                };
+                if !results.is_empty() {
+                    write!(f, "{} = ", list(results))?;
+                }
+                write!(f, "call {} @{}({})", types, callee, list(args))
+            },
+            Self::Ret(values) if values.is_empty() => write!(f, "ret"),
+            Self::Ret(values) => write!(f, "ret {}", values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", ")),
             Self::Jmp(b) => write!(f, "jmp {}", b),
             Self::Branch { 
                 condition, true_path, false_path 
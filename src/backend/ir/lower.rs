@@ -0,0 +1,407 @@
+//! # Cranelift Lowering
+//!
+//! * Translates Kese IR modules into Cranelift IR
+//! * Emits the result as a native object file through `cranelift-object`
+//! * Functions follow the calling convention of the host, externs are imported
+//!   and left for the linker to resolve
+//! * Global data is local to the object, read-only data lands in `.rodata` and
+//!   mutable data of zeros in `.bss`
+
+use std::collections::HashMap;
+use std::error::Error;
+
+use cranelift::codegen::{self, ir::{self as clir, condcodes::{FloatCC, IntCC}, types as cltypes, AbiParam, InstBuilder as _, MemFlags, StackSlotData, StackSlotKind}};
+use cranelift::codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_module::{default_libcall_names, DataDescription, DataId, FuncId, Linkage, Module as _};
+use cranelift_object::{ObjectBuilder, ObjectModule};
+
+use super::{analysis::ControlFlowGraph, codegen::context::Module, entities::*, inst::*};
+
+pub type LowerResult<T> = Result<T, Box<dyn Error>>;
+
+/// Maps a Kese IR type onto the Cranelift type holding it, `void` has no representation
+pub fn cl_type(ty: Type) -> Option<clir::Type> {
+    match ty {
+        Type::I8 | Type::U8 | Type::Bool => Some(cltypes::I8),
+        Type::I16 | Type::U16 => Some(cltypes::I16),
+        Type::I32 | Type::U32 => Some(cltypes::I32),
+        Type::I64 | Type::U64 | Type::Ptr => Some(cltypes::I64),
+        Type::F32 => Some(cltypes::F32),
+        Type::F64 => Some(cltypes::F64),
+        Type::Void => None,
+    }
+}
+
+pub struct Lowerer {
+    object: ObjectModule,
+    ctx: codegen::Context,
+    fn_ctx: FunctionBuilderContext
+}
+
+impl Lowerer {
+    /// Creates a lowerer targeting the host machine
+    pub fn new(name: &str) -> LowerResult<Self> {
+        let mut flags = settings::builder();
+        flags.set("is_pic", "true")?;
+
+        let isa = cranelift_native::builder()?
+            .finish(settings::Flags::new(flags))?;
+        if isa.pointer_type() != cltypes::I64 {
+            return Err(format!("`ptr` is 64 bits wide, the target uses `{}` pointers", isa.pointer_type()).into())
+        }
+        let builder = ObjectBuilder::new(isa, name, default_libcall_names())?;
+        let object = ObjectModule::new(builder);
+
+        Ok(Self {
+            ctx: object.make_context(),
+            object,
+            fn_ctx: FunctionBuilderContext::new()
+        })
+    }
+
+    pub fn lower_module(&mut self, module: &Module) -> LowerResult<()> {
+        let mut ids: HashMap<String, FuncId> = HashMap::new();
+        for external in &module.externs {
+            let sig = self.signature(&external.sig);
+            ids.insert(external.alias.clone(), self.object.declare_function(&external.alias, Linkage::Import, &sig)?);
+        }
+        for function in &module.functions {
+            let sig = self.signature(&function.sig);
+            ids.insert(function.alias.clone(), self.object.declare_function(&function.alias, Linkage::Export, &sig)?);
+        }
+        // Cranelift has no float remainder, `frem` calls the C library's `fmod`
+        let remainders = module.functions
+            .iter()
+            .flat_map(|f| &f.blocks)
+            .flat_map(|b| &b.insts)
+            .filter_map(|inst| match inst {
+                Inst::Assign { dest, op: Op::FRem { .. } } => Some(dest.1),
+                _ => None
+            });
+        for ty in remainders {
+            let name = fmod(ty);
+            if !ids.contains_key(name) {
+                let sig = self.signature(&FunctionSignature::new().with_params(vec![ty, ty]).with_return_ty(ty));
+                ids.insert(name.to_string(), self.object.declare_function(name, Linkage::Import, &sig)?);
+            }
+        }
+
+        let mut data: HashMap<String, DataId> = HashMap::new();
+        for global in &module.globals {
+            let id = self.object.declare_data(&global.alias, Linkage::Local, global.mutable, false)?;
+            let mut description = DataDescription::new();
+            // Mutable zeros go into `.bss` rather than taking space in the file
+            match &global.init {
+                GlobalInit::Zeroed(size) if global.mutable => description.define_zeroinit(*size as usize),
+                GlobalInit::Zeroed(size) => description.define(vec![0; *size as usize].into_boxed_slice()),
+                GlobalInit::Bytes(bytes) => description.define(bytes.clone().into_boxed_slice())
+            }
+            description.set_align(global.align as u64);
+            self.object.define_data(id, &description)
+                .map_err(|e| format!("in global data `@{}`: {:?}", global.alias, e))?;
+            data.insert(global.alias.clone(), id);
+        }
+
+        for function in &module.functions {
+            let id = ids[&function.alias];
+            self.ctx.func.signature = self.signature(&function.sig);
+            self.lower_function(function, &ids, &data)
+                .map_err(|e| format!("in function `@{}`: {}", function.alias, e))?;
+            self.object.define_function(id, &mut self.ctx)
+                .map_err(|e| format!("in function `@{}`: {:?}", function.alias, e))?;
+            self.object.clear_context(&mut self.ctx);
+        }
+
+        Ok(())
+    }
+
+    /// Consumes the lowerer and returns the bytes of the object file
+    pub fn finish(self) -> LowerResult<Vec<u8>> {
+        Ok(self.object.finish().emit()?)
+    }
+
+    pub fn write(self, path: &str) -> LowerResult<()> {
+        std::fs::write(path, self.finish()?)?;
+        Ok(())
+    }
+
+    fn signature(&self, sig: &FunctionSignature) -> clir::Signature {
+        let mut signature = self.object.make_signature();
+        for param in sig.params.iter().filter_map(|p| cl_type(*p)) {
+            signature.params.push(AbiParam::new(param));
+        }
+        for ret in sig.returns.iter().filter_map(|r| cl_type(*r)) {
+            signature.returns.push(AbiParam::new(ret));
+        }
+        signature
+    }
+
+    fn lower_function(&mut self, function: &Function, ids: &HashMap<String, FuncId>, data: &HashMap<String, DataId>) -> LowerResult<()> {
+        let mut builder = FunctionBuilder::new(&mut self.ctx.func, &mut self.fn_ctx);
+        let mut values: HashMap<usize, clir::Value> = HashMap::new();
+        let mut blocks: HashMap<usize, clir::Block> = HashMap::new();
+        let mut callees: HashMap<String, clir::FuncRef> = HashMap::new();
+        let mut globals: HashMap<String, clir::GlobalValue> = HashMap::new();
+        for inst in function.blocks.iter().flat_map(|b| &b.insts) {
+            match inst {
+                Inst::Call { callee, .. } if !callees.contains_key(callee) => {
+                    let id = ids.get(callee).ok_or_else(|| format!("call to undefined function `@{}`", callee))?;
+                    callees.insert(callee.clone(), self.object.declare_func_in_func(*id, builder.func));
+                },
+                Inst::Assign { dest, op: Op::FRem { .. } } if !callees.contains_key(fmod(dest.1)) => {
+                    let name = fmod(dest.1);
+                    callees.insert(name.to_string(), self.object.declare_func_in_func(ids[name], builder.func));
+                },
+                Inst::Assign { op: Op::GlobalAddr(alias), .. } if !globals.contains_key(alias) => {
+                    let id = data.get(alias).ok_or_else(|| format!("use of undefined global data `@{}`", alias))?;
+                    globals.insert(alias.clone(), self.object.declare_data_in_func(*id, builder.func));
+                },
+                _ => {}
+            }
+        }
+        let slots: Vec<clir::StackSlot> = function.stack_slots
+            .iter()
+            .map(|slot| builder.create_sized_stack_slot(StackSlotData::new(
+                StackSlotKind::ExplicitSlot,
+                slot.size,
+                slot.align.trailing_zeros() as u8
+            )))
+            .collect();
+
+        let Some(entry) = function.blocks.first() else {
+            return Err("function has no blocks".into())
+        };
+        if !entry.params.is_empty() {
+            return Err(format!("entry block `{}` cannot take parameters", entry.id).into())
+        }
+
+        for block in &function.blocks {
+            let cl_block = builder.create_block();
+            for param in &block.params {
+                let ty = cl_type(param.1).ok_or("block parameters cannot be `void`")?;
+                values.insert(param.0, builder.append_block_param(cl_block, ty));
+            }
+            blocks.insert(block.id.0, cl_block);
+        }
+
+        let cl_entry = blocks[&entry.id.0];
+        builder.append_block_params_for_function_params(cl_entry);
+        let params = builder.block_params(cl_entry).to_vec();
+        let param_types = function.sig.params.iter().filter(|p| cl_type(**p).is_some());
+        for ((id, _), value) in param_types.enumerate().zip(params) {
+            values.insert(id, value);
+        }
+
+        // Reverse postorder lowers every definition before its uses
+        let by_id: HashMap<BlockID, &Block> = function.blocks.iter().map(|b| (b.id, b)).collect();
+        for id in ControlFlowGraph::new(function).reverse_postorder() {
+            let block = by_id[id];
+            builder.switch_to_block(blocks[&block.id.0]);
+            for inst in &block.insts {
+                lower_inst(&mut builder, inst, &mut values, &blocks, &slots, &callees, &globals)?;
+            }
+        }
+
+        builder.seal_all_blocks();
+        builder.finalize();
+        Ok(())
+    }
+}
+
+/// The C library function computing the float remainder for `ty`
+fn fmod(ty: Type) -> &'static str {
+    if ty == Type::F32 { "fmodf" } else { "fmod" }
+}
+
+fn value_of(values: &HashMap<usize, clir::Value>, id: &ValueID) -> LowerResult<clir::Value> {
+    values.get(&id.0)
+        .copied()
+        .ok_or_else(|| format!("use of undefined value `{}`", id).into())
+}
+
+fn block_of(blocks: &HashMap<usize, clir::Block>, id: &BlockID) -> LowerResult<clir::Block> {
+    blocks.get(&id.0)
+        .copied()
+        .ok_or_else(|| format!("jump to undefined block `{}`", id).into())
+}
+
+fn lower_inst(
+    builder: &mut FunctionBuilder,
+    inst: &Inst,
+    values: &mut HashMap<usize, clir::Value>,
+    blocks: &HashMap<usize, clir::Block>,
+    slots: &[clir::StackSlot],
+    callees: &HashMap<String, clir::FuncRef>,
+    globals: &HashMap<String, clir::GlobalValue>
+) -> LowerResult<()> {
+    match inst {
+        Inst::Assign { dest, op } => {
+            if let Some(value) = lower_op(builder, dest.1, op, values, slots, callees, globals)? {
+                values.insert(dest.0, value);
+            }
+        },
+        Inst::Store { value, address, align } => {
+            let flags = mem_flags(value.1, *align);
+            let (value, address) = (value_of(values, value)?, value_of(values, address)?);
+            builder.ins().store(flags, value, address, 0);
+        },
+        Inst::Call { results, callee, args } => {
+            let args = args.iter().map(|a| value_of(values, a)).collect::<LowerResult<Vec<_>>>()?;
+            let call = builder.ins().call(callees[callee], &args);
+            let returned = builder.inst_results(call).to_vec();
+            for (result, value) in results.iter().zip(returned) {
+                values.insert(result.0, value);
+            }
+        },
+        Inst::Ret(returned) => {
+            let returned = returned.iter().map(|v| value_of(values, v)).collect::<LowerResult<Vec<_>>>()?;
+            builder.ins().return_(&returned);
+        },
+        Inst::Jmp(call) => {
+            let args = call.args.iter().map(|a| value_of(values, a)).collect::<LowerResult<Vec<_>>>()?;
+            builder.ins().jump(block_of(blocks, &call.block)?, &args);
+        },
+        Inst::Branch { condition, true_path, false_path } => {
+            let condition = value_of(values, condition)?;
+            let true_args = true_path.args.iter().map(|a| value_of(values, a)).collect::<LowerResult<Vec<_>>>()?;
+            let false_args = false_path.args.iter().map(|a| value_of(values, a)).collect::<LowerResult<Vec<_>>>()?;
+            builder.ins().brif(
+                condition,
+                block_of(blocks, &true_path.block)?,
+                &true_args,
+                block_of(blocks, &false_path.block)?,
+                &false_args
+            );
+        }
+    }
+    Ok(())
+}
+
+fn lower_op(
+    builder: &mut FunctionBuilder,
+    ty: Type,
+    op: &Op,
+    values: &HashMap<usize, clir::Value>,
+    slots: &[clir::StackSlot],
+    callees: &HashMap<String, clir::FuncRef>,
+    globals: &HashMap<String, clir::GlobalValue>
+) -> LowerResult<Option<clir::Value>> {
+    let v = |id: &ValueID| value_of(values, id);
+    let mut ins = builder.ins();
+    let value = match op {
+        // Immediates are passed zero-extended, Cranelift rejects negative
+        // constants for types narrower than 64 bits
+        Op::Const(c) => match c {
+            Const::I8(n) => ins.iconst(cltypes::I8, *n as u8 as i64),
+            Const::I16(n) => ins.iconst(cltypes::I16, *n as u16 as i64),
+            Const::I32(n) => ins.iconst(cltypes::I32, *n as u32 as i64),
+            Const::I64(n) => ins.iconst(cltypes::I64, *n),
+            Const::U8(n) => ins.iconst(cltypes::I8, *n as i64),
+            Const::U16(n) => ins.iconst(cltypes::I16, *n as i64),
+            Const::U32(n) => ins.iconst(cltypes::I32, *n as i64),
+            Const::U64(n) => ins.iconst(cltypes::I64, *n as i64),
+            Const::F32(n) => ins.f32const(*n),
+            Const::F64(n) => ins.f64const(*n),
+            Const::Bool(n) => ins.iconst(cltypes::I8, (*n != 0) as i64),
+            Const::Void => return Ok(None),
+        },
+        Op::IAdd { left, right } => ins.iadd(v(left)?, v(right)?),
+        Op::ISub { left, right } => ins.isub(v(left)?, v(right)?),
+        Op::IMul { left, right } => ins.imul(v(left)?, v(right)?),
+        Op::SDiv { left, right } => ins.sdiv(v(left)?, v(right)?),
+        Op::UDiv { left, right } => ins.udiv(v(left)?, v(right)?),
+        Op::SRem { left, right } => ins.srem(v(left)?, v(right)?),
+        Op::URem { left, right } => ins.urem(v(left)?, v(right)?),
+        Op::FAdd { left, right } => ins.fadd(v(left)?, v(right)?),
+        Op::FSub { left, right } => ins.fsub(v(left)?, v(right)?),
+        Op::FMul { left, right } => ins.fmul(v(left)?, v(right)?),
+        Op::FDiv { left, right } => ins.fdiv(v(left)?, v(right)?),
+        // Rounding `l / r` loses precision for large quotients, `fmod` is exact
+        // and agrees with the constant folder
+        Op::FRem { left, right } => {
+            let call = ins.call(callees[fmod(ty)], &[v(left)?, v(right)?]);
+            builder.inst_results(call)[0]
+        },
+        // Cranelift masks the amount to the bit width, as the IR defines
+        Op::IShl { left, right } if ty.is_int() => ins.ishl(v(left)?, v(right)?),
+        Op::UShr { left, right } if ty.is_int() => ins.ushr(v(left)?, v(right)?),
+        Op::SShr { left, right } if ty.is_int() => ins.sshr(v(left)?, v(right)?),
+        Op::IShl { .. } | Op::UShr { .. } | Op::SShr { .. } => return Err(format!("cannot shift values of type `{}`", ty).into()),
+        // Booleans are stored as 0 or 1, so flipping the low bit is enough
+        Op::BNot(n) if ty == Type::Bool => ins.bxor_imm(v(n)?, 1),
+        Op::BNot(n) => ins.bnot(v(n)?),
+        Op::BOr { left, right } => ins.bor(v(left)?, v(right)?),
+        Op::BAnd { left, right } => ins.band(v(left)?, v(right)?),
+        Op::BXor { left, right } => ins.bxor(v(left)?, v(right)?),
+        Op::INeg(n) => ins.ineg(v(n)?),
+        Op::FNeg(n) => ins.fneg(v(n)?),
+        Op::ICmp { predicate, left, right } => ins.icmp(int_cc(predicate), v(left)?, v(right)?),
+        Op::FCmp { predicate, left, right } => ins.fcmp(float_cc(predicate), v(left)?, v(right)?),
+        Op::SExt(n, to) | Op::UExt(n, to) | Op::Trunc(n, to) | Op::FCvt(n, to)
+        | Op::SIToF(n, to) | Op::UIToF(n, to) | Op::FToSI(n, to) | Op::FToUI(n, to) => {
+            let to = cl_type(*to).ok_or("cannot convert to `void`")?;
+            match op {
+                Op::SExt(..) => ins.sextend(to, v(n)?),
+                Op::UExt(..) => ins.uextend(to, v(n)?),
+                Op::Trunc(..) => ins.ireduce(to, v(n)?),
+                Op::FCvt(..) if to == cltypes::F64 => ins.fpromote(to, v(n)?),
+                Op::FCvt(..) => ins.fdemote(to, v(n)?),
+                Op::SIToF(..) => ins.fcvt_from_sint(to, v(n)?),
+                Op::UIToF(..) => ins.fcvt_from_uint(to, v(n)?),
+                // Out of range floats saturate and NaN becomes zero instead of trapping
+                Op::FToSI(..) => ins.fcvt_to_sint_sat(to, v(n)?),
+                _ => ins.fcvt_to_uint_sat(to, v(n)?),
+            }
+        },
+        // Signedness only lives in Kese types, the bits are unchanged
+        Op::Bitcast(n, _) => v(n)?,
+        Op::StackAddr(slot) => {
+            let slot = slots.get(slot.0).ok_or_else(|| format!("use of undefined stack slot `{}`", slot))?;
+            ins.stack_addr(cltypes::I64, *slot, 0)
+        },
+        Op::GlobalAddr(alias) => ins.global_value(cltypes::I64, globals[alias]),
+        Op::Load { ty, address, align } => {
+            let cl_ty = cl_type(*ty).ok_or("cannot load `void`")?;
+            ins.load(cl_ty, mem_flags(*ty, *align), v(address)?, 0)
+        },
+    };
+    Ok(Some(value))
+}
+
+/// Accesses at least as aligned as their width are marked aligned
+fn mem_flags(ty: Type, align: u32) -> MemFlags {
+    let mut flags = MemFlags::new();
+    if align >= ty.bytes() {
+        flags.set_aligned();
+    }
+    flags
+}
+
+fn int_cc(predicate: &CmpPred) -> IntCC {
+    match predicate {
+        CmpPred::Eq => IntCC::Equal,
+        CmpPred::Ne => IntCC::NotEqual,
+        CmpPred::SGt => IntCC::SignedGreaterThan,
+        CmpPred::SLt => IntCC::SignedLessThan,
+        CmpPred::SGe => IntCC::SignedGreaterThanOrEqual,
+        CmpPred::SLe => IntCC::SignedLessThanOrEqual,
+        CmpPred::UGt => IntCC::UnsignedGreaterThan,
+        CmpPred::ULt => IntCC::UnsignedLessThan,
+        CmpPred::UGe => IntCC::UnsignedGreaterThanOrEqual,
+        CmpPred::ULe => IntCC::UnsignedLessThanOrEqual,
+    }
+}
+
+/// Float comparisons are ordered, the signedness of the predicate is ignored
+fn float_cc(predicate: &CmpPred) -> FloatCC {
+    match predicate {
+        CmpPred::Eq => FloatCC::Equal,
+        CmpPred::Ne => FloatCC::NotEqual,
+        CmpPred::SGt | CmpPred::UGt => FloatCC::GreaterThan,
+        CmpPred::SLt | CmpPred::ULt => FloatCC::LessThan,
+        CmpPred::SGe | CmpPred::UGe => FloatCC::GreaterThanOrEqual,
+        CmpPred::SLe | CmpPred::ULe => FloatCC::LessThanOrEqual,
+    }
+}
+
@@ -6,8 +6,12 @@
 
 pub mod inst;
 pub mod entities;
+pub mod analysis;
 pub mod codegen;
 pub mod optimization;
+pub mod lower;
+pub mod parser;
+pub mod verifier;
 
 pub mod prelude {
     pub use super::{
@@ -20,7 +24,9 @@ pub mod prelude {
         inst::{
             CmpPred, BlockCall
         },
-        optimization::*
+        analysis::*,
+        optimization::*,
+        verifier::Verifier
     };
 }
 
@@ -38,5 +44,6 @@ pub mod types {
     pub const F32: Type = Type::F32;
     pub const F64: Type = Type::F64;
     pub const BOOL: Type = Type::Bool;
+    pub const PTR: Type = Type::Ptr;
     pub const VOID: Type = Type::Void;
 }
\ No newline at end of file
@@ -1,28 +1,38 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use crate::backend::ir::{entities::*, inst::*};
 
 use super::*;
 
+/// Folds operations on constants with the wrapping semantics of the IR.
+/// A division by a constant zero, or an `sdiv` that would overflow, traps
+/// at runtime, so it is left in place and reported as a diagnostic
 pub struct ConstantFolder {
-    constants: HashMap<usize, Const>
+    constants: HashMap<usize, Const>,
+    diagnostics: Vec<Diagnostic>
 }
 
 impl ConstantFolder {
     pub fn new() -> Self {
         Self {
-            constants: HashMap::new()
+            constants: HashMap::new(),
+            diagnostics: Vec::new()
         }
     }
 
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
     pub fn run(&mut self, module: &mut Module) {
         let functions = &mut module.functions;
 
         for function in functions {
             self.constants.clear();
             let blocks = &mut function.blocks;
+            let mut removals: Vec<(usize, Vec<usize>)> = Vec::new();
 
-            for block in blocks {
+            for (b, block) in blocks.iter_mut().enumerate() {
                 let consts_to_remove: Vec<usize> = block.insts
                     .iter()
                     .enumerate()
@@ -53,14 +63,14 @@ impl ConstantFolder {
                                     };
 
                                     match (left_value, right_value) {
-                                        (Const::I8(l), Const::I8(r)) => *op = Op::Const(Const::I8(l + r)),
-                                        (Const::I16(l), Const::I16(r)) => *op = Op::Const(Const::I16(l + r)),
-                                        (Const::I32(l), Const::I32(r)) => *op = Op::Const(Const::I32(l + r)),
-                                        (Const::I64(l), Const::I64(r)) => *op = Op::Const(Const::I64(l + r)),
-                                        (Const::U8(l), Const::U8(r)) => *op = Op::Const(Const::U8(l + r)),
-                                        (Const::U16(l), Const::U16(r)) => *op = Op::Const(Const::U16(l + r)),
-                                        (Const::U32(l), Const::U32(r)) => *op = Op::Const(Const::U32(l + r)),
-                                        (Const::U64(l), Const::U64(r)) => *op = Op::Const(Const::U64(l + r)),
+                                        (Const::I8(l), Const::I8(r)) => *op = Op::Const(Const::I8(l.wrapping_add(*r))),
+                                        (Const::I16(l), Const::I16(r)) => *op = Op::Const(Const::I16(l.wrapping_add(*r))),
+                                        (Const::I32(l), Const::I32(r)) => *op = Op::Const(Const::I32(l.wrapping_add(*r))),
+                                        (Const::I64(l), Const::I64(r)) => *op = Op::Const(Const::I64(l.wrapping_add(*r))),
+                                        (Const::U8(l), Const::U8(r)) => *op = Op::Const(Const::U8(l.wrapping_add(*r))),
+                                        (Const::U16(l), Const::U16(r)) => *op = Op::Const(Const::U16(l.wrapping_add(*r))),
+                                        (Const::U32(l), Const::U32(r)) => *op = Op::Const(Const::U32(l.wrapping_add(*r))),
+                                        (Const::U64(l), Const::U64(r)) => *op = Op::Const(Const::U64(l.wrapping_add(*r))),
                                         _ => continue
                                     }
                                     if let Op::Const(c) = op {
@@ -78,14 +88,14 @@ impl ConstantFolder {
                                     };
 
                                     match (left_value, right_value) {
-                                        (Const::I8(l), Const::I8(r)) => *op = Op::Const(Const::I8(l - r)),
-                                        (Const::I16(l), Const::I16(r)) => *op = Op::Const(Const::I16(l - r)),
-                                        (Const::I32(l), Const::I32(r)) => *op = Op::Const(Const::I32(l - r)),
-                                        (Const::I64(l), Const::I64(r)) => *op = Op::Const(Const::I64(l - r)),
-                                        (Const::U8(l), Const::U8(r)) => *op = Op::Const(Const::U8(l - r)),
-                                        (Const::U16(l), Const::U16(r)) => *op = Op::Const(Const::U16(l - r)),
-                                        (Const::U32(l), Const::U32(r)) => *op = Op::Const(Const::U32(l - r)),
-                                        (Const::U64(l), Const::U64(r)) => *op = Op::Const(Const::U64(l - r)),
+                                        (Const::I8(l), Const::I8(r)) => *op = Op::Const(Const::I8(l.wrapping_sub(*r))),
+                                        (Const::I16(l), Const::I16(r)) => *op = Op::Const(Const::I16(l.wrapping_sub(*r))),
+                                        (Const::I32(l), Const::I32(r)) => *op = Op::Const(Const::I32(l.wrapping_sub(*r))),
+                                        (Const::I64(l), Const::I64(r)) => *op = Op::Const(Const::I64(l.wrapping_sub(*r))),
+                                        (Const::U8(l), Const::U8(r)) => *op = Op::Const(Const::U8(l.wrapping_sub(*r))),
+                                        (Const::U16(l), Const::U16(r)) => *op = Op::Const(Const::U16(l.wrapping_sub(*r))),
+                                        (Const::U32(l), Const::U32(r)) => *op = Op::Const(Const::U32(l.wrapping_sub(*r))),
+                                        (Const::U64(l), Const::U64(r)) => *op = Op::Const(Const::U64(l.wrapping_sub(*r))),
                                         _ => continue
                                     }
                                     if let Op::Const(c) = op {
@@ -103,14 +113,14 @@ impl ConstantFolder {
                                     };
 
                                     match (left_value, right_value) {
-                                        (Const::I8(l), Const::I8(r)) => *op = Op::Const(Const::I8(l * r)),
-                                        (Const::I16(l), Const::I16(r)) => *op = Op::Const(Const::I16(l * r)),
-                                        (Const::I32(l), Const::I32(r)) => *op = Op::Const(Const::I32(l * r)),
-                                        (Const::I64(l), Const::I64(r)) => *op = Op::Const(Const::I64(l * r)),
-                                        (Const::U8(l), Const::U8(r)) => *op = Op::Const(Const::U8(l * r)),
-                                        (Const::U16(l), Const::U16(r)) => *op = Op::Const(Const::U16(l * r)),
-                                        (Const::U32(l), Const::U32(r)) => *op = Op::Const(Const::U32(l * r)),
-                                        (Const::U64(l), Const::U64(r)) => *op = Op::Const(Const::U64(l * r)),
+                                        (Const::I8(l), Const::I8(r)) => *op = Op::Const(Const::I8(l.wrapping_mul(*r))),
+                                        (Const::I16(l), Const::I16(r)) => *op = Op::Const(Const::I16(l.wrapping_mul(*r))),
+                                        (Const::I32(l), Const::I32(r)) => *op = Op::Const(Const::I32(l.wrapping_mul(*r))),
+                                        (Const::I64(l), Const::I64(r)) => *op = Op::Const(Const::I64(l.wrapping_mul(*r))),
+                                        (Const::U8(l), Const::U8(r)) => *op = Op::Const(Const::U8(l.wrapping_mul(*r))),
+                                        (Const::U16(l), Const::U16(r)) => *op = Op::Const(Const::U16(l.wrapping_mul(*r))),
+                                        (Const::U32(l), Const::U32(r)) => *op = Op::Const(Const::U32(l.wrapping_mul(*r))),
+                                        (Const::U64(l), Const::U64(r)) => *op = Op::Const(Const::U64(l.wrapping_mul(*r))),
                                         _ => continue
                                     }
                                     if let Op::Const(c) = op {
@@ -118,41 +128,70 @@ impl ConstantFolder {
                                     }
                                 },
                                 Op::SDiv { left, right } => {
-                                    let left_value = match self.constants.get(&left.0) {
+                                    // A zero divisor is reported whatever the dividend is
+                                    let right_value = match self.constants.get(&right.0) {
                                         Some(s) => s,
                                         None => continue
                                     };
-                                    let right_value = match self.constants.get(&right.0) {
+                                    if right_value.is_zero() {
+                                        self.diagnostics.push(Diagnostic {
+                                            function: function.alias.clone(),
+                                            block: block.id,
+                                            message: format!("`{} = {}` divides by zero", dest, op)
+                                        });
+                                        continue
+                                    }
+                                    let left_value = match self.constants.get(&left.0) {
                                         Some(s) => s,
                                         None => continue
                                     };
 
-                                    match (left_value, right_value) {
-                                        (Const::I8(l), Const::I8(r)) => *op = Op::Const(Const::I8(l / r)),
-                                        (Const::I16(l), Const::I16(r)) => *op = Op::Const(Const::I16(l / r)),
-                                        (Const::I32(l), Const::I32(r)) => *op = Op::Const(Const::I32(l / r)),
-                                        (Const::I64(l), Const::I64(r)) => *op = Op::Const(Const::I64(l / r)),
+                                    let folded = match (left_value, right_value) {
+                                        (Const::I8(l), Const::I8(r)) => l.checked_div(*r).map(Const::I8),
+                                        (Const::I16(l), Const::I16(r)) => l.checked_div(*r).map(Const::I16),
+                                        (Const::I32(l), Const::I32(r)) => l.checked_div(*r).map(Const::I32),
+                                        (Const::I64(l), Const::I64(r)) => l.checked_div(*r).map(Const::I64),
                                         _ => continue
+                                    };
+                                    match folded {
+                                        Some(c) => *op = Op::Const(c),
+                                        None => {
+                                            self.diagnostics.push(Diagnostic {
+                                                function: function.alias.clone(),
+                                                block: block.id,
+                                                message: format!("`{} = {}` overflows", dest, op)
+                                            });
+                                            continue
+                                        }
                                     }
                                     if let Op::Const(c) = op {
                                         self.constants.insert(dest.0, c.clone());
                                     }
                                 },
                                 Op::UDiv { left, right } => {
-                                    let left_value = match self.constants.get(&left.0) {
+                                    // A zero divisor is reported whatever the dividend is
+                                    let right_value = match self.constants.get(&right.0) {
                                         Some(s) => s,
                                         None => continue
                                     };
-                                    let right_value = match self.constants.get(&right.0) {
+                                    if right_value.is_zero() {
+                                        self.diagnostics.push(Diagnostic {
+                                            function: function.alias.clone(),
+                                            block: block.id,
+                                            message: format!("`{} = {}` divides by zero", dest, op)
+                                        });
+                                        continue
+                                    }
+                                    let left_value = match self.constants.get(&left.0) {
                                         Some(s) => s,
                                         None => continue
                                     };
 
                                     match (left_value, right_value) {
-                                        (Const::U8(l), Const::U8(r)) => *op = Op::Const(Const::U8(l / r)),
-                                        (Const::U16(l), Const::U16(r)) => *op = Op::Const(Const::U16(l / r)),
-                                        (Const::U32(l), Const::U32(r)) => *op = Op::Const(Const::U32(l / r)),
-                                        (Const::U64(l), Const::U64(r)) => *op = Op::Const(Const::U64(l / r)),
+                                        (Const::U8(l), Const::U8(r)) => *op = Op::Const(Const::U8(l.wrapping_div(*r))),
+                                        (Const::U16(l), Const::U16(r)) => *op = Op::Const(Const::U16(l.wrapping_div(*r))),
+                                        (Const::U32(l), Const::U32(r)) => *op = Op::Const(Const::U32(l.wrapping_div(*r))),
+                                        (Const::U64(l), Const::U64(r)) => *op = Op::Const(Const::U64(l.wrapping_div(*r))),
                                         _ => continue
                                     }
                                     if let Op::Const(c) = op {
@@ -160,20 +199,29 @@ impl ConstantFolder {
                                     }
                                 },
                                 Op::SRem { left, right } => {
-                                    let left_value = match self.constants.get(&left.0) {
+                                    // A zero divisor is reported whatever the dividend is
+                                    let right_value = match self.constants.get(&right.0) {
                                         Some(s) => s,
                                         None => continue
                                     };
-                                    let right_value = match self.constants.get(&right.0) {
+                                    if right_value.is_zero() {
+                                        self.diagnostics.push(Diagnostic {
+                                            function: function.alias.clone(),
+                                            block: block.id,
+                                            message: format!("`{} = {}` divides by zero", dest, op)
+                                        });
+                                        continue
+                                    }
+                                    let left_value = match self.constants.get(&left.0) {
                                         Some(s) => s,
                                         None => continue
                                     };
 
                                     match (left_value, right_value) {
-                                        (Const::I8(l), Const::I8(r)) => *op = Op::Const(Const::I8(l % r)),
-                                        (Const::I16(l), Const::I16(r)) => *op = Op::Const(Const::I16(l % r)),
-                                        (Const::I32(l), Const::I32(r)) => *op = Op::Const(Const::I32(l % r)),
-                                        (Const::I64(l), Const::I64(r)) => *op = Op::Const(Const::I64(l % r)),
+                                        (Const::I8(l), Const::I8(r)) => *op = Op::Const(Const::I8(l.wrapping_rem(*r))),
+                                        (Const::I16(l), Const::I16(r)) => *op = Op::Const(Const::I16(l.wrapping_rem(*r))),
+                                        (Const::I32(l), Const::I32(r)) => *op = Op::Const(Const::I32(l.wrapping_rem(*r))),
+                                        (Const::I64(l), Const::I64(r)) => *op = Op::Const(Const::I64(l.wrapping_rem(*r))),
                                         _ => continue
                                     }
                                     if let Op::Const(c) = op {
@@ -181,20 +229,29 @@ impl ConstantFolder {
                                     }
                                 },
                                 Op::URem { left, right } => {
-                                    let left_value = match self.constants.get(&left.0) {
+                                    // A zero divisor is reported whatever the dividend is
+                                    let right_value = match self.constants.get(&right.0) {
                                         Some(s) => s,
                                         None => continue
                                     };
-                                    let right_value = match self.constants.get(&right.0) {
+                                    if right_value.is_zero() {
+                                        self.diagnostics.push(Diagnostic {
+                                            function: function.alias.clone(),
+                                            block: block.id,
+                                            message: format!("`{} = {}` divides by zero", dest, op)
+                                        });
+                                        continue
+                                    }
+                                    let left_value = match self.constants.get(&left.0) {
                                         Some(s) => s,
                                         None => continue
                                     };
 
                                     match (left_value, right_value) {
-                                        (Const::U8(l), Const::U8(r)) => *op = Op::Const(Const::U8(l % r)),
-                                        (Const::U16(l), Const::U16(r)) => *op = Op::Const(Const::U16(l % r)),
-                                        (Const::U32(l), Const::U32(r)) => *op = Op::Const(Const::U32(l % r)),
-                                        (Const::U64(l), Const::U64(r)) => *op = Op::Const(Const::U64(l % r)),
+                                        (Const::U8(l), Const::U8(r)) => *op = Op::Const(Const::U8(l.wrapping_rem(*r))),
+                                        (Const::U16(l), Const::U16(r)) => *op = Op::Const(Const::U16(l.wrapping_rem(*r))),
+                                        (Const::U32(l), Const::U32(r)) => *op = Op::Const(Const::U32(l.wrapping_rem(*r))),
+                                        (Const::U64(l), Const::U64(r)) => *op = Op::Const(Const::U64(l.wrapping_rem(*r))),
                                         _ => continue
                                     }
                                     if let Op::Const(c) = op {
@@ -298,67 +355,76 @@ impl ConstantFolder {
                                     }
                                 },
 
-                                Op::Lsh(v) => {
-                                    let value = match self.constants.get(&v.0) {
+                                Op::IShl { left, right } => {
+                                    let left_value = match self.constants.get(&left.0) {
+                                        Some(s) => s,
+                                        None => continue
+                                    };
+                                    let right_value = match self.constants.get(&right.0) {
                                         Some(s) => s,
                                         None => continue
                                     };
 
-                                    match value {
-                                        Const::I8(l) => *op = Op::Const(Const::I8(l << 1)),
-                                        Const::I16(l) => *op = Op::Const(Const::I16(l << 1)),
-                                        Const::I32(l) => *op = Op::Const(Const::I32(l << 1)),
-                                        Const::I64(l) => *op = Op::Const(Const::I64(l << 1)),
-                                        Const::U8(l) => *op = Op::Const(Const::U8(l << 1)),
-                                        Const::U16(l) => *op = Op::Const(Const::U16(l << 1)),
-                                        Const::U32(l) => *op = Op::Const(Const::U32(l << 1)),
-                                        Const::U64(l) => *op = Op::Const(Const::U64(l << 1)),
-                                        Const::F32(l) => *op = Op::Const(Const::F32(f32::from_bits(l.to_bits() << 1))),
-                                        Const::F64(l) => *op = Op::Const(Const::F64(f64::from_bits(l.to_bits() << 1))),
+                                    // Rust masks the amount to the bit width the same way
+                                    match (left_value, right_value) {
+                                        (Const::I8(l), Const::I8(r)) => *op = Op::Const(Const::I8(l.wrapping_shl(*r as u32))),
+                                        (Const::I16(l), Const::I16(r)) => *op = Op::Const(Const::I16(l.wrapping_shl(*r as u32))),
+                                        (Const::I32(l), Const::I32(r)) => *op = Op::Const(Const::I32(l.wrapping_shl(*r as u32))),
+                                        (Const::I64(l), Const::I64(r)) => *op = Op::Const(Const::I64(l.wrapping_shl(*r as u32))),
+                                        (Const::U8(l), Const::U8(r)) => *op = Op::Const(Const::U8(l.wrapping_shl(*r as u32))),
+                                        (Const::U16(l), Const::U16(r)) => *op = Op::Const(Const::U16(l.wrapping_shl(*r as u32))),
+                                        (Const::U32(l), Const::U32(r)) => *op = Op::Const(Const::U32(l.wrapping_shl(*r))),
+                                        (Const::U64(l), Const::U64(r)) => *op = Op::Const(Const::U64(l.wrapping_shl(*r as u32))),
                                         _ => continue
                                     }
                                     if let Op::Const(c) = op {
                                         self.constants.insert(dest.0, c.clone());
                                     }
                                 },
-                                Op::LRsh(v) => {
-                                    let value = match self.constants.get(&v.0) {
+                                Op::UShr { left, right } => {
+                                    let left_value = match self.constants.get(&left.0) {
+                                        Some(s) => s,
+                                        None => continue
+                                    };
+                                    let right_value = match self.constants.get(&right.0) {
                                         Some(s) => s,
                                         None => continue
                                     };
 
-                                    match value {
-                                        Const::I8(l) => *op = Op::Const(Const::I8((*l as u8 >> 1) as i8)),
-                                        Const::I16(l) => *op = Op::Const(Const::I16((*l as u16 >> 1) as i16)),
-                                        Const::I32(l) => *op = Op::Const(Const::I32((*l as u32 >> 1) as i32)),
-                                        Const::I64(l) => *op = Op::Const(Const::I64((*l as u64 >> 1) as i64)),
-                                        Const::U8(l) => *op = Op::Const(Const::U8(l >> 1)),
-                                        Const::U16(l) => *op = Op::Const(Const::U16(l >> 1)),
-                                        Const::U32(l) => *op = Op::Const(Const::U32(l >> 1)),
-                                        Const::U64(l) => *op = Op::Const(Const::U64(l >> 1)),
+                                    match (left_value, right_value) {
+                                        (Const::I8(l), Const::I8(r)) => *op = Op::Const(Const::I8((*l as u8).wrapping_shr(*r as u32) as i8)),
+                                        (Const::I16(l), Const::I16(r)) => *op = Op::Const(Const::I16((*l as u16).wrapping_shr(*r as u32) as i16)),
+                                        (Const::I32(l), Const::I32(r)) => *op = Op::Const(Const::I32((*l as u32).wrapping_shr(*r as u32) as i32)),
+                                        (Const::I64(l), Const::I64(r)) => *op = Op::Const(Const::I64((*l as u64).wrapping_shr(*r as u32) as i64)),
+                                        (Const::U8(l), Const::U8(r)) => *op = Op::Const(Const::U8(l.wrapping_shr(*r as u32))),
+                                        (Const::U16(l), Const::U16(r)) => *op = Op::Const(Const::U16(l.wrapping_shr(*r as u32))),
+                                        (Const::U32(l), Const::U32(r)) => *op = Op::Const(Const::U32(l.wrapping_shr(*r))),
+                                        (Const::U64(l), Const::U64(r)) => *op = Op::Const(Const::U64(l.wrapping_shr(*r as u32))),
                                         _ => continue
                                     }
                                     if let Op::Const(c) = op {
                                         self.constants.insert(dest.0, c.clone());
                                     }
                                 },
-                                Op::ARsh(v) => {
-                                    let value = match self.constants.get(&v.0) {
+                                Op::SShr { left, right } => {
+                                    let left_value = match self.constants.get(&left.0) {
+                                        Some(s) => s,
+                                        None => continue
+                                    };
+                                    let right_value = match self.constants.get(&right.0) {
                                         Some(s) => s,
                                         None => continue
                                     };
 
-                                    match value {
-                                        Const::I8(l) => *op = Op::Const(Const::I8(l >> 1)),
-                                        Const::I16(l) => *op = Op::Const(Const::I16(l >> 1)),
-                                        Const::I32(l) => *op = Op::Const(Const::I32(l >> 1)),
-                                        Const::I64(l) => *op = Op::Const(Const::I64(l >> 1)),
-                                        Const::U8(l) => *op = Op::Const(Const::U8((*l as i8 >> 1) as u8)),
-                                        Const::U16(l) => *op = Op::Const(Const::U16((*l as i16 >> 1) as u16)),
-                                        Const::U32(l) => *op = Op::Const(Const::U32((*l as i32 >> 1) as u32)),
-                                        Const::U64(l) => *op = Op::Const(Const::U64((*l as i64 >> 1) as u64)),
-                                        Const::F32(l) => *op = Op::Const(Const::F32(l / 2.0)),
-                                        Const::F64(l) => *op = Op::Const(Const::F64(l / 2.0)),
+                                    match (left_value, right_value) {
+                                        (Const::I8(l), Const::I8(r)) => *op = Op::Const(Const::I8(l.wrapping_shr(*r as u32))),
+                                        (Const::I16(l), Const::I16(r)) => *op = Op::Const(Const::I16(l.wrapping_shr(*r as u32))),
+                                        (Const::I32(l), Const::I32(r)) => *op = Op::Const(Const::I32(l.wrapping_shr(*r as u32))),
+                                        (Const::I64(l), Const::I64(r)) => *op = Op::Const(Const::I64(l.wrapping_shr(*r as u32))),
+                                        (Const::U8(l), Const::U8(r)) => *op = Op::Const(Const::U8((*l as i8).wrapping_shr(*r as u32) as u8)),
+                                        (Const::U16(l), Const::U16(r)) => *op = Op::Const(Const::U16((*l as i16).wrapping_shr(*r as u32) as u16)),
+                                        (Const::U32(l), Const::U32(r)) => *op = Op::Const(Const::U32((*l as i32).wrapping_shr(*r) as u32)),
+                                        (Const::U64(l), Const::U64(r)) => *op = Op::Const(Const::U64((*l as i64).wrapping_shr(*r as u32) as u64)),
                                         _ => continue
                                     }
                                     if let Op::Const(c) = op {
@@ -436,6 +502,31 @@ impl ConstantFolder {
                                         self.constants.insert(dest.0, c.clone());
                                     }
                                 },
+                                Op::BXor { left, right } => {
+                                    let left_value = match self.constants.get(&left.0) {
+                                        Some(s) => s,
+                                        None => continue
+                                    };
+                                    let right_value = match self.constants.get(&right.0) {
+                                        Some(s) => s,
+                                        None => continue
+                                    };
+
+                                    match (left_value, right_value) {
+                                        (Const::I8(l), Const::I8(r)) => *op = Op::Const(Const::I8(l ^ r)),
+                                        (Const::I16(l), Const::I16(r)) => *op = Op::Const(Const::I16(l ^ r)),
+                                        (Const::I32(l), Const::I32(r)) => *op = Op::Const(Const::I32(l ^ r)),
+                                        (Const::I64(l), Const::I64(r)) => *op = Op::Const(Const::I64(l ^ r)),
+                                        (Const::U8(l), Const::U8(r)) => *op = Op::Const(Const::U8(l ^ r)),
+                                        (Const::U16(l), Const::U16(r)) => *op = Op::Const(Const::U16(l ^ r)),
+                                        (Const::U32(l), Const::U32(r)) => *op = Op::Const(Const::U32(l ^ r)),
+                                        (Const::U64(l), Const::U64(r)) => *op = Op::Const(Const::U64(l ^ r)),
+                                        _ => continue
+                                    }
+                                    if let Op::Const(c) = op {
+                                        self.constants.insert(dest.0, c.clone());
+                                    }
+                                },
                                 Op::INeg(v) => {
                                     let value = match self.constants.get(&v.0) {
                                         Some(s) => s,
@@ -443,10 +534,10 @@ impl ConstantFolder {
                                     };
 
                                     match value {
-                                        Const::I8(l) => *op = Op::Const(Const::I8(-l)),
-                                        Const::I16(l) => *op = Op::Const(Const::I16(-l)),
-                                        Const::I32(l) => *op = Op::Const(Const::I32(-l)),
-                                        Const::I64(l) => *op = Op::Const(Const::I64(-l)),
+                                        Const::I8(l) => *op = Op::Const(Const::I8(l.wrapping_neg())),
+                                        Const::I16(l) => *op = Op::Const(Const::I16(l.wrapping_neg())),
+                                        Const::I32(l) => *op = Op::Const(Const::I32(l.wrapping_neg())),
+                                        Const::I64(l) => *op = Op::Const(Const::I64(l.wrapping_neg())),
                                         _ => continue
                                     }
                                     if let Op::Const(c) = op {
@@ -468,20 +559,139 @@ impl ConstantFolder {
                                         self.constants.insert(dest.0, c.clone());
                                     }
                                 },
+                                Op::ICmp { predicate, left, right } => {
+                                    let left_value = match self.constants.get(&left.0) {
+                                        Some(s) => s,
+                                        None => continue
+                                    };
+                                    let right_value = match self.constants.get(&right.0) {
+                                        Some(s) => s,
+                                        None => continue
+                                    };
+
+                                    match fold_icmp(predicate, left_value, right_value) {
+                                        Some(b) => *op = Op::Const(Const::Bool(b as u8)),
+                                        None => continue
+                                    }
+                                    if let Op::Const(c) = op {
+                                        self.constants.insert(dest.0, c.clone());
+                                    }
+                                },
+                                Op::FCmp { predicate, left, right } => {
+                                    let left_value = match self.constants.get(&left.0) {
+                                        Some(s) => s,
+                                        None => continue
+                                    };
+                                    let right_value = match self.constants.get(&right.0) {
+                                        Some(s) => s,
+                                        None => continue
+                                    };
+
+                                    match fold_fcmp(predicate, left_value, right_value) {
+                                        Some(b) => *op = Op::Const(Const::Bool(b as u8)),
+                                        None => continue
+                                    }
+                                    if let Op::Const(c) = op {
+                                        self.constants.insert(dest.0, c.clone());
+                                    }
+                                },
                                 _ => continue
                             }
                         },
+                        // A branch on a known condition always takes the same path
+                        Inst::Branch { condition, true_path, false_path } => {
+                            let taken = match self.constants.get(&condition.0) {
+                                Some(Const::Bool(0)) => false_path.clone(),
+                                Some(Const::Bool(_)) => true_path.clone(),
+                                _ => continue
+                            };
+                            *inst = Inst::Jmp(taken);
+                        },
                         _ => continue
                     }
                 }
+                removals.push((b, consts_to_remove));
+            }
+
+            // Constants that still feed an unfolded instruction have to stay
+            let used: HashSet<usize> = blocks
+                .iter()
+                .flat_map(|block| block.insts.iter())
+                .flat_map(|inst| inst.uses())
+                .map(|value| value.0)
+                .collect();
+            for (b, consts_to_remove) in removals {
+                let insts = &mut blocks[b].insts;
                 for &i in consts_to_remove.iter().rev() {
-                    block.insts.remove(i);
+                    if let Inst::Assign { dest, .. } = &insts[i] {
+                        if !used.contains(&dest.0) {
+                            insts.remove(i);
+                        }
+                    }
                 }
             }
         }
     }
 }
 
+/// The bits of an integer constant read as signed and as unsigned
+fn int_bits(c: &Const) -> Option<(i64, u64)> {
+    match c {
+        Const::I8(n) => Some((*n as i64, *n as u8 as u64)),
+        Const::I16(n) => Some((*n as i64, *n as u16 as u64)),
+        Const::I32(n) => Some((*n as i64, *n as u32 as u64)),
+        Const::I64(n) => Some((*n, *n as u64)),
+        Const::U8(n) => Some((*n as i8 as i64, *n as u64)),
+        Const::U16(n) => Some((*n as i16 as i64, *n as u64)),
+        Const::U32(n) => Some((*n as i32 as i64, *n as u64)),
+        Const::U64(n) => Some((*n as i64, *n)),
+        Const::Bool(n) => Some((*n as i64, *n as u64)),
+        _ => None
+    }
+}
+
+/// Signed predicates read both operands as signed and unsigned predicates
+/// as unsigned, whatever the type of the operands
+fn fold_icmp(predicate: &CmpPred, left: &Const, right: &Const) -> Option<bool> {
+    if left.ty() != right.ty() {
+        return None
+    }
+    let (ls, lu) = int_bits(left)?;
+    let (rs, ru) = int_bits(right)?;
+
+    Some(match predicate {
+        CmpPred::Eq => lu == ru,
+        CmpPred::Ne => lu != ru,
+        CmpPred::SGt => ls > rs,
+        CmpPred::SLt => ls < rs,
+        CmpPred::SGe => ls >= rs,
+        CmpPred::SLe => ls <= rs,
+        CmpPred::UGt => lu > ru,
+        CmpPred::ULt => lu < ru,
+        CmpPred::UGe => lu >= ru,
+        CmpPred::ULe => lu <= ru,
+    })
+}
+
+/// Float comparisons are ordered except `ne`, which holds when either
+/// operand is NaN, matching the lowering to Cranelift
+fn fold_fcmp(predicate: &CmpPred, left: &Const, right: &Const) -> Option<bool> {
+    let (l, r) = match (left, right) {
+        (Const::F32(l), Const::F32(r)) => (*l as f64, *r as f64),
+        (Const::F64(l), Const::F64(r)) => (*l, *r),
+        _ => return None
+    };
+
+    Some(match predicate {
+        CmpPred::Eq => l == r,
+        CmpPred::Ne => l != r,
+        CmpPred::SGt | CmpPred::UGt => l > r,
+        CmpPred::SLt | CmpPred::ULt => l < r,
+        CmpPred::SGe | CmpPred::UGe => l >= r,
+        CmpPred::SLe | CmpPred::ULe => l <= r,
+    })
+}
+
 impl OptimizationPass for ConstantFolder {
     fn name(&self) -> String {
         "ConstantFolder".to_string()
@@ -489,4 +699,7 @@ impl OptimizationPass for ConstantFolder {
     fn apply(&mut self, module: &mut Module) {
         self.run(module);
     }
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
 }
\ No newline at end of file
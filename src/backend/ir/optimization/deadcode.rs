@@ -0,0 +1,168 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::backend::ir::{entities::*, inst::*};
+
+use super::*;
+
+/// Where a value comes from, function parameters are left out since they
+/// are never removed
+enum Def {
+    Assign(Op),
+    Param { block: usize, index: usize }
+}
+
+/// Removes blocks the entry block cannot reach, then every instruction
+/// result and block parameter that no return, store, call, branch condition
+/// or possibly trapping division depends on.
+/// Liveness is propagated from the uses, so values that only feed each other
+/// around a loop are removed as well
+pub struct DeadCodeEliminator;
+
+impl DeadCodeEliminator {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn run(&mut self, module: &mut Module) {
+        self.run_with(module, &mut AnalysisCache::new());
+    }
+
+    fn run_with(&mut self, module: &mut Module, analyses: &mut AnalysisCache) {
+        for function in &mut module.functions {
+            Self::remove_unreachable(function, analyses.cfg(function));
+            Self::remove_dead_values(function);
+        }
+    }
+
+    fn remove_unreachable(function: &mut Function, cfg: &ControlFlowGraph) {
+        function.blocks.retain(|block| cfg.is_reachable(block.id));
+    }
+
+    /// Whether `op` divides by a value not known to be safe, zero always
+    /// traps and -1 does for `sdiv`
+    fn may_trap(op: &Op, defs: &HashMap<usize, Def>) -> bool {
+        let (right, signed_div) = match op {
+            Op::SDiv { right, .. } => (right, true),
+            Op::UDiv { right, .. } | Op::SRem { right, .. } | Op::URem { right, .. } => (right, false),
+            _ => return false
+        };
+        match defs.get(&right.0) {
+            Some(Def::Assign(Op::Const(c))) => {
+                c.is_zero() || (signed_div && matches!(
+                    c,
+                    Const::I8(-1) | Const::I16(-1) | Const::I32(-1) | Const::I64(-1)
+                ))
+            },
+            _ => true
+        }
+    }
+
+    fn remove_dead_values(function: &mut Function) {
+        let mut defs: HashMap<usize, Def> = HashMap::new();
+        // Arguments passed to each block parameter, keyed by block id
+        let mut args: HashMap<usize, Vec<Vec<ValueID>>> = HashMap::new();
+        let mut live: HashSet<usize> = HashSet::new();
+        let mut worklist: Vec<ValueID> = Vec::new();
+
+        for (b, block) in function.blocks.iter().enumerate() {
+            // The entry block's parameters are the function's own
+            if b != 0 {
+                for (index, param) in block.params.iter().enumerate() {
+                    defs.insert(param.0, Def::Param { block: block.id.0, index });
+                }
+            }
+            for inst in &block.insts {
+                match inst {
+                    Inst::Assign { dest, op } => {
+                        defs.insert(dest.0, Def::Assign(op.clone()));
+                    },
+                    // Memory is not tracked, every store is kept
+                    Inst::Store { value, address, .. } => worklist.extend([*value, *address]),
+                    // The callee may have effects, calls are kept whatever their results
+                    Inst::Call { args, .. } => worklist.extend(args.iter().copied()),
+                    Inst::Ret(values) => worklist.extend(values.iter().copied()),
+                    Inst::Branch { condition, .. } => worklist.push(*condition),
+                    Inst::Jmp(_) => {}
+                }
+                for call in inst.targets() {
+                    let params = args.entry(call.block.0).or_default();
+                    params.resize(call.args.len().max(params.len()), Vec::new());
+                    for (i, arg) in call.args.iter().enumerate() {
+                        params[i].push(*arg);
+                    }
+                }
+            }
+        }
+
+        // A division that may trap is kept for its effect
+        for inst in function.blocks.iter().flat_map(|b| &b.insts) {
+            if let Inst::Assign { dest, op } = inst {
+                if Self::may_trap(op, &defs) {
+                    worklist.push(*dest);
+                }
+            }
+        }
+
+        while let Some(value) = worklist.pop() {
+            if !live.insert(value.0) {
+                continue
+            }
+            match defs.get(&value.0) {
+                Some(Def::Assign(op)) => worklist.extend(op.operands()),
+                Some(Def::Param { block, index }) => {
+                    if let Some(values) = args.get(block).and_then(|params| params.get(*index)) {
+                        worklist.extend(values.iter().copied());
+                    }
+                },
+                None => {}
+            }
+        }
+
+        // Parameter positions to drop from each block and its callers
+        let mut dead_params: HashMap<usize, Vec<usize>> = HashMap::new();
+        for block in function.blocks.iter().skip(1) {
+            let dead: Vec<usize> = block.params
+                .iter()
+                .enumerate()
+                .filter(|(_, param)| !live.contains(&param.0))
+                .map(|(i, _)| i)
+                .collect();
+            if !dead.is_empty() {
+                dead_params.insert(block.id.0, dead);
+            }
+        }
+
+        for block in &mut function.blocks {
+            if let Some(dead) = dead_params.get(&block.id.0) {
+                for &i in dead.iter().rev() {
+                    block.params.remove(i);
+                }
+            }
+            block.insts.retain(|inst| match inst {
+                Inst::Assign { dest, .. } => live.contains(&dest.0),
+                _ => true
+            });
+            if let Some(inst) = block.insts.last_mut() {
+                for call in inst.targets_mut() {
+                    if let Some(dead) = dead_params.get(&call.block.0) {
+                        for &i in dead.iter().rev() {
+                            call.args.remove(i);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl OptimizationPass for DeadCodeEliminator {
+    fn name(&self) -> String {
+        "DeadCodeEliminator".to_string()
+    }
+    fn apply(&mut self, module: &mut Module) {
+        self.run(module);
+    }
+    fn apply_with(&mut self, module: &mut Module, analyses: &mut AnalysisCache) {
+        self.run_with(module, analyses);
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use crate::backend::ir::{entities::*, inst::*};
+
+use super::*;
+
+/// Replaces an operation computing the same value as one in a dominating
+/// block, or earlier in the same block, with that first definition.
+/// Blocks are visited down the dominator tree, so a value is only reused
+/// where it is available on every path
+pub struct GlobalValueNumbering;
+
+impl GlobalValueNumbering {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn run(&mut self, module: &mut Module) {
+        self.run_with(module, &mut AnalysisCache::new());
+    }
+
+    fn run_with(&mut self, module: &mut Module, analyses: &mut AnalysisCache) {
+        for function in &mut module.functions {
+            let dominators = analyses.dominators(function).clone();
+            Self::number_function(function, &dominators);
+        }
+    }
+
+    /// Operations other than loads are pure, so two of them with the same key give the same value.
+    /// Operands of commutative operations are ordered to match either way round
+    fn key(dest: ValueID, op: &Op) -> (Type, String) {
+        let op = match op {
+            Op::IAdd { left, right } if left.0 > right.0 => Op::IAdd { left: *right, right: *left },
+            Op::IMul { left, right } if left.0 > right.0 => Op::IMul { left: *right, right: *left },
+            Op::BAnd { left, right } if left.0 > right.0 => Op::BAnd { left: *right, right: *left },
+            Op::BOr { left, right } if left.0 > right.0 => Op::BOr { left: *right, right: *left },
+            Op::BXor { left, right } if left.0 > right.0 => Op::BXor { left: *right, right: *left },
+            Op::ICmp { predicate: CmpPred::Eq, left, right } if left.0 > right.0 => {
+                Op::ICmp { predicate: CmpPred::Eq, left: *right, right: *left }
+            },
+            Op::ICmp { predicate: CmpPred::Ne, left, right } if left.0 > right.0 => {
+                Op::ICmp { predicate: CmpPred::Ne, left: *right, right: *left }
+            },
+            _ => op.clone()
+        };
+        (dest.1, op.to_string())
+    }
+
+    fn number_function(function: &mut Function, dominators: &DominatorTree) {
+        let Some(root) = dominators.root() else { return };
+        let index: HashMap<BlockID, usize> = function.blocks
+            .iter()
+            .enumerate()
+            .map(|(i, b)| (b.id, i))
+            .collect();
+
+        let mut replacements: HashMap<usize, ValueID> = HashMap::new();
+        // One table per block on the path from the entry block
+        let mut scopes: Vec<HashMap<(Type, String), ValueID>> = Vec::new();
+        // `true` once the children of the block have been visited
+        let mut stack: Vec<(BlockID, bool)> = vec![(root, false)];
+
+        while let Some((id, visited)) = stack.pop() {
+            if visited {
+                scopes.pop();
+                continue
+            }
+            stack.push((id, true));
+            stack.extend(dominators.children(id).iter().rev().map(|child| (*child, false)));
+
+            let mut scope: HashMap<(Type, String), ValueID> = HashMap::new();
+            let block = &mut function.blocks[index[&id]];
+            block.insts.retain_mut(|inst| {
+                for value in inst.uses_mut() {
+                    if let Some(leader) = replacements.get(&value.0) {
+                        *value = *leader;
+                    }
+                }
+                let Inst::Assign { dest, op } = inst else { return true };
+                // A store may have changed the memory in between
+                if let Op::Load { .. } = op {
+                    return true
+                }
+
+                let key = Self::key(*dest, op);
+                let leader = scopes.iter().rev().find_map(|s| s.get(&key)).or(scope.get(&key));
+                match leader {
+                    Some(leader) => {
+                        replacements.insert(dest.0, *leader);
+                        false
+                    },
+                    None => {
+                        scope.insert(key, *dest);
+                        true
+                    }
+                }
+            });
+            scopes.push(scope);
+        }
+
+        // Blocks the walk did not reach may still name a replaced value
+        for block in &mut function.blocks {
+            for inst in &mut block.insts {
+                for value in inst.uses_mut() {
+                    if let Some(leader) = replacements.get(&value.0) {
+                        *value = *leader;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl OptimizationPass for GlobalValueNumbering {
+    fn name(&self) -> String {
+        "GlobalValueNumbering".to_string()
+    }
+    fn apply(&mut self, module: &mut Module) {
+        self.run(module);
+    }
+    fn apply_with(&mut self, module: &mut Module, analyses: &mut AnalysisCache) {
+        self.run_with(module, analyses);
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::backend::ir::{entities::*, inst::*};
+
+use super::*;
+
+/// Promotes stack slots whose address is only ever loaded from or stored to
+/// into SSA values. Block parameters are placed on the iterated dominance
+/// frontier of the blocks storing to a slot, then every load is replaced by
+/// the value last stored on the way down the dominator tree and every jump
+/// passes that value to the parameters it reaches
+pub struct MemToReg;
+
+/// A slot that will be promoted, `params` are the blocks given a parameter for it
+struct Promoted {
+    ty: Type,
+    params: Vec<BlockID>,
+    /// Read where nothing was stored yet
+    zero: ValueID
+}
+
+impl MemToReg {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn run(&mut self, module: &mut Module) {
+        self.run_with(module, &mut AnalysisCache::new());
+    }
+
+    fn run_with(&mut self, module: &mut Module, analyses: &mut AnalysisCache) {
+        for function in &mut module.functions {
+            let cfg = analyses.cfg(function).clone();
+            let dominators = analyses.dominators(function).clone();
+            Self::promote_function(function, &cfg, &dominators);
+        }
+    }
+
+    /// Type of every slot that can be promoted, a slot qualifies when its
+    /// address never escapes into anything but the address of a load or store,
+    /// every access uses the one type filling the slot and all of them are
+    /// in reachable blocks
+    fn promotable(function: &Function, cfg: &ControlFlowGraph) -> HashMap<usize, Type> {
+        let mut addresses: HashMap<usize, usize> = HashMap::new();
+        for inst in function.blocks.iter().flat_map(|b| &b.insts) {
+            if let Inst::Assign { dest, op: Op::StackAddr(slot) } = inst {
+                addresses.insert(dest.0, slot.0);
+            }
+        }
+
+        let mut types: HashMap<usize, Option<Type>> = HashMap::new();
+        let mut escaped: HashSet<usize> = HashSet::new();
+        for block in &function.blocks {
+            let reachable = cfg.is_reachable(block.id);
+            for inst in &block.insts {
+                let access = match inst {
+                    Inst::Assign { op: Op::Load { ty, address, .. }, .. } => Some((*address, *ty)),
+                    Inst::Store { value, address, .. } if value.0 != address.0 => Some((*address, value.1)),
+                    _ => None
+                };
+                let accessed = access.and_then(|(address, ty)| Some((*addresses.get(&address.0)?, ty)));
+                if let Some((slot, ty)) = accessed {
+                    let known = types.entry(slot).or_insert(Some(ty));
+                    if *known != Some(ty) || !reachable {
+                        *known = None;
+                    }
+                }
+
+                let address = access.map(|(address, _)| address.0);
+                for value in inst.uses() {
+                    if Some(value.0) != address {
+                        if let Some(slot) = addresses.get(&value.0) {
+                            escaped.insert(*slot);
+                        }
+                    }
+                }
+                if let Inst::Assign { op: Op::StackAddr(slot), .. } = inst {
+                    if !reachable {
+                        escaped.insert(slot.0);
+                    }
+                }
+            }
+        }
+
+        types.into_iter()
+            .filter(|(slot, _)| !escaped.contains(slot))
+            .filter_map(|(slot, ty)| Some((slot, ty?)))
+            // There is no `ptr` constant to stand for a read of an unset slot
+            .filter(|(slot, ty)| Const::zero(*ty).is_some() && ty.bytes() == function.stack_slots[*slot].size)
+            .collect()
+    }
+
+    fn promote_function(function: &mut Function, cfg: &ControlFlowGraph, dominators: &DominatorTree) {
+        let Some(root) = dominators.root() else { return };
+        let types = Self::promotable(function, cfg);
+        if types.is_empty() {
+            return
+        }
+
+        let mut next_id = function.sig.params.len();
+        for block in &function.blocks {
+            for param in &block.params {
+                next_id = next_id.max(param.0 + 1);
+            }
+            for result in block.insts.iter().flat_map(|inst| inst.results()) {
+                next_id = next_id.max(result.0 + 1);
+            }
+        }
+
+        // Blocks storing to each slot
+        let mut stores: HashMap<usize, Vec<BlockID>> = HashMap::new();
+        let mut addresses: HashMap<usize, usize> = HashMap::new();
+        for block in &function.blocks {
+            for inst in &block.insts {
+                match inst {
+                    Inst::Assign { dest, op: Op::StackAddr(slot) } if types.contains_key(&slot.0) => {
+                        addresses.insert(dest.0, slot.0);
+                    },
+                    Inst::Store { address, .. } => {
+                        if let Some(slot) = addresses.get(&address.0) {
+                            stores.entry(*slot).or_default().push(block.id);
+                        }
+                    },
+                    _ => {}
+                }
+            }
+        }
+
+        let mut slots: Vec<usize> = types.keys().copied().collect();
+        slots.sort();
+        let mut promoted: HashMap<usize, Promoted> = HashMap::new();
+        for slot in slots {
+            let mut params: Vec<BlockID> = Vec::new();
+            let mut worklist: Vec<BlockID> = stores.get(&slot).cloned().unwrap_or_default();
+            while let Some(block) = worklist.pop() {
+                for frontier in dominators.frontier(block) {
+                    if !params.contains(frontier) {
+                        params.push(*frontier);
+                        worklist.push(*frontier);
+                    }
+                }
+            }
+            // The entry block's parameters are the function's own
+            if params.contains(&root) {
+                continue
+            }
+            let zero = ValueID(next_id, types[&slot]);
+            next_id += 1;
+            promoted.insert(slot, Promoted { ty: types[&slot], params, zero });
+        }
+        if promoted.is_empty() {
+            return
+        }
+        addresses.retain(|_, slot| promoted.contains_key(slot));
+
+        // Parameters are appended in slot order, so are the arguments passed to them
+        let mut order: Vec<usize> = promoted.keys().copied().collect();
+        order.sort();
+        let mut block_params: HashMap<BlockID, Vec<(usize, ValueID)>> = HashMap::new();
+        for slot in &order {
+            let info = &promoted[slot];
+            for block in &info.params {
+                block_params.entry(*block).or_default().push((*slot, ValueID(next_id, info.ty)));
+                next_id += 1;
+            }
+        }
+
+        let index: HashMap<BlockID, usize> = function.blocks
+            .iter()
+            .enumerate()
+            .map(|(i, b)| (b.id, i))
+            .collect();
+        for (block, params) in &block_params {
+            let block = &mut function.blocks[index[block]];
+            block.params.extend(params.iter().map(|(_, value)| ParamID(value.0, value.1)));
+        }
+
+        let mut replacements: HashMap<usize, ValueID> = HashMap::new();
+        let resolve = |replacements: &HashMap<usize, ValueID>, mut value: ValueID| {
+            while let Some(next) = replacements.get(&value.0) {
+                value = *next;
+            }
+            value
+        };
+
+        let initial: HashMap<usize, ValueID> = promoted.iter().map(|(slot, info)| (*slot, info.zero)).collect();
+        let mut visited: Vec<BlockID> = Vec::new();
+        let mut stack: Vec<(BlockID, HashMap<usize, ValueID>)> = vec![(root, initial.clone())];
+        while let Some((id, mut current)) = stack.pop() {
+            visited.push(id);
+            for (slot, value) in block_params.get(&id).into_iter().flatten() {
+                current.insert(*slot, *value);
+            }
+
+            let block = &mut function.blocks[index[&id]];
+            block.insts.retain_mut(|inst| {
+                for value in inst.uses_mut() {
+                    *value = resolve(&replacements, *value);
+                }
+                match inst {
+                    Inst::Assign { op: Op::StackAddr(slot), .. } if promoted.contains_key(&slot.0) => false,
+                    Inst::Assign { dest, op: Op::Load { address, .. } } if addresses.contains_key(&address.0) => {
+                        replacements.insert(dest.0, current[&addresses[&address.0]]);
+                        false
+                    },
+                    Inst::Store { value, address, .. } if addresses.contains_key(&address.0) => {
+                        current.insert(addresses[&address.0], *value);
+                        false
+                    },
+                    _ => {
+                        for call in inst.targets_mut() {
+                            for (slot, _) in block_params.get(&call.block).into_iter().flatten() {
+                                call.args.push(current[slot]);
+                            }
+                        }
+                        true
+                    }
+                }
+            });
+
+            for child in dominators.children(id).iter().rev() {
+                stack.push((*child, current.clone()));
+            }
+        }
+
+        // Blocks the walk did not reach may still name a replaced value, and
+        // their jumps pass zeros to the new parameters as no slot is accessed there
+        for block in &mut function.blocks {
+            let reached = visited.contains(&block.id);
+            for inst in &mut block.insts {
+                for value in inst.uses_mut() {
+                    *value = resolve(&replacements, *value);
+                }
+                if reached {
+                    continue
+                }
+                for call in inst.targets_mut() {
+                    for (slot, _) in block_params.get(&call.block).into_iter().flatten() {
+                        call.args.push(initial[slot]);
+                    }
+                }
+            }
+        }
+
+        let entry = &mut function.blocks[index[&root]];
+        let mut zeros: Vec<&Promoted> = promoted.values().collect();
+        zeros.sort_by_key(|info| info.zero.0);
+        for (i, info) in zeros.into_iter().enumerate() {
+            let op = Op::Const(Const::zero(info.ty).expect("promoted slots have a zero"));
+            entry.insts.insert(i, Inst::Assign { dest: info.zero, op });
+        }
+
+        Self::remove_slots(function, &promoted);
+    }
+
+    /// Drops the promoted slots and renumbers the ones left
+    fn remove_slots(function: &mut Function, promoted: &HashMap<usize, Promoted>) {
+        let mut renumbered: HashMap<usize, usize> = HashMap::new();
+        let mut kept: Vec<StackSlotData> = Vec::new();
+        for (slot, data) in function.stack_slots.iter().enumerate() {
+            if !promoted.contains_key(&slot) {
+                renumbered.insert(slot, kept.len());
+                kept.push(*data);
+            }
+        }
+        function.stack_slots = kept;
+
+        for inst in function.blocks.iter_mut().flat_map(|b| &mut b.insts) {
+            if let Inst::Assign { op: Op::StackAddr(slot), .. } = inst {
+                slot.0 = renumbered[&slot.0];
+            }
+        }
+    }
+}
+
+impl OptimizationPass for MemToReg {
+    fn name(&self) -> String {
+        "MemToReg".to_string()
+    }
+    fn apply(&mut self, module: &mut Module) {
+        self.run(module);
+    }
+    fn apply_with(&mut self, module: &mut Module, analyses: &mut AnalysisCache) {
+        self.run_with(module, analyses);
+    }
+}
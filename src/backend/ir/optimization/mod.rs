@@ -2,18 +2,54 @@
 //! 
 //! ## Introduces:
 //! * Constant Folding
+//! * Dead Code Elimination
+//! * Global Value Numbering
+//! * Promotion of stack slots to SSA values
 //! 
 
+use std::fmt;
 use std::hash::Hash;
-use crate::backend::ir::prelude::*;
+use std::time::{Duration, Instant};
+use crate::backend::ir::{entities::{BlockID, Function}, prelude::*};
 
 pub mod constantfolder;
+pub mod deadcode;
+pub mod gvn;
+pub mod mem2reg;
 
 pub use constantfolder::*;
+pub use deadcode::*;
+pub use gvn::*;
+pub use mem2reg::*;
+
+/// A problem a pass found in the code it optimizes, such as a division
+/// by a constant zero
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub function: String,
+    pub block: BlockID,
+    pub message: String
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "@{} {}: {}", self.function, self.block, self.message)
+    }
+}
 
 pub trait OptimizationPass {
     fn name(&self) -> String;
     fn apply(&mut self, module: &mut Module);
+    /// Applies the pass with the analyses cached by the optimizer, passes
+    /// that need none only implement `apply`
+    fn apply_with(&mut self, module: &mut Module, analyses: &mut AnalysisCache) {
+        let _ = analyses;
+        self.apply(module);
+    }
+    /// Diagnostics reported since the last call
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        Vec::new()
+    }
 }
 
 impl std::fmt::Debug for dyn OptimizationPass {
@@ -36,26 +72,128 @@ impl Hash for dyn OptimizationPass {
 
 impl Eq for dyn OptimizationPass {}
 
+/// The pipelines selected by `-O`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptLevel {
+    /// No passes
+    O0,
+    /// A single round of slot promotion, folding and dead code elimination
+    O1,
+    /// Slot promotion, folding, value numbering and dead code elimination until nothing changes
+    O2
+}
+
+impl OptLevel {
+    /// Parses the value given to `-O`
+    pub fn from_flag(flag: &str) -> Option<Self> {
+        match flag {
+            "0" => Some(Self::O0),
+            "1" => Some(Self::O1),
+            "2" => Some(Self::O2),
+            _ => None
+        }
+    }
+}
+
+/// What one pass did over every run of the pipeline
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassStatistics {
+    pub name: String,
+    pub runs: usize,
+    /// Runs that changed the module
+    pub changes: usize,
+    /// Net number of instructions the pass removed
+    pub instructions_removed: isize,
+    pub time: Duration
+}
+
+impl fmt::Display for PassStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f, "{:<22} {:>4} run(s) {:>4} change(s) {:>6} inst(s) removed {:>10.3?}",
+            self.name, self.runs, self.changes, self.instructions_removed, self.time
+        )
+    }
+}
+
+/// Upper bound on the rounds of a pipeline run until a fixpoint
+pub const MAX_ITERATIONS: usize = 16;
+
 pub struct Optimizer<'a>  {
     module: &'a mut Module,
-    optimizations: Vec<Box<dyn OptimizationPass>>
+    optimizations: Vec<Box<dyn OptimizationPass>>,
+    diagnostics: Vec<Diagnostic>,
+    analyses: AnalysisCache,
+    statistics: Vec<PassStatistics>,
+    iterations: usize
 }
 
 impl<'a> Optimizer<'a> {
     pub fn new(module: &'a mut Module) -> Self {
         Self {
             module,
-            optimizations: Vec::new()
+            optimizations: Vec::new(),
+            diagnostics: Vec::new(),
+            analyses: AnalysisCache::new(),
+            statistics: Vec::new(),
+            iterations: 1
+        }
+    }
+
+    /// Adds the passes of `level`'s pipeline
+    pub fn with_level(self, level: OptLevel) -> Self {
+        match level {
+            OptLevel::O0 => self,
+            OptLevel::O1 => self
+                .with_mem_to_reg()
+                .with_constant_folder()
+                .with_dead_code_eliminator(),
+            OptLevel::O2 => self
+                .with_mem_to_reg()
+                .with_constant_folder()
+                .with_global_value_numbering()
+                .with_dead_code_eliminator()
+                .until_fixpoint()
         }
     }
 
+    /// Repeats the pipeline until a round leaves the module unchanged, up
+    /// to `MAX_ITERATIONS` rounds
+    pub fn until_fixpoint(mut self) -> Self {
+        self.iterations = MAX_ITERATIONS;
+        self
+    }
+
     pub fn with_constant_folder(mut self) -> Self {
         self.add_pass(Box::new(ConstantFolder::new()) as Box<dyn OptimizationPass>);
         self
     }
     
+    pub fn with_dead_code_eliminator(mut self) -> Self {
+        self.add_pass(Box::new(DeadCodeEliminator::new()) as Box<dyn OptimizationPass>);
+        self
+    }
+    
+    pub fn with_global_value_numbering(mut self) -> Self {
+        self.add_pass(Box::new(GlobalValueNumbering::new()) as Box<dyn OptimizationPass>);
+        self
+    }
+    
+    pub fn with_mem_to_reg(mut self) -> Self {
+        self.add_pass(Box::new(MemToReg::new()) as Box<dyn OptimizationPass>);
+        self
+    }
+    
+    /// Adds `pass` unless a pass with the same name was already added
     pub fn add_pass(&mut self, pass: Box<dyn OptimizationPass>) -> &mut Self {
-        if let None = self.optimizations.iter().find(|p| p.name() == "ConstantFolder") {
+        if self.optimizations.iter().all(|p| p.name() != pass.name()) {
+            self.statistics.push(PassStatistics {
+                name: pass.name(),
+                runs: 0,
+                changes: 0,
+                instructions_removed: 0,
+                time: Duration::ZERO
+            });
             self.optimizations.push(pass);
         }
         self
@@ -65,9 +203,63 @@ impl<'a> Optimizer<'a> {
         self.optimizations.iter().map(|pass| pass.as_ref())
     }
     
+    /// Applies every pass in order, repeating the pipeline as configured.
+    /// Debug builds verify the module before and after each pass and panic
+    /// on the first broken invariant
     pub fn run(&mut self) {
-        for pass in self.optimizations.iter_mut() {
-            pass.apply(self.module)
+        for _ in 0..self.iterations {
+            let mut changed = false;
+            for (pass, statistics) in self.optimizations.iter_mut().zip(&mut self.statistics) {
+                if cfg!(debug_assertions) {
+                    Self::verify(self.module, &format!("before {}", pass.name()));
+                }
+
+                let before = self.module.functions.clone();
+                let start = Instant::now();
+                pass.apply_with(self.module, &mut self.analyses);
+                statistics.time += start.elapsed();
+                statistics.runs += 1;
+                statistics.instructions_removed += Self::count_insts(&before) as isize
+                    - Self::count_insts(&self.module.functions) as isize;
+                if before != self.module.functions {
+                    statistics.changes += 1;
+                    changed = true;
+                }
+
+                // Later rounds find the same problems again
+                for diagnostic in pass.take_diagnostics() {
+                    if !self.diagnostics.contains(&diagnostic) {
+                        self.diagnostics.push(diagnostic);
+                    }
+                }
+                if cfg!(debug_assertions) {
+                    Self::verify(self.module, &format!("after {}", pass.name()));
+                }
+            }
+            if !changed {
+                break
+            }
+        }
+    }
+
+    /// Statistics of every pass, in pipeline order
+    pub fn statistics(&self) -> &[PassStatistics] {
+        &self.statistics
+    }
+
+    fn count_insts(functions: &[Function]) -> usize {
+        functions.iter().flat_map(|f| &f.blocks).map(|b| b.insts.len()).sum()
+    }
+
+    /// Diagnostics reported by the passes that ran
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    fn verify(module: &Module, stage: &str) {
+        if let Err(errors) = Verifier::new().verify_module(module) {
+            let errors: Vec<String> = errors.iter().map(|e| format!("  {}", e)).collect();
+            panic!("invalid IR in module `{}` {}:\n{}", module.name(), stage, errors.join("\n"));
         }
     }
 }
\ No newline at end of file
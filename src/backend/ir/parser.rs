@@ -0,0 +1,771 @@
+//! # Kese IR Parser
+//!
+//! * Reads the textual IR printed by `Module::display` back into functions
+//! * Uses only spell out value ids (`%3`), their types are recovered from the
+//!   definitions once the whole function has been read
+//! * Stack slots are declared before the first block, as `ss0 = stack_slot 8 align 8`
+//! * Functions defined outside the module are declared as `extern i32 : @abs(i32)`
+//! * Global data is declared as `global @str0 = readonly "hi\0a" align 1`, any
+//!   byte can be written as `\` followed by two hex digits. Data of zeros only
+//!   is declared by its size, as `global @buffer = mutable zeroed 64 align 8`
+//! * `//` starts a comment running to the end of the line
+
+use std::collections::HashMap;
+
+use crate::global::{ECode, Error, Span};
+use super::{entities::*, inst::*};
+
+/// Externs, global data and functions of a module, in the order they were read
+pub type ParsedModule = (Vec<ExternFunction>, Vec<GlobalData>, Vec<Function>);
+
+#[derive(Clone, Copy)]
+struct Cursor {
+    pos: usize,
+    line: usize,
+    column: usize
+}
+
+pub struct IRParser {
+    chars: Vec<char>,
+    cursor: Cursor,
+    src: String,
+    path: String
+}
+
+/// Per-function bookkeeping used to type values after parsing
+#[derive(Default)]
+struct FunctionScope {
+    types: HashMap<usize, Type>,
+    defs: HashMap<usize, Span>,
+    uses: Vec<(usize, Span)>,
+    blocks: HashMap<usize, Vec<Type>>,
+    calls: Vec<(usize, usize, Span)>,
+    stack_slots: usize
+}
+
+impl IRParser {
+    pub fn new(src: &str, path: &str) -> Self {
+        Self {
+            chars: src.chars().collect(),
+            cursor: Cursor { pos: 0, line: 0, column: 0 },
+            src: src.to_string(),
+            path: path.to_string()
+        }
+    }
+
+    pub fn parse(&mut self) -> Result<ParsedModule, Error> {
+        let mut externs: Vec<ExternFunction> = Vec::new();
+        let mut globals: Vec<GlobalData> = Vec::new();
+        let mut functions: Vec<Function> = Vec::new();
+        loop {
+            self.skip_trivia();
+            if self.peek().is_none() {
+                break
+            }
+            let start = self.cursor;
+            let (word, _) = self.word()?;
+            self.cursor = start;
+            match &*word {
+                "extern" => externs.push(self.parse_extern()?),
+                "global" => globals.push(self.parse_global()?),
+                _ => functions.push(self.parse_function()?)
+            }
+        }
+        Ok((externs, globals, functions))
+    }
+
+    fn parse_extern(&mut self) -> Result<ExternFunction, Error> {
+        self.keyword("extern")?;
+        let returns = self.returns()?;
+        self.expect(':')?;
+        self.expect('@')?;
+        let (alias, _) = self.word()?;
+
+        let mut params: Vec<Type> = Vec::new();
+        self.expect('(')?;
+        if !self.eat(')') {
+            loop {
+                params.push(self.type_()?.0);
+                if !self.eat(',') {
+                    break
+                }
+            }
+            self.expect(')')?;
+        }
+        Ok(ExternFunction {
+            alias,
+            sig: FunctionSignature::new()
+                .with_params(params)
+                .with_returns(returns)
+        })
+    }
+
+    fn parse_global(&mut self) -> Result<GlobalData, Error> {
+        self.keyword("global")?;
+        self.expect('@')?;
+        let (alias, _) = self.word()?;
+        self.expect('=')?;
+        let (kind, span) = self.word()?;
+        let mutable = match &*kind {
+            "readonly" => false,
+            "mutable" => true,
+            _ => return Err(self.error(ECode::ExpectedToken, format!("expected `readonly` or `mutable`, found `{}`", kind), span))
+        };
+        self.skip_trivia();
+        let init = if self.peek() == Some('"') {
+            GlobalInit::Bytes(self.bytes()?)
+        } else {
+            self.keyword("zeroed")?;
+            GlobalInit::Zeroed(self.number()?)
+        };
+        let align = self.align()?;
+        Ok(GlobalData { alias, init, mutable, align })
+    }
+
+    fn parse_function(&mut self) -> Result<Function, Error> {
+        self.keyword("func")?;
+        let returns = self.returns()?;
+        self.expect(':')?;
+        self.expect('@')?;
+        let (alias, _) = self.word()?;
+
+        let mut scope = FunctionScope::default();
+        let mut params: Vec<Type> = Vec::new();
+        self.expect('(')?;
+        if !self.eat(')') {
+            loop {
+                let (ty, _) = self.type_()?;
+                let (id, span) = self.value_ref()?;
+                if id != params.len() {
+                    return Err(self.error(
+                        ECode::UnexpectedToken,
+                        format!("expected parameter `%{}`, found `%{}`", params.len(), id),
+                        span
+                    ))
+                }
+                self.define(&mut scope, id, span)?;
+                scope.types.insert(id, ty);
+                params.push(ty);
+                if !self.eat(',') {
+                    break
+                }
+            }
+            self.expect(')')?;
+        }
+        self.expect('{')?;
+
+        let mut stack_slots: Vec<StackSlotData> = Vec::new();
+        loop {
+            self.skip_trivia();
+            let start = self.cursor;
+            let (word, span) = self.word()?;
+            if !word.starts_with("ss") {
+                self.cursor = start;
+                break
+            }
+            let slot = self.stack_slot_ref(&word, span)?;
+            if slot.0 != stack_slots.len() {
+                return Err(self.error(
+                    ECode::UnexpectedToken,
+                    format!("expected stack slot `ss{}`, found `{}`", stack_slots.len(), slot),
+                    span
+                ))
+            }
+            self.expect('=')?;
+            self.keyword("stack_slot")?;
+            let size = self.number()?;
+            let align = self.align()?;
+            stack_slots.push(StackSlotData { size, align });
+        }
+        scope.stack_slots = stack_slots.len();
+
+        let mut blocks: Vec<Block> = Vec::new();
+        while !self.eat('}') {
+            blocks.push(self.parse_block(&mut scope)?);
+        }
+
+        self.resolve(&mut scope, &mut blocks)?;
+        Ok(Function {
+            alias,
+            blocks,
+            sig: FunctionSignature::new()
+                .with_params(params)
+                .with_returns(returns),
+            stack_slots
+        })
+    }
+
+    fn parse_block(&mut self, scope: &mut FunctionScope) -> Result<Block, Error> {
+        let (id, span) = self.block_ref()?;
+        if scope.blocks.contains_key(&id) {
+            return Err(self.error(ECode::UnexpectedToken, format!("block `u{}` is defined more than once", id), span))
+        }
+
+        let mut params: Vec<ParamID> = Vec::new();
+        self.expect('(')?;
+        if !self.eat(')') {
+            loop {
+                let (ty, _) = self.type_()?;
+                let (param, span) = self.value_ref()?;
+                self.define(scope, param, span)?;
+                scope.types.insert(param, ty);
+                params.push(ParamID(param, ty));
+                if !self.eat(',') {
+                    break
+                }
+            }
+            self.expect(')')?;
+        }
+        self.expect(':')?;
+        scope.blocks.insert(id, params.iter().map(|p| p.1).collect());
+
+        let mut insts: Vec<Inst> = Vec::new();
+        loop {
+            self.skip_trivia();
+            match self.peek() {
+                Some('%') => insts.push(self.parse_assign(scope)?),
+                Some('}') | None => break,
+                _ => {
+                    let start = self.cursor;
+                    let (word, _) = self.word()?;
+                    self.cursor = start;
+                    match &*word {
+                        "store" => insts.push(self.parse_store(scope)?),
+                        "call" => {
+                            self.word()?;
+                            insts.push(self.parse_call(scope, Vec::new())?);
+                        },
+                        "ret" | "jmp" | "br" => insts.push(self.parse_terminator(scope)?),
+                        _ => break
+                    }
+                }
+            }
+        }
+
+        Ok(Block { id: BlockID(id), insts, params })
+    }
+
+    fn parse_assign(&mut self, scope: &mut FunctionScope) -> Result<Inst, Error> {
+        let mut dests: Vec<(usize, Span)> = vec![self.value_ref()?];
+        while self.eat(',') {
+            dests.push(self.value_ref()?);
+        }
+        for (id, span) in &dests {
+            self.define(scope, *id, *span)?;
+        }
+        self.expect('=')?;
+
+        let (name, span) = self.word()?;
+        if name == "call" {
+            return self.parse_call(scope, dests)
+        }
+        if dests.len() > 1 {
+            return Err(self.error(ECode::UnexpectedToken, format!("`{}` defines a single value", name), span))
+        }
+        let id = dests[0].0;
+        let op = match &*name {
+            "const" => {
+                let (ty, _) = self.type_()?;
+                self.expect(':')?;
+                let c = self.constant(ty)?;
+                scope.types.insert(id, ty);
+                Op::Const(c)
+            },
+            "icmp" | "fcmp" => {
+                let predicate = self.predicate()?;
+                let left = self.use_(scope)?;
+                let right = self.use_(scope)?;
+                scope.types.insert(id, Type::Bool);
+                if name == "icmp" {
+                    Op::ICmp { predicate, left, right }
+                } else {
+                    Op::FCmp { predicate, left, right }
+                }
+            },
+            "iadd" | "isub" | "imul" | "sdiv" | "udiv" | "srem" | "urem"
+            | "fadd" | "fsub" | "fmul" | "fdiv" | "frem" | "bor" | "band" | "bxor"
+            | "ishl" | "ushr" | "sshr" => {
+                let left = self.use_(scope)?;
+                let right = self.use_(scope)?;
+                match &*name {
+                    "iadd" => Op::IAdd { left, right },
+                    "isub" => Op::ISub { left, right },
+                    "imul" => Op::IMul { left, right },
+                    "sdiv" => Op::SDiv { left, right },
+                    "udiv" => Op::UDiv { left, right },
+                    "srem" => Op::SRem { left, right },
+                    "urem" => Op::URem { left, right },
+                    "fadd" => Op::FAdd { left, right },
+                    "fsub" => Op::FSub { left, right },
+                    "fmul" => Op::FMul { left, right },
+                    "fdiv" => Op::FDiv { left, right },
+                    "frem" => Op::FRem { left, right },
+                    "bor" => Op::BOr { left, right },
+                    "band" => Op::BAnd { left, right },
+                    "bxor" => Op::BXor { left, right },
+                    "ishl" => Op::IShl { left, right },
+                    "ushr" => Op::UShr { left, right },
+                    _ => Op::SShr { left, right },
+                }
+            },
+            "bnot" | "ineg" | "fneg" => {
+                let n = self.use_(scope)?;
+                match &*name {
+                    "bnot" => Op::BNot(n),
+                    "ineg" => Op::INeg(n),
+                    _ => Op::FNeg(n),
+                }
+            },
+            "sext" | "uext" | "trunc" | "bitcast" | "fcvt" | "sitof" | "uitof" | "ftosi" | "ftoui" => {
+                let (ty, _) = self.type_()?;
+                let n = self.use_(scope)?;
+                scope.types.insert(id, ty);
+                match &*name {
+                    "sext" => Op::SExt(n, ty),
+                    "uext" => Op::UExt(n, ty),
+                    "trunc" => Op::Trunc(n, ty),
+                    "bitcast" => Op::Bitcast(n, ty),
+                    "fcvt" => Op::FCvt(n, ty),
+                    "sitof" => Op::SIToF(n, ty),
+                    "uitof" => Op::UIToF(n, ty),
+                    "ftosi" => Op::FToSI(n, ty),
+                    _ => Op::FToUI(n, ty),
+                }
+            },
+            "stack_addr" => {
+                let (word, span) = self.word()?;
+                let slot = self.stack_slot_ref(&word, span)?;
+                if slot.0 >= scope.stack_slots {
+                    return Err(self.error(ECode::UndefinedIdentifier, format!("use of undefined stack slot `{}`", slot), span))
+                }
+                scope.types.insert(id, Type::Ptr);
+                Op::StackAddr(slot)
+            },
+            "global_addr" => {
+                self.expect('@')?;
+                let (alias, _) = self.word()?;
+                scope.types.insert(id, Type::Ptr);
+                Op::GlobalAddr(alias)
+            },
+            "load" => {
+                let (ty, _) = self.type_()?;
+                let address = self.use_(scope)?;
+                let align = self.align()?;
+                scope.types.insert(id, ty);
+                Op::Load { ty, address, align }
+            },
+            _ => return Err(self.error(ECode::UnexpectedToken, format!("unknown operation `{}`", name), span))
+        };
+
+        Ok(Inst::Assign { dest: ValueID(id, Type::Void), op })
+    }
+
+    /// Parses `call` from its result types on, `results` are the values it defines
+    fn parse_call(&mut self, scope: &mut FunctionScope, results: Vec<(usize, Span)>) -> Result<Inst, Error> {
+        self.skip_trivia();
+        let start = self.cursor;
+        let returns = self.returns()?;
+        if returns.len() != results.len() {
+            return Err(self.error(
+                ECode::MismatchedTypes,
+                format!("call returning {} value(s) defines {}", returns.len(), results.len()),
+                self.span_from(start)
+            ))
+        }
+        let results: Vec<ValueID> = results
+            .iter()
+            .zip(returns)
+            .map(|((id, _), ty)| {
+                scope.types.insert(*id, ty);
+                ValueID(*id, ty)
+            })
+            .collect();
+
+        self.expect('@')?;
+        let (callee, _) = self.word()?;
+        let mut args: Vec<ValueID> = Vec::new();
+        self.expect('(')?;
+        if !self.eat(')') {
+            loop {
+                args.push(self.use_(scope)?);
+                if !self.eat(',') {
+                    break
+                }
+            }
+            self.expect(')')?;
+        }
+        Ok(Inst::Call { results, callee, args })
+    }
+
+    fn parse_store(&mut self, scope: &mut FunctionScope) -> Result<Inst, Error> {
+        self.keyword("store")?;
+        let value = self.use_(scope)?;
+        let address = self.use_(scope)?;
+        let align = self.align()?;
+        Ok(Inst::Store { value, address, align })
+    }
+
+    fn parse_terminator(&mut self, scope: &mut FunctionScope) -> Result<Inst, Error> {
+        let (name, _) = self.word()?;
+        match &*name {
+            "ret" => {
+                let mut values: Vec<ValueID> = Vec::new();
+                self.skip_trivia();
+                if self.peek() == Some('%') {
+                    values.push(self.use_(scope)?);
+                    while self.eat(',') {
+                        values.push(self.use_(scope)?);
+                    }
+                }
+                Ok(Inst::Ret(values))
+            },
+            "jmp" => Ok(Inst::Jmp(self.block_call(scope)?)),
+            _ => {
+                let condition = self.use_(scope)?;
+                let true_path = self.block_call(scope)?;
+                let false_path = self.block_call(scope)?;
+                Ok(Inst::Branch { condition, true_path, false_path })
+            }
+        }
+    }
+
+    fn block_call(&mut self, scope: &mut FunctionScope) -> Result<BlockCall, Error> {
+        self.skip_trivia();
+        let start = self.cursor;
+        let (id, _) = self.block_ref()?;
+        let mut args: Vec<ValueID> = Vec::new();
+        self.expect('(')?;
+        if !self.eat(')') {
+            loop {
+                args.push(self.use_(scope)?);
+                if !self.eat(',') {
+                    break
+                }
+            }
+            self.expect(')')?;
+        }
+        scope.calls.push((id, args.len(), self.span_from(start)));
+        Ok(BlockCall { block: BlockID(id), args })
+    }
+
+    /// Gives every value its type, following definitions until nothing changes
+    fn resolve(&self, scope: &mut FunctionScope, blocks: &mut [Block]) -> Result<(), Error> {
+        loop {
+            let mut progress = false;
+            for inst in blocks.iter().flat_map(|b| b.insts.iter()) {
+                let Inst::Assign { dest, op } = inst else { continue };
+                if scope.types.contains_key(&dest.0) {
+                    continue
+                }
+                let ty = op.operands()
+                    .first()
+                    .and_then(|first| scope.types.get(&first.0).copied());
+                if let Some(ty) = ty {
+                    scope.types.insert(dest.0, ty);
+                    progress = true;
+                }
+            }
+            if !progress {
+                break
+            }
+        }
+
+        for (id, span) in &scope.uses {
+            if !scope.types.contains_key(id) {
+                let details = if scope.defs.contains_key(id) {
+                    format!("cannot infer the type of `%{}`", id)
+                } else {
+                    format!("use of undefined value `%{}`", id)
+                };
+                return Err(self.error(ECode::UndefinedIdentifier, details, *span))
+            }
+        }
+        for (block, args, span) in &scope.calls {
+            match scope.blocks.get(block) {
+                None => return Err(self.error(ECode::UndefinedIdentifier, format!("jump to undefined block `u{}`", block), *span)),
+                Some(params) if params.len() != *args => return Err(self.error(
+                    ECode::MismatchedTypes,
+                    format!("block `u{}` takes {} argument(s) but {} were given", block, params.len(), args),
+                    *span
+                )),
+                _ => {}
+            }
+        }
+
+        for inst in blocks.iter_mut().flat_map(|b| b.insts.iter_mut()) {
+            match inst {
+                Inst::Assign { dest, .. } => dest.1 = scope.types[&dest.0],
+                Inst::Call { results, .. } => results.iter_mut().for_each(|r| r.1 = scope.types[&r.0]),
+                _ => {}
+            }
+            for value in inst.uses_mut() {
+                value.1 = scope.types[&value.0];
+            }
+        }
+        Ok(())
+    }
+
+    fn define(&self, scope: &mut FunctionScope, id: usize, span: Span) -> Result<(), Error> {
+        if scope.defs.insert(id, span).is_some() {
+            return Err(self.error(ECode::UnexpectedToken, format!("`%{}` is defined more than once", id), span))
+        }
+        Ok(())
+    }
+
+    fn use_(&mut self, scope: &mut FunctionScope) -> Result<ValueID, Error> {
+        let (id, span) = self.value_ref()?;
+        scope.uses.push((id, span));
+        Ok(ValueID(id, Type::Void))
+    }
+
+    fn constant(&mut self, ty: Type) -> Result<Const, Error> {
+        let (word, span) = self.word()?;
+        let c = match ty {
+            Type::I8 => word.parse().ok().map(Const::I8),
+            Type::I16 => word.parse().ok().map(Const::I16),
+            Type::I32 => word.parse().ok().map(Const::I32),
+            Type::I64 => word.parse().ok().map(Const::I64),
+            Type::U8 => word.parse().ok().map(Const::U8),
+            Type::U16 => word.parse().ok().map(Const::U16),
+            Type::U32 => word.parse().ok().map(Const::U32),
+            Type::U64 => word.parse().ok().map(Const::U64),
+            Type::F32 => word.parse().ok().map(Const::F32),
+            Type::F64 => word.parse().ok().map(Const::F64),
+            Type::Bool => match &*word {
+                "0" | "false" => Some(Const::Bool(0)),
+                "1" | "true" => Some(Const::Bool(1)),
+                _ => None
+            },
+            Type::Void => (word == "VOID").then_some(Const::Void),
+            Type::Ptr => None,
+        };
+        c.ok_or_else(|| self.error(ECode::MismatchedTypes, format!("invalid `{}` constant `{}`", ty, word), span))
+    }
+
+    fn predicate(&mut self) -> Result<CmpPred, Error> {
+        let (word, span) = self.word()?;
+        match &*word {
+            "eq" => Ok(CmpPred::Eq),
+            "ne" => Ok(CmpPred::Ne),
+            "sgt" => Ok(CmpPred::SGt),
+            "slt" => Ok(CmpPred::SLt),
+            "sge" => Ok(CmpPred::SGe),
+            "sle" => Ok(CmpPred::SLe),
+            "ugt" => Ok(CmpPred::UGt),
+            "ult" => Ok(CmpPred::ULt),
+            "uge" => Ok(CmpPred::UGe),
+            "ule" => Ok(CmpPred::ULe),
+            _ => Err(self.error(ECode::UnexpectedToken, format!("unknown comparison predicate `{}`", word), span))
+        }
+    }
+
+    fn type_(&mut self) -> Result<(Type, Span), Error> {
+        let (word, span) = self.word()?;
+        let ty = match &*word {
+            "i8" => Type::I8,
+            "i16" => Type::I16,
+            "i32" => Type::I32,
+            "i64" => Type::I64,
+            "u8" => Type::U8,
+            "u16" => Type::U16,
+            "u32" => Type::U32,
+            "u64" => Type::U64,
+            "f32" => Type::F32,
+            "f64" => Type::F64,
+            "bool" => Type::Bool,
+            "void" => Type::Void,
+            "ptr" => Type::Ptr,
+            _ => return Err(self.error(ECode::UnexpectedToken, format!("unknown type `{}`", word), span))
+        };
+        Ok((ty, span))
+    }
+
+    fn value_ref(&mut self) -> Result<(usize, Span), Error> {
+        self.skip_trivia();
+        let start = self.cursor;
+        self.expect('%')?;
+        let (word, _) = self.word()?;
+        let span = self.span_from(start);
+        word.parse::<usize>()
+            .map(|id| (id, span))
+            .map_err(|_| self.error(ECode::ExpectedToken, format!("expected value id, found `%{}`", word), span))
+    }
+
+    /// Types separated by commas, `void` alone stands for none
+    fn returns(&mut self) -> Result<Vec<Type>, Error> {
+        let (first, _) = self.type_()?;
+        if first == Type::Void {
+            return Ok(Vec::new())
+        }
+        let mut returns = vec![first];
+        while self.eat(',') {
+            returns.push(self.type_()?.0);
+        }
+        Ok(returns)
+    }
+
+    fn stack_slot_ref(&self, word: &str, span: Span) -> Result<StackSlot, Error> {
+        word.strip_prefix("ss")
+            .and_then(|id| id.parse::<usize>().ok())
+            .map(StackSlot)
+            .ok_or_else(|| self.error(ECode::ExpectedToken, format!("expected stack slot, found `{}`", word), span))
+    }
+
+    fn align(&mut self) -> Result<u32, Error> {
+        self.keyword("align")?;
+        self.number()
+    }
+
+    fn number(&mut self) -> Result<u32, Error> {
+        let (word, span) = self.word()?;
+        word.parse::<u32>()
+            .map_err(|_| self.error(ECode::ExpectedToken, format!("expected a number, found `{}`", word), span))
+    }
+
+    /// A quoted byte string, characters outside of escapes are taken as their UTF-8 bytes
+    fn bytes(&mut self) -> Result<Vec<u8>, Error> {
+        self.expect('"')?;
+        let mut bytes: Vec<u8> = Vec::new();
+        loop {
+            let start = self.cursor;
+            match self.peek() {
+                Some('"') => {
+                    self.bump();
+                    return Ok(bytes)
+                },
+                Some('\\') => {
+                    self.bump();
+                    let digits: String = self.chars.iter().skip(self.cursor.pos).take(2).collect();
+                    self.bump();
+                    self.bump();
+                    let hex = digits.len() == 2 && digits.chars().all(|c| c.is_ascii_hexdigit());
+                    let byte = u8::from_str_radix(&digits, 16).ok().filter(|_| hex).ok_or_else(|| self.error(
+                        ECode::ExpectedToken,
+                        format!("expected two hex digits after `\\`, found `{}`", digits),
+                        self.span_from(start)
+                    ))?;
+                    bytes.push(byte);
+                },
+                Some(c) => {
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                    self.bump();
+                },
+                None => return Err(self.unexpected("`\"`"))
+            }
+        }
+    }
+
+    fn block_ref(&mut self) -> Result<(usize, Span), Error> {
+        let (word, span) = self.word()?;
+        word.strip_prefix('u')
+            .and_then(|id| id.parse::<usize>().ok())
+            .map(|id| (id, span))
+            .ok_or_else(|| self.error(ECode::ExpectedToken, format!("expected block label, found `{}`", word), span))
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<(), Error> {
+        let (word, span) = self.word()?;
+        if word != keyword {
+            return Err(self.error(ECode::ExpectedToken, format!("expected `{}`, found `{}`", keyword, word), span))
+        }
+        Ok(())
+    }
+
+    fn word(&mut self) -> Result<(String, Span), Error> {
+        self.skip_trivia();
+        let start = self.cursor;
+        let mut word = String::new();
+        while let Some(c) = self.peek() {
+            if !(c.is_alphanumeric() || "_.-+".contains(c)) {
+                break
+            }
+            word.push(c);
+            self.bump();
+        }
+        if word.is_empty() {
+            return Err(self.unexpected("a word"))
+        }
+        Ok((word, self.span_from(start)))
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), Error> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("`{}`", c)))
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_trivia();
+        if self.peek() == Some(c) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> Error {
+        let span = Span {
+            line: self.cursor.line,
+            column: self.cursor.column,
+            start_pos: self.cursor.pos,
+            end_pos: self.cursor.pos
+        };
+        match self.peek() {
+            Some(c) => self.error(ECode::ExpectedToken, format!("expected {}, found `{}`", expected, c), span),
+            None => self.error(ECode::UnexpectedEOF, format!("unexpected end of input, expected {}", expected), span)
+        }
+    }
+
+    fn skip_trivia(&mut self) {
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() {
+                self.bump();
+            } else if c == '/' && self.chars.get(self.cursor.pos + 1) == Some(&'/') {
+                while !matches!(self.peek(), Some('\n') | None) {
+                    self.bump();
+                }
+            } else {
+                break
+            }
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.cursor.pos).copied()
+    }
+
+    fn bump(&mut self) {
+        if self.peek() == Some('\n') {
+            self.cursor.line += 1;
+            self.cursor.column = 0;
+        } else {
+            self.cursor.column += 1;
+        }
+        self.cursor.pos += 1;
+    }
+
+    fn span_from(&self, start: Cursor) -> Span {
+        Span {
+            line: start.line,
+            column: start.column,
+            start_pos: start.pos,
+            end_pos: self.cursor.pos.max(start.pos + 1) - 1
+        }
+    }
+
+    fn error(&self, code: ECode, details: String, span: Span) -> Error {
+        Error {
+            code,
+            details,
+            span,
+            src: self.src.clone(),
+            path: self.path.clone(),
+            note: None,
+            help: None
+        }
+    }
+}
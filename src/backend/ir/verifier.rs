@@ -0,0 +1,340 @@
+//! # Kese IR Verifier
+//!
+//! * Checks the invariants the builders do not enforce:
+//!   - every block ends in exactly one terminator
+//!   - every value is defined once and every use refers to a definition
+//!   - operands, block call arguments and returns agree on their types
+//!   - conversions go between types they can convert
+//!   - memory is accessed through `ptr` values with power of two alignments
+//!     and stack slots and global data are declared before use
+//!   - calls and returns match the signatures of the functions involved
+//! * Every violation is reported, tagged with the function alias and block id,
+//!   or with the alias of the global data at fault
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use super::{codegen::context::Module, entities::*, inst::*};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifierError {
+    pub function: String,
+    pub block: Option<BlockID>,
+    pub message: String
+}
+
+impl fmt::Display for VerifierError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.block {
+            Some(block) => write!(f, "@{} {}: {}", self.function, block, self.message),
+            None => write!(f, "@{}: {}", self.function, self.message),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Verifier {
+    errors: Vec<VerifierError>,
+    /// Signatures of the functions and externs calls may target, `None`
+    /// when verifying a function on its own
+    signatures: Option<HashMap<String, FunctionSignature>>,
+    /// Aliases of the module's global data, `None` when verifying a function on its own
+    globals: Option<HashSet<String>>
+}
+
+fn type_list(types: &[Type]) -> String {
+    types.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+/// Returned types as written in a signature, `void` when there are none
+fn return_list(types: &[Type]) -> String {
+    if types.is_empty() { Type::Void.to_string() } else { type_list(types) }
+}
+
+impl Verifier {
+    pub fn new() -> Self {
+        Self { errors: Vec::new(), signatures: None, globals: None }
+    }
+
+    pub fn verify_module(&mut self, module: &Module) -> Result<(), Vec<VerifierError>> {
+        self.errors.clear();
+        let externs = module.externs.iter().map(|e| (e.alias.clone(), e.sig.clone()));
+        let functions = module.functions.iter().map(|f| (f.alias.clone(), f.sig.clone()));
+        self.signatures = Some(externs.chain(functions).collect());
+
+        let mut globals: HashSet<String> = HashSet::new();
+        for global in &module.globals {
+            let mut report = |message: String| self.errors.push(VerifierError {
+                function: global.alias.clone(),
+                block: None,
+                message
+            });
+            if !globals.insert(global.alias.clone()) {
+                report("global data is defined more than once".to_string());
+            }
+            if !global.align.is_power_of_two() {
+                report(format!("global data is aligned to {}, not a power of two", global.align));
+            }
+        }
+        self.globals = Some(globals);
+
+        for function in &module.functions {
+            self.check_function(function);
+        }
+        self.signatures = None;
+        self.globals = None;
+        self.take_errors()
+    }
+
+    /// Calls are only checked when they target `function` itself, the
+    /// signatures of other callees and global data need the whole module
+    pub fn verify_function(&mut self, function: &Function) -> Result<(), Vec<VerifierError>> {
+        self.errors.clear();
+        self.check_function(function);
+        self.take_errors()
+    }
+
+    fn take_errors(&mut self) -> Result<(), Vec<VerifierError>> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn report(&mut self, function: &Function, block: Option<&Block>, message: String) {
+        self.errors.push(VerifierError {
+            function: function.alias.clone(),
+            block: block.map(|b| b.id),
+            message
+        });
+    }
+
+    fn check_function(&mut self, function: &Function) {
+        if function.blocks.is_empty() {
+            self.report(function, None, "function has no blocks".to_string());
+            return
+        }
+
+        for (id, slot) in function.stack_slots.iter().enumerate() {
+            if !slot.align.is_power_of_two() {
+                self.report(function, None, format!("`{}` is aligned to {}, not a power of two", StackSlot(id), slot.align));
+            }
+        }
+
+        // Function parameters are the first values, followed by every block
+        // parameter and instruction result
+        let mut defs: HashMap<usize, Type> = HashMap::new();
+        for (id, ty) in function.sig.params.iter().enumerate() {
+            defs.insert(id, *ty);
+        }
+
+        let mut blocks: HashMap<usize, &Block> = HashMap::new();
+        for block in &function.blocks {
+            if blocks.insert(block.id.0, block).is_some() {
+                self.report(function, Some(block), "block is defined more than once".to_string());
+            }
+            let results = block.insts.iter().flat_map(|inst| inst.results());
+            for value in block.params.iter().map(|p| ValueID::from(*p)).chain(results) {
+                if defs.insert(value.0, value.1).is_some() {
+                    self.report(function, Some(block), format!("value `{}` is defined more than once", value));
+                }
+            }
+        }
+
+        for block in &function.blocks {
+            self.check_block(function, block, &defs, &blocks);
+        }
+    }
+
+    fn check_block(&mut self, function: &Function, block: &Block, defs: &HashMap<usize, Type>, blocks: &HashMap<usize, &Block>) {
+        match block.insts.last() {
+            None => self.report(function, Some(block), "block is empty".to_string()),
+            Some(inst) if !inst.is_terminator() => {
+                self.report(function, Some(block), "block does not end in a terminator".to_string())
+            },
+            _ => {}
+        }
+
+        for (i, inst) in block.insts.iter().enumerate() {
+            if inst.is_terminator() && i + 1 != block.insts.len() {
+                self.report(function, Some(block), format!("terminator `{}` is not the last instruction", inst));
+            }
+
+            for value in inst.uses() {
+                match defs.get(&value.0) {
+                    None => self.report(function, Some(block), format!("use of undefined value `{}`", value)),
+                    Some(ty) if *ty != value.1 => self.report(
+                        function,
+                        Some(block),
+                        format!("`{}` is used as `{}` but defined as `{}`", value, value.1, ty)
+                    ),
+                    _ => {}
+                }
+            }
+
+            match inst {
+                Inst::Assign { dest, op } => self.check_op(function, block, *dest, op),
+                Inst::Store { value, address, align } => {
+                    if value.1 == Type::Void {
+                        self.report(function, Some(block), format!("`{}` stores a `void` value", inst));
+                    }
+                    if address.1 != Type::Ptr {
+                        self.report(function, Some(block), format!(
+                            "`{}` stores through `{}` of type `{}`, expected `ptr`", inst, address, address.1
+                        ));
+                    }
+                    self.check_align(function, block, &inst.to_string(), *align);
+                },
+                Inst::Call { results, callee, args } => self.check_fn_call(function, block, inst, results, callee, args),
+                Inst::Ret(values) => {
+                    let types: Vec<Type> = values.iter().map(|v| v.1).collect();
+                    if types != function.sig.returns {
+                        self.report(function, Some(block), format!(
+                            "`{}` returns `{}` from a function returning `{}`",
+                            inst, return_list(&types), function.sig.display_returns()
+                        ));
+                    }
+                },
+                Inst::Jmp(call) => self.check_call(function, block, call, blocks),
+                Inst::Branch { condition, true_path, false_path } => {
+                    if condition.1 != Type::Bool {
+                        self.report(function, Some(block), format!(
+                            "branch condition `{}` has type `{}`, expected `bool`", condition, condition.1
+                        ));
+                    }
+                    self.check_call(function, block, true_path, blocks);
+                    self.check_call(function, block, false_path, blocks);
+                }
+            }
+        }
+    }
+
+    fn check_fn_call(&mut self, function: &Function, block: &Block, inst: &Inst, results: &[ValueID], callee: &str, args: &[ValueID]) {
+        let sig = match &self.signatures {
+            Some(signatures) => signatures.get(callee).cloned(),
+            None => (callee == function.alias).then(|| function.sig.clone())
+        };
+        let Some(sig) = sig else {
+            if self.signatures.is_some() {
+                self.report(function, Some(block), format!("call to undefined function `@{}`", callee));
+            }
+            return
+        };
+
+        let types: Vec<Type> = args.iter().map(|v| v.1).collect();
+        if types != sig.params {
+            self.report(function, Some(block), format!(
+                "`{}` passes `({})` to `@{}` taking `({})`",
+                inst, type_list(&types), callee, type_list(&sig.params)
+            ));
+        }
+        let types: Vec<Type> = results.iter().map(|v| v.1).collect();
+        if types != sig.returns {
+            self.report(function, Some(block), format!(
+                "`{}` expects `{}` from `@{}` returning `{}`",
+                inst, return_list(&types), callee, sig.display_returns()
+            ));
+        }
+    }
+
+    fn check_call(&mut self, function: &Function, block: &Block, call: &BlockCall, blocks: &HashMap<usize, &Block>) {
+        let Some(target) = blocks.get(&call.block.0) else {
+            self.report(function, Some(block), format!("call to undefined block `{}`", call.block));
+            return
+        };
+
+        if call.args.len() != target.params.len() {
+            self.report(function, Some(block), format!(
+                "`{}` passes {} argument(s) to a block taking {}",
+                call, call.args.len(), target.params.len()
+            ));
+            return
+        }
+        for (arg, param) in call.args.iter().zip(&target.params) {
+            if arg.1 != param.1 {
+                self.report(function, Some(block), format!(
+                    "`{}` passes `{}` of type `{}` to parameter `%{}` of type `{}`",
+                    call, arg, arg.1, param.0, param.1
+                ));
+            }
+        }
+    }
+
+    fn check_align(&mut self, function: &Function, block: &Block, inst: &str, align: u32) {
+        if !align.is_power_of_two() {
+            self.report(function, Some(block), format!("`{}` is aligned to {}, not a power of two", inst, align));
+        }
+    }
+
+    fn check_op(&mut self, function: &Function, block: &Block, dest: ValueID, op: &Op) {
+        let operands = op.operands();
+        if let [left, right] = operands[..] {
+            if left.1 != right.1 {
+                self.report(function, Some(block), format!(
+                    "`{} = {}` mixes `{}` and `{}`", dest, op, left.1, right.1
+                ));
+                return
+            }
+        }
+
+        if let Some((value, ty)) = op.conversion() {
+            if !op.converts(value.1, ty) {
+                self.report(function, Some(block), format!(
+                    "`{} = {}` cannot convert `{}` to `{}`", dest, op, value.1, ty
+                ));
+                return
+            }
+        }
+
+        match op {
+            Op::StackAddr(slot) if slot.0 >= function.stack_slots.len() => {
+                self.report(function, Some(block), format!(
+                    "`{} = {}` uses undefined stack slot `{}`", dest, op, slot
+                ));
+                return
+            },
+            Op::GlobalAddr(alias) if self.globals.as_ref().is_some_and(|g| !g.contains(alias)) => {
+                self.report(function, Some(block), format!(
+                    "`{} = {}` uses undefined global data `@{}`", dest, op, alias
+                ));
+                return
+            },
+            Op::Load { ty: Type::Void, .. } => {
+                self.report(function, Some(block), format!("`{} = {}` loads a `void` value", dest, op));
+                return
+            },
+            Op::Load { align, .. } => self.check_align(function, block, &format!("{} = {}", dest, op), *align),
+            _ => {}
+        }
+
+        let accepts: fn(&Type) -> bool = match op {
+            Op::Const(_) | Op::StackAddr(_) | Op::GlobalAddr(_) => |_| true,
+            Op::Load { .. } => |ty| *ty == Type::Ptr,
+            // Checked against the target type above
+            Op::SExt(..) | Op::UExt(..) | Op::Trunc(..) | Op::Bitcast(..) | Op::FCvt(..)
+            | Op::SIToF(..) | Op::UIToF(..) | Op::FToSI(..) | Op::FToUI(..) => |_| true,
+            Op::IAdd { .. } | Op::ISub { .. } | Op::IMul { .. }
+            | Op::IShl { .. } | Op::UShr { .. } | Op::SShr { .. } | Op::INeg(_) => Type::is_int,
+            Op::SDiv { .. } | Op::SRem { .. } => Type::is_signed,
+            Op::UDiv { .. } | Op::URem { .. } => Type::is_unsigned,
+            Op::FAdd { .. } | Op::FSub { .. } | Op::FMul { .. } | Op::FDiv { .. }
+            | Op::FRem { .. } | Op::FNeg(_) | Op::FCmp { .. } => Type::is_float,
+            Op::BNot(_) | Op::BOr { .. } | Op::BAnd { .. } | Op::BXor { .. }
+            | Op::ICmp { .. } => |ty| ty.is_int() || *ty == Type::Bool,
+        };
+        if let Some(operand) = operands.iter().find(|v| !accepts(&v.1)) {
+            self.report(function, Some(block), format!(
+                "`{} = {}` does not accept an operand of type `{}`", dest, op, operand.1
+            ));
+            return
+        }
+
+        let result = op.result_type();
+        if dest.1 != result {
+            self.report(function, Some(block), format!(
+                "`{} = {}` produces `{}` but its result is typed `{}`", dest, op, result, dest.1
+            ));
+        }
+    }
+}
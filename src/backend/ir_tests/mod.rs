@@ -90,7 +90,880 @@ mod tests {
         eprintln!("{}", module.display());
     }
 
+    // -- Lowering Tests --
+    fn lower(src: &str) -> Module {
+        let src = src.to_string();
+        let path = "test.kese".to_string();
+        let tokens = crate::frontend::tokenize(&src);
+        let (ast, errors) = crate::frontend::Parser::new(tokens, &src, &path).parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        crate::backend::ASTCompiler::new(ast, src, path)
+            .compile_module()
+            .expect("lowering failed")
+    }
+
+    /// Type checks `src`, returning the details of every error
+    fn check(src: &str) -> Vec<String> {
+        let src = src.to_string();
+        let path = "test.kese".to_string();
+        let tokens = crate::frontend::tokenize(&src);
+        let (ast, errors) = crate::frontend::Parser::new(tokens, &src, &path).parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        crate::frontend::TypeChecker::new(ast, src, path)
+            .check()
+            .into_iter()
+            .map(|e| e.details)
+            .collect()
+    }
+
+    /// ## Lowered if expression
+    /// 
+    /// * Both arms jump into a join block whose parameters carry the
+    ///   `if` value and the mutated `x`
+    #[test]
+    fn lower_if() {
+        let module = lower("mut x := 1;\ny := if x > 0 { x = 2; 10 } else { 5 };");
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains("br %2 u1() u2()"));
+        assert!(ir.contains("jmp u3(%4, %3)"));
+        assert!(ir.contains("jmp u3(%5, %0)"));
+        assert!(ir.contains("u3(i32 %6, i32 %7):"));
+    }
+
+    /// ## Lowered functions
+    /// 
+    /// * A `func` declaration becomes its own IR function with a matching
+    ///   signature, and an early `return` ends its block
+    #[test]
+    fn lower_function() {
+        let module = lower("func max(a: i32, b: i32) -> i32 {\n    if a > b { return a; }\n    b\n}");
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains(
+            "func i32 : @max(i32 %0, i32 %1) {\nu0():\n  %2 = icmp sgt %0 %1\n  br %2 u1() u2()\nu1():\n  ret %0\nu2():\n  ret %1\n}"
+        ));
+        assert_eq!(Verifier::new().verify_module(&module), Ok(()));
+    }
+
+    /// ## Lowered structs
+    /// 
+    /// * Struct values are kept per field, so a field mutated in one arm
+    ///   is joined through a single block parameter
+    #[test]
+    fn lower_struct() {
+        let module = lower(
+            "struct Point { x: i32, y: i32 }\n\
+             mut p := Point { y: 2, x: 1 };\n\
+             if p.x > 0 { p.y = 5; } else { p.x = 3; };\n\
+             z := p.x + p.y;"
+        );
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains("%0 = const i32 : 2\n  %1 = const i32 : 1"));
+        assert!(ir.contains("jmp u3(%1, %4)"));
+        assert!(ir.contains("jmp u3(%5, %0)"));
+        assert!(ir.contains("u3(i32 %6, i32 %7):\n  %8 = iadd %6 %7"));
+    }
+
+    /// ## Struct layout
+    /// 
+    /// * Fields are padded to their alignment in declaration order
+    #[test]
+    fn struct_layout() {
+        use crate::frontend::{Type, TypeRegistry};
+
+        let mut registry = TypeRegistry::new();
+        registry.define_struct("Inner", vec![("a".to_string(), Type::UInt8), ("b".to_string(), Type::Int64)]);
+        let layout = registry.define_struct("Outer", vec![
+            ("flag".to_string(), Type::Boolean),
+            ("inner".to_string(), Type::Alias("Inner".to_string())),
+            ("count".to_string(), Type::Int16)
+        ]);
+
+        let offsets: Vec<usize> = layout.fields.iter().map(|f| f.offset).collect();
+        assert_eq!(offsets, vec![0, 8, 24]);
+        assert_eq!((layout.size, layout.align), (32, 8));
+    }
+
+    /// ## Structs across calls
+    /// 
+    /// * Struct arguments are copied to a stack slot at their layout offsets
+    ///   and passed as its address, as in `tests/structs.kese`
+    /// * Struct results are written through an address passed ahead of the arguments
+    #[test]
+    fn struct_calls() {
+        use crate::backend::ir::lower::Lowerer;
+
+        let module = lower(include_str!("../../../tests/structs.kese"));
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains("  ss0 = stack_slot 8 align 4\n  ss1 = stack_slot 8 align 4\n"));
+        assert!(ir.contains("store %5 %8 align 4\n  %9 = bitcast u64 %8\n  %10 = const u64 : 4\n  %11 = iadd %9 %10\n  %12 = bitcast ptr %11\n  store %1 %12 align 4\n  call void @shift(%7, %8, %6)\n  %13 = load i32 %7 align 4"));
+        assert!(ir.contains("func void : @shift(ptr %0, ptr %1, i32 %2) {\nu0():\n  %3 = load i32 %1 align 4"));
+        assert!(ir.contains("%9 = iadd %3 %2\n  store %9 %0 align 4"));
+        assert_eq!(Verifier::new().verify_module(&module), Ok(()));
+
+        let mut optimized = lower(include_str!("../../../tests/structs.kese"));
+        Optimizer::new(&mut optimized).with_level(OptLevel::O2).run();
+        let mut lowerer = Lowerer::new(optimized.name()).expect("no native target");
+        lowerer.lower_module(&optimized).expect("lowering to Cranelift failed");
+    }
+
+    /// ## Enums across calls
+    /// 
+    /// * Enum arguments and results are passed through memory like structs, as in
+    ///   `tests/enums.kese`
+    /// * The tag is always written, then a branch per variant with a payload
+    ///   writes the payload of the active one
+    #[test]
+    fn enum_calls() {
+        use crate::backend::ir::lower::Lowerer;
+
+        let module = lower(include_str!("../../../tests/enums.kese"));
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains("store %2 %19 align 4\n  %20 = const u32 : 0\n  %21 = icmp eq %2 %20\n  br %21 u8() u9()"));
+        assert!(ir.contains("u9():\n  %26 = const u32 : 1\n  %27 = icmp eq %2 %26\n  br %27 u10() u11()"));
+        assert!(ir.contains("u11():\n  call void @widen(%18, %19)\n  %36 = load u32 %18 align 4"));
+        assert!(ir.contains("func void : @widen(ptr %0, ptr %1) {\nu0():\n  %2 = load u32 %1 align 4"));
+        assert_eq!(Verifier::new().verify_module(&module), Ok(()));
+
+        let mut optimized = lower(include_str!("../../../tests/enums.kese"));
+        Optimizer::new(&mut optimized).with_level(OptLevel::O2).run();
+        let mut lowerer = Lowerer::new(optimized.name()).expect("no native target");
+        lowerer.lower_module(&optimized).expect("lowering to Cranelift failed");
+    }
+
+    /// ## Lowered match
+    /// 
+    /// * Each refutable arm compares the tag and branches on to the next arm,
+    ///   the last arm is entered without a test
+    #[test]
+    fn lower_match() {
+        let module = lower(
+            "enum Shape { Circle(i32), Rect(i32, i32), Empty }\n\
+             s := Shape::Rect(4, 3);\n\
+             a := match s { Shape::Circle(r) => r * r, Shape::Rect(w, h) => w * h, Shape::Empty => 0 };"
+        );
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains("%2 = const u32 : 1\n  %3 = const i32 : 0"));
+        assert!(ir.contains("%4 = const u32 : 0\n  %5 = icmp eq %2 %4\n  br %5 u1() u2()"));
+        assert!(ir.contains("u2():\n  %7 = const u32 : 1\n  %8 = icmp eq %2 %7\n  br %8 u3() u4()"));
+        assert!(ir.contains("u4():\n  %10 = const i32 : 0\n  jmp u5(%10)"));
+        assert_eq!(Verifier::new().verify_module(&module), Ok(()));
+    }
+
+    /// ## Lowered loops
+    /// 
+    /// * Variables assigned in the body become header parameters, fed by the
+    ///   entry jump, `continue` and the end of the body
+    /// * The failing `while` condition and `break` are joined after the loop
+    #[test]
+    fn lower_loop() {
+        let module = lower(
+            "func count(n: i32) -> i32 {\n\
+                 mut i := 0;\n\
+                 mut odd := 0;\n\
+                 while i < n {\n\
+                     i = i + 1;\n\
+                     if i / 2 * 2 == i { continue; };\n\
+                     odd = odd + 1;\n\
+                 };\n\
+                 odd\n\
+             }\n\
+             x := loop { break 3; };"
+        );
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains("jmp u1(%1, %2)\nu1(i32 %3, i32 %4):\n  %5 = icmp slt %3 %0\n  br %5 u2() u3()"));
+        assert!(ir.contains("u4():\n  jmp u1(%7, %4)"));
+        assert!(ir.contains("%14 = iadd %4 %13\n  jmp u1(%7, %14)"));
+        assert!(ir.contains("u3():\n  ret %4"));
+        assert!(ir.contains("u0():\n  jmp u1()\nu1():\n  %0 = const i32 : 3"));
+        assert_eq!(Verifier::new().verify_module(&module), Ok(()));
+    }
+
+    /// ## Match exhaustiveness
+    /// 
+    /// * Missing variants are reported with a witness pattern
+    /// * Arms covered by earlier arms are rejected
+    #[test]
+    fn match_exhaustiveness() {
+        let decl = "enum E { A, B(bool), C(E2) }\nenum E2 { X, Y }\nx := E::A;\n";
+
+        assert_eq!(check(&format!("{}y := match x {{ E::A => 1, E::B(true) => 2, E::C(_) => 3 }};", decl)),
+            vec!["pattern `E::B(false)` not covered"]);
+        assert_eq!(check(&format!("{}y := match x {{ E::C(E2::X) => 1, E::A => 2, E::B(_) => 3 }};", decl)),
+            vec!["pattern `E::C(E2::Y)` not covered"]);
+        assert_eq!(check(&format!("{}y := match x {{ E::B(b) => 1, _ => 2, E::A => 3 }};", decl)),
+            vec!["unreachable pattern"]);
+        assert!(check(&format!("{}y := match x {{ E::B(true) => 1, E::B(false) => 2, E::C(E2::X) => 3, _ => 4 }};", decl))
+            .is_empty());
+        assert_eq!(check("x := 3;\ny := match x { 1 => 1, 2 => 2 };"), vec!["pattern `_` not covered"]);
+    }
+
+    /// ## Block scoping
+    /// 
+    /// * Names declared in a block are gone once it ends
+    /// * Nested blocks may shadow a name, the same scope may not redeclare it
+    #[test]
+    fn block_scoping() {
+        assert_eq!(check("if true { y := 2; };\nz := y;"), vec!["cannot find `y` in scope"]);
+        assert!(check("x := 1;\ny := { x := true; 2 } + x;").is_empty());
+        assert_eq!(check("x := 1;\nx := 2;"), vec!["`x` is already declared in this scope"]);
+
+        let module = lower("x := 1;\ny := { x := 2; x * 10 } + x;");
+        assert!(module.display().contains("%3 = imul %1 %2\n  %4 = iadd %3 %0"));
+    }
+
+    /// ## Literal types
+    /// 
+    /// * Initializers must match the declared type
+    /// * Integer and float literals take the type they are assigned to, or
+    ///   the type of the other operand, and must fit in it
+    /// * Integer literals wider than `i64` are still integers, so the full
+    ///   `u64` range and `i64::MIN` can be written
+    #[test]
+    fn literal_types() {
+        assert_eq!(check("x: i32 := true;"), vec!["expected `i32`, found `bool`"]);
+        assert_eq!(check("x: u8 := 256;"), vec!["integer literal `256` is out of range for `u8`"]);
+        assert_eq!(check("x: i8 := -129;"), vec!["integer literal `-129` is out of range for `i8`"]);
+        assert!(check("x: i8 := -128;\ny: u64 := 10000000000;").is_empty());
+        assert!(check("x: u64 := 18446744073709551615;\ny: i64 := -9223372036854775808;").is_empty());
+        assert_eq!(
+            check("x: u64 := 18446744073709551616;"),
+            vec!["integer literal `18446744073709551616` is out of range for `u64`"]
+        );
+
+        let module = lower("x: u8 := 10;\ny := 2 * x;\nz: f32 := 1.5;");
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains("%0 = const u8 : 10\n  %1 = const u8 : 2\n  %2 = imul %1 %0"));
+        assert!(ir.contains("%3 = const f32 : 1.5"));
+
+        let ir = lower("x: u64 := 18446744073709551615;\ny: i64 := -9223372036854775808;").display();
+        assert!(ir.contains("%0 = const u64 : 18446744073709551615\n  %1 = const i64 : -9223372036854775808"));
+    }
+
+    /// ## Definite assignment
+    ///
+    /// * A binding declared without a value must be assigned on every path
+    ///   before it is read, and takes the type of its first assignment
+    /// * Immutable bindings may be assigned once, never twice or once per
+    ///   loop iteration, assignments before the loop do not count as inside it
+    /// * No binding can hold `unit`, whether declared with it or assigned it
+    #[test]
+    fn definite_assignment() {
+        assert_eq!(check("x;\nx + 5"), vec!["`x` is used before being assigned"]);
+        assert_eq!(
+            check("x;\nif true { x = 1 };\nx + 5"),
+            vec!["`x` is possibly used before being assigned"]
+        );
+        assert!(check("x;\nif true { x = 1 } else { x = 2 };\nx + 5").is_empty());
+        assert!(check("x;\nloop { x = 1; break };\nx + 5").is_empty());
+        assert!(check("x;\nmatch 3 { 1 => { x = 1 }, _ => { x = 2 } };\nx + 5").is_empty());
+        assert_eq!(check("mut x;\nwhile true { x = 1 };\nx"), vec!["`x` is possibly used before being assigned"]);
+        assert_eq!(check("x;\nx = true;\nx + 5"), vec!["cannot do `+` operation on types `bool`, `i32`"]);
+        assert_eq!(check("x;\nx = 1;\nx = 2;"), vec!["cannot assign twice to immutable variable `x`"]);
+        assert_eq!(
+            check("x;\nwhile true { x = 1 };"),
+            vec!["cannot assign to immutable variable `x` inside a loop"]
+        );
+        assert!(check("x: i32;\nc := true;\nmut i := 0;\nif c { x = 1; } else { };\nwhile i < 3 { i = i + 1; }").is_empty());
+        assert_eq!(check("y := {};"), vec!["`y` cannot hold a value of type `unit`"]);
+        assert_eq!(check("y := loop { break; };"), vec!["`y` cannot hold a value of type `unit`"]);
+        assert_eq!(check("c := true;\nw := while c {};"), vec!["`w` cannot hold a value of type `unit`"]);
+        assert_eq!(check("func f() {}\ny;\ny = f();"), vec!["`y` cannot hold a value of type `unit`"]);
+
+        let module = lower("x: u8;\nif true { x = 1 } else { x = 2 };\nx + 3");
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains("u3(u8 %3):\n  %4 = const u8 : 3\n  %5 = iadd %3 %4"));
+    }
+
+    /// ## Casts
+    ///
+    /// * Integers extend by their own signedness, narrow by truncation and
+    ///   convert to and from floats, booleans only become integers
+    /// * The lowered conversions pass the verifier and Cranelift
+    #[test]
+    fn casts() {
+        use crate::backend::ir::lower::Lowerer;
+
+        assert_eq!(check("x := 1 as bool;"), vec!["cannot cast `i32` as `bool`"]);
+        assert_eq!(check("x := true as f64;"), vec!["cannot cast `bool` as `f64`"]);
+        assert_eq!(check("x := 1 as string;"), vec!["cannot cast `i32` as `string`"]);
+        assert!(check("x: u8 := 1;\ny := x as i64 + 2;").is_empty());
+
+        let module = lower(
+            "a: u8 := 200;\nb := a as i64;\nc := -1 as u8 as i8;\nd := 3.5 as i32 + b as i32;\ne := true as u16;\nf := d as f32 as f64;"
+        );
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains("%1 = uext i64 %0"));
+        assert!(ir.contains("%3 = trunc u8 %2\n  %4 = bitcast i8 %3"));
+        assert!(ir.contains("%6 = ftosi i32 %5\n  %7 = trunc i32 %1"));
+        assert!(ir.contains("%10 = uext u16 %9"));
+        assert!(ir.contains("%11 = sitof f32 %8\n  %12 = fcvt f64 %11"));
+        assert_eq!(Verifier::new().verify_module(&module), Ok(()));
+
+        let mut context = Context::new();
+        assert_eq!(ir, context.parse_module("test", &ir, "test.kir").unwrap().display());
+
+        let mut lowerer = Lowerer::new(module.name()).expect("no native target");
+        lowerer.lower_module(&module).expect("lowering to Cranelift failed");
+    }
+
+    /// ## Logical and bitwise operators
+    ///
+    /// * `&&` and `||` branch around their right operand and join the
+    ///   result in a block parameter
+    /// * Bitwise operators take integers or booleans, shifts only integers
+    #[test]
+    fn logical_operators() {
+        assert_eq!(check("x := 1 && true;"), vec!["cannot do `&&` operation on types `i32`, `bool`"]);
+        assert_eq!(check("x := 1.5 & 2.5;"), vec!["cannot do `&` operation on types `f64`, `f64`"]);
+        assert_eq!(check("x := true << true;"), vec!["cannot do `<<` operation on types `bool`, `bool`"]);
+        assert_eq!(
+            check("x;\ny := false && { x = 1; true };\nx"),
+            vec!["`x` is possibly used before being assigned"]
+        );
+        assert!(check("x: u8 := 6;\ny := x % 4 | 1 << 2 ^ x & 3;\nz := true ^ false;").is_empty());
+
+        let module = lower("a := 5;\nb := a > 2 && a < 10;\nc := a == 0 || b;\nd := a % 3 ^ a & 6;");
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains("%2 = icmp sgt %0 %1\n  br %2 u1() u2()"));
+        assert!(ir.contains("u2():\n  jmp u3(%2)\nu3(bool %5):"));
+        assert!(ir.contains("%7 = icmp eq %0 %6\n  br %7 u5() u4()"));
+        assert!(ir.contains("u5():\n  jmp u6(%7)\nu6(bool %8):"));
+        assert!(ir.contains("%10 = srem %0 %9\n  %11 = const i32 : 6\n  %12 = band %0 %11\n  %13 = bxor %10 %12"));
+        assert_eq!(Verifier::new().verify_module(&module), Ok(()));
+    }
+
+    /// ## Inequality
+    ///
+    /// * `!=` is lexed as one operator and lowers to `icmp ne`, or to a
+    ///   negated runtime call for strings
+    /// * An operator with no binary form is a parse error
+    #[test]
+    fn inequality() {
+        let module = lower("a := 1 != 2;\nfunc f(a: string, b: string) -> bool { a != b }");
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains("%2 = icmp ne %0 %1"));
+        assert!(ir.contains("%4 = call bool @kese_str_eq(%0, %1, %2, %3)\n  %5 = bnot %4\n  ret %5"));
+        assert_eq!(Verifier::new().verify_module(&module), Ok(()));
+
+        let src = "a := 3 ! 4;".to_string();
+        let path = "test.kese".to_string();
+        let tokens = crate::frontend::tokenize(&src);
+        let (_, errors) = crate::frontend::Parser::new(tokens, &src, &path).parse_program();
+        assert_eq!(errors.into_iter().map(|e| e.details).collect::<Vec<_>>(), vec!["`!` is not a binary operator"]);
+    }
+
+    // -- Cranelift Lowering Tests --
+    /// ## Object emission
+    /// 
+    /// * Lowers the `control_flow` shape to Cranelift and checks an object file comes out
+    /// * Float remainders call the C library's `fmod`
+    #[test]
+    fn emit_object() {
+        use crate::backend::ir::lower::Lowerer;
+
+        let module = lower("x := 5;\nif x > 3 { x * 2 } else { x - 1 }");
+        let mut lowerer = Lowerer::new(module.name()).expect("no native target");
+        lowerer.lower_module(&module).expect("lowering to Cranelift failed");
+        let bytes = lowerer.finish().unwrap();
+
+        if cfg!(target_os = "linux") {
+            assert_eq!(&bytes[..4], b"\x7fELF");
+        }
+        // Float remainders import `fmod` and `fmodf` rather than being open-coded
+        let module = lower("func r(a: f64, b: f64) -> f64 { a % b }\nfunc s(a: f32, b: f32) -> f32 { a % b }");
+        let mut lowerer = Lowerer::new(module.name()).expect("no native target");
+        lowerer.lower_module(&module).expect("lowering to Cranelift failed");
+        let bytes = lowerer.finish().unwrap();
+
+        assert!(bytes.windows(5).any(|w| w == b"fmod\0"));
+        assert!(bytes.windows(6).any(|w| w == b"fmodf\0"));
+    }
+
+    /// ## Stack slots
+    /// 
+    /// * `tests/ir/stack.kir` prints back as it was read, passes the verifier
+    ///   and lowers to Cranelift
+    /// * Memory is only accessed through `ptr` values with power of two alignments
+    /// * With stack locals, `mut` variables are stored once declared and loaded
+    ///   at every read
+    #[test]
+    fn stack_slots() {
+        use crate::backend::ir::lower::Lowerer;
+
+        let src = include_str!("../../../tests/ir/stack.kir");
+        let mut context = Context::new();
+        let module = context.parse_module("stack", src, "stack.kir").unwrap();
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains("func i64 : @swap(i64 %0, i64 %1) {\n  ss0 = stack_slot 8 align 8\n  ss1 = stack_slot 8 align 8\nu0():"));
+        assert!(ir.contains("%7 = load i64 %3 align 4"));
+        assert_eq!(Verifier::new().verify_module(module), Ok(()));
+        let mut context = Context::new();
+        assert_eq!(ir, context.parse_module("stack", &ir, "stack.kir").unwrap().display());
+
+        let mut lowerer = Lowerer::new(module.name()).expect("no native target");
+        lowerer.lower_module(module).expect("lowering to Cranelift failed");
+
+        let src = "func i32 : @f(i32 %0) {\n  ss0 = stack_slot 4 align 3\nu0():\n  %1 = stack_addr ss0\n  %2 = stack_addr ss1\n  store %0 %1 align 2\n  %3 = load i32 %0 align 4\n  ret %3\n}";
+        let mut context = Context::new();
+        assert_eq!(
+            context.parse_module("f", src, "f.kir").unwrap_err().details,
+            "use of undefined stack slot `ss1`"
+        );
+        let src = src.replace("  %2 = stack_addr ss1\n", "");
+        let mut context = Context::new();
+        let errors: Vec<String> = Verifier::new()
+            .verify_module(context.parse_module("f", &src, "f.kir").unwrap())
+            .unwrap_err()
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(errors, vec![
+            "@f: `ss0` is aligned to 3, not a power of two",
+            "@f u0: `%3 = load i32 %0 align 4` does not accept an operand of type `i32`"
+        ]);
+
+        let src = "mut x := 1;\nmut p := 2.5;\nx = x + 2;\ny := x * 3;".to_string();
+        let path = "test.kese".to_string();
+        let tokens = crate::frontend::tokenize(&src);
+        let (ast, _) = crate::frontend::Parser::new(tokens, &src, &path).parse_program();
+        let module = crate::backend::ASTCompiler::new(ast, src, path)
+            .with_stack_locals()
+            .compile_module()
+            .expect("lowering failed");
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains("ss0 = stack_slot 4 align 4\n  ss1 = stack_slot 8 align 8"));
+        assert!(ir.contains("%1 = stack_addr ss0\n  store %0 %1 align 4"));
+        assert!(ir.contains("%4 = load i32 %1 align 4\n  %5 = const i32 : 2\n  %6 = iadd %4 %5\n  store %6 %1 align 4\n  %7 = load i32 %1 align 4"));
+        assert_eq!(Verifier::new().verify_module(&module), Ok(()));
+        let mut lowerer = Lowerer::new(module.name()).expect("no native target");
+        lowerer.lower_module(&module).expect("lowering to Cranelift failed");
+    }
+
+    /// ## Calls
+    /// 
+    /// * `tests/ir/calls.kir` returns several values from one function and
+    ///   calls two externs, it prints back as it was read and lowers to Cranelift
+    /// * Calls are checked against the signature of their callee and kept by
+    ///   dead code elimination even when their results are unused
+    /// * Calls in Kese source become `call` instructions, `void` functions return nothing
+    #[test]
+    fn calls() {
+        use crate::backend::ir::lower::Lowerer;
+
+        let src = include_str!("../../../tests/ir/calls.kir");
+        let mut context = Context::new();
+        let module = context.parse_module("calls", src, "calls.kir").unwrap();
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.starts_with("extern i32 : @abs(i32)\nextern void : @exit(i32)\nfunc i32, i32 : @divmod(i32 %0, i32 %1) {"));
+        assert!(ir.contains("ret %2, %3\n}"));
+        assert!(ir.contains("%2, %3 = call i32, i32 @divmod(%0, %1)\n  %4 = call i32 @abs(%2)"));
+        assert_eq!(Verifier::new().verify_module(module), Ok(()));
+        let mut context = Context::new();
+        assert_eq!(ir, context.parse_module("calls", &ir, "calls.kir").unwrap().display());
+
+        Optimizer::new(module)
+            .with_dead_code_eliminator()
+            .run();
+        let optimized = module.display();
+        assert!(optimized.contains("%5 = call i32 @abs(%3)\n  %6 = iadd %4 %3\n  call void @exit(%6)"));
+
+        let mut lowerer = Lowerer::new(module.name()).expect("no native target");
+        lowerer.lower_module(module).expect("lowering to Cranelift failed");
+        let bytes = lowerer.finish().unwrap();
+        if cfg!(target_os = "linux") {
+            assert_eq!(&bytes[..4], b"\x7fELF");
+        }
+
+        let src = "extern i32 : @abs(i32)\nfunc void : @f(i64 %0) {\nu0():\n  %1 = call i32 @abs(%0)\n  %2, %3 = call i32, i32 @abs(%1)\n  call void @g()\n  ret %1\n}";
+        let mut context = Context::new();
+        let errors: Vec<String> = Verifier::new()
+            .verify_module(context.parse_module("f", src, "f.kir").unwrap())
+            .unwrap_err()
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(errors, vec![
+            "@f u0: `%1 = call i32 @abs(%0)` passes `(i64)` to `@abs` taking `(i32)`",
+            "@f u0: `%2, %3 = call i32, i32 @abs(%1)` expects `i32, i32` from `@abs` returning `i32`",
+            "@f u0: call to undefined function `@g`",
+            "@f u0: `ret %1` returns `i32` from a function returning `void`"
+        ]);
+
+        let module = lower("func max(a: i32, b: i32) -> i32 {\n    if a > b { return a; }\n    b\n}\nfunc nothing() {\n    return;\n}\nx := max(3, 4) + max(1, 2);\nnothing();");
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains("%2 = call i32 @max(%0, %1)"));
+        assert!(ir.contains("%6 = iadd %2 %5\n  call void @nothing()"));
+        assert!(ir.contains("func void : @nothing() {\nu0():\n  ret\n}"));
+        assert_eq!(Verifier::new().verify_module(&module), Ok(()));
+    }
+
+    /// ## Global data
+    /// 
+    /// * `tests/ir/globals.kir` reads and writes a mutable counter and reads a
+    ///   read-only string, it prints back as it was read and lowers to Cranelift
+    /// * `global_addr` is checked against the module's global data
+    /// * String literals in Kese source are interned into read-only globals,
+    ///   equal literals share one
+    #[test]
+    fn global_data() {
+        use crate::backend::ir::{entities::GlobalInit, lower::Lowerer};
+
+        let src = include_str!("../../../tests/ir/globals.kir");
+        let mut context = Context::new();
+        let module = context.parse_module("globals", src, "globals.kir").unwrap();
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.starts_with("global @counter = mutable zeroed 4 align 4\nglobal @hello = readonly \"say \\22hi\\22\\0a\" align 1\n"));
+        assert_eq!(module.global("hello").unwrap().init, GlobalInit::Bytes(b"say \"hi\"\n".to_vec()));
+        assert_eq!(module.global("counter").unwrap().init, GlobalInit::Zeroed(4));
+        assert_eq!(Verifier::new().verify_module(module), Ok(()));
+        let mut context = Context::new();
+        assert_eq!(ir, context.parse_module("globals", &ir, "globals.kir").unwrap().display());
+
+        Optimizer::new(module)
+            .with_global_value_numbering()
+            .with_dead_code_eliminator()
+            .run();
+        let optimized = module.display();
+        assert!(optimized.contains("u0():\n  %0 = global_addr @hello\n  %2 = load u8 %0 align 1"));
+
+        let mut lowerer = Lowerer::new(module.name()).expect("no native target");
+        lowerer.lower_module(module).expect("lowering to Cranelift failed");
+        let bytes = lowerer.finish().unwrap();
+        if cfg!(target_os = "linux") {
+            assert_eq!(&bytes[..4], b"\x7fELF");
+        }
+
+        let src = "global @a = readonly \"\" align 3\nglobal @a = mutable \"\\ff\" align 1\nfunc ptr : @f() {\nu0():\n  %0 = global_addr @b\n  ret %0\n}";
+        let mut context = Context::new();
+        let errors: Vec<String> = Verifier::new()
+            .verify_module(context.parse_module("f", src, "f.kir").unwrap())
+            .unwrap_err()
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(errors, vec![
+            "@a: global data is aligned to 3, not a power of two",
+            "@a: global data is defined more than once",
+            "@f u0: `%0 = global_addr @b` uses undefined global data `@b`"
+        ]);
+        let mut context = Context::new();
+        let error = context.parse_module("f", "global @a = readonly \"\\4g\" align 1", "f.kir").unwrap_err();
+        assert_eq!(error.details, "expected two hex digits after `\\`, found `4g`");
+
+        let module = lower("a := \"kese\";\nb := \"ir\";\nc := \"kese\";");
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.starts_with("global @str0 = readonly \"kese\" align 1\nglobal @str1 = readonly \"ir\" align 1\nfunc"));
+        assert!(ir.contains("%0 = global_addr @str0\n  %1 = const u64 : 4\n  %2 = global_addr @str1\n  %3 = const u64 : 2\n  %4 = global_addr @str0"));
+        assert_eq!(Verifier::new().verify_module(&module), Ok(()));
+    }
+
+    /// ## String runtime
+    /// 
+    /// * The runtime verifies and lowers to Cranelift with either allocator,
+    ///   the arena allocator brings the arena as zeroed global data and its
+    ///   size as a constant, and spins if the exhaustion hook returns, the
+    ///   default hook aborts
+    /// * The arena takes no space in the object file, even at `MAX_ARENA`
+    /// * `tests/strings.kese` passes and returns strings as an address and a
+    ///   length, `++`, `==` and the `len` and `slice` builtins call into the
+    ///   runtime which is linked in
+    /// * A `func` named like a builtin is called instead of the builtin
+    /// * Modules that never call into the runtime are left as they are
+    #[test]
+    fn string_runtime() {
+        use crate::backend::ir::{entities::GlobalInit, lower::Lowerer};
+        use crate::backend::runtime::{self, StringAlloc, MAX_ARENA};
+
+        for alloc in [StringAlloc::Heap, StringAlloc::Arena(64)] {
+            let module = runtime::runtime(alloc);
+            eprintln!("{}", module.display());
+            assert_eq!(Verifier::new().verify_module(&module), Ok(()));
+            let mut lowerer = Lowerer::new(module.name()).expect("no native target");
+            lowerer.lower_module(&module).expect("lowering to Cranelift failed");
+        }
+        let arena = runtime::runtime(StringAlloc::Arena(64));
+        assert_eq!(arena.global("kese_arena").unwrap().init, GlobalInit::Zeroed(64));
+        let text = arena.display();
+        assert!(text.contains("func u64 : @kese_arena_size() {\nu0():\n  %0 = const u64 : 64\n  ret %0\n}"));
+        assert!(text.contains("u1():\n  call void @kese_arena_exhausted()\n  jmp u3()"));
+        assert!(text.contains("u3():\n  jmp u3()\n}"));
+        assert!(text.contains("func void : @kese_arena_exhausted() {\nu0():\n  call void @abort()\n  ret\n}"));
+        // The arena goes into `.bss`, the object stays small whatever its size
+        let mut lowerer = Lowerer::new("runtime").expect("no native target");
+        lowerer.lower_module(&runtime::runtime(StringAlloc::Arena(MAX_ARENA))).expect("lowering to Cranelift failed");
+        assert!(lowerer.finish().expect("emitting the object failed").len() < 1 << 16);
+
+        let module = lower(include_str!("../../../tests/strings.kese"));
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.starts_with("extern ptr : @malloc(u64)\nglobal @str0 = readonly \"kese\" align 1\n"));
+        assert!(ir.contains("%2, %3 = call ptr, u64 @greet(%0, %1)\n  %4 = global_addr @str1\n  %5 = const u64 : 10\n  %6 = call bool @kese_str_eq(%2, %3, %4, %5)"));
+        assert!(ir.contains("func ptr, u64 : @greet(ptr %0, u64 %1) {\nu0():\n  %2 = global_addr @str2\n  %3 = const u64 : 6\n  %4, %5 = call ptr, u64 @kese_str_concat(%2, %3, %0, %1)\n  ret %4, %5\n}"));
+        assert!(ir.contains("func ptr : @kese_alloc(u64 %0) {"));
+        assert!(ir.contains("%7 = const u64 : 6\n  %8 = call u64 @kese_str_len(%2, %3)\n  %9, %10 = call ptr, u64 @kese_str_slice(%2, %3, %7, %8)"));
+        assert_eq!(Verifier::new().verify_module(&module), Ok(()));
+
+        let mut lowerer = Lowerer::new(module.name()).expect("no native target");
+        lowerer.lower_module(&module).expect("lowering to Cranelift failed");
+        let bytes = lowerer.finish().unwrap();
+        if cfg!(target_os = "linux") {
+            assert_eq!(&bytes[..4], b"\x7fELF");
+        }
+
+        let module = lower("x := 1;");
+        assert!(!module.display().contains("kese_str"));
+
+        assert_eq!(check("n := len(1);"), vec!["expected `string`, found `i32`"]);
+        assert!(check("s := \"kese\";\nn: u64 := len(slice(s, 1, 3));").is_empty());
+        let module = lower("n := len(\"kese\");\nfunc len(s: string) -> u64 { 0 }");
+        assert!(module.display().contains("%2 = call u64 @len(%0, %1)"));
+        assert!(!module.display().contains("kese_str"));
+    }
+
+    /// ## String arena linking
+    ///
+    /// * An arena build links with the C compiler alone, the default
+    ///   exhaustion hook needs nothing but `abort`
+    /// * Running out of arena aborts, a program defining its own
+    ///   `kese_arena_exhausted` gets it instead of the default
+    /// * Skipped when there is no `cc` to link with
+    #[test]
+    fn string_arena_links() {
+        use std::process::Command;
+        use crate::backend::ir::lower::Lowerer;
+        use crate::backend::runtime::StringAlloc;
+
+        if Command::new("cc").arg("--version").output().is_err() {
+            eprintln!("no `cc`, skipping");
+            return
+        }
+        let dir = std::env::temp_dir().join(format!("kese_arena_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let compile = |src: &str, size: u32| {
+            let src = src.to_string();
+            let path = "test.kese".to_string();
+            let tokens = crate::frontend::tokenize(&src);
+            let (ast, errors) = crate::frontend::Parser::new(tokens, &src, &path).parse_program();
+            assert!(errors.is_empty(), "{:?}", errors);
+            crate::backend::ASTCompiler::new(ast, src, path)
+                .with_string_alloc(StringAlloc::Arena(size))
+                .compile_module()
+                .expect("lowering failed")
+        };
+        let run = |name: &str, module: &Module| {
+            eprintln!("{}", module.display());
+            let object = dir.join(format!("{}.o", name));
+            let exe = dir.join(name);
+            let mut lowerer = Lowerer::new(module.name()).expect("no native target");
+            lowerer.lower_module(module).expect("lowering to Cranelift failed");
+            lowerer.write(object.to_str().unwrap()).expect("writing the object failed");
+            let linked = Command::new("cc").arg(&object).arg("-o").arg(&exe).output().unwrap();
+            assert!(linked.status.success(), "{}", String::from_utf8_lossy(&linked.stderr));
+            Command::new(&exe).status().unwrap()
+        };
+
+        let src = "s := \"hello\" ++ \" kese\";";
+        assert!(run("fits", &compile(src, 64)).success());
+        assert!(!run("exhausted", &compile(src, 4)).success());
+
+        let module = compile("s := \"hello\" ++ \" kese\";\nfunc kese_arena_exhausted() {\n}", 64);
+        let ir = module.display();
+        assert!(ir.contains("func void : @kese_arena_exhausted() {\nu0():\n  ret\n}"));
+        assert!(!ir.contains("call void @abort()"));
+        assert!(run("hook", &module).success());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    // -- Textual IR Tests --
+    /// ## Round trip
+    /// 
+    /// * Parsing the printed IR and printing it again gives back the same text
+    #[test]
+    fn parse_round_trip() {
+        let mut context = Context::new();
+        let module = context
+            .parse_module("control_flow", include_str!("../../../tests/ir/control_flow.kir"), "control_flow.kir")
+            .unwrap();
+        let printed = module.display();
+
+        let mut context = Context::new();
+        let reparsed = context.parse_module("control_flow", &printed, "control_flow.kir").unwrap();
+        assert_eq!(printed, reparsed.display());
+
+        let lowered = lower("mut x := 1;\ny := if x > 0 { x = 2; 10 } else { 5 };").display();
+        let mut context = Context::new();
+        assert_eq!(lowered, context.parse_module("test", &lowered, "test.kir").unwrap().display());
+    }
+
+    /// ## Parse errors
+    /// 
+    /// * Undefined values are reported at their use
+    #[test]
+    fn parse_undefined_value() {
+        let src = "func i32 : @f() {\nu0():\n  %0 = const i32 : 1\n  %1 = iadd %0 %7\n  ret %1\n}";
+        let mut context = Context::new();
+        let error = context.parse_module("f", src, "f.kir").unwrap_err();
+
+        assert_eq!(error.details, "use of undefined value `%7`");
+        assert_eq!((error.span.line, error.span.column), (3, 15));
+    }
+
+    // -- Verifier Tests --
+    /// ## Valid IR
+    /// 
+    /// * Lowered programs pass the verifier
+    #[test]
+    fn verify_lowered() {
+        let module = lower("mut x := 1;\ny := if x > 0 { x = 2; 10 } else { 5 };\nz := -x * y;");
+        assert_eq!(Verifier::new().verify_module(&module), Ok(()));
+    }
+
+    /// ## Invalid IR
+    /// 
+    /// * Every broken invariant is reported with its function and block
+    #[test]
+    fn verify_invalid() {
+        use crate::backend::ir::entities::ValueID;
+
+        let mut context = Context::new();
+        let module = context.create_module("broken");
+        {
+            let mut builder = module.builder();
+            let sig = FunctionSignature::new()
+                .with_return_ty(types::I32);
+            let mut function_builder = builder.create_function("broken", sig);
+
+            let entry_block = function_builder.create_block();
+            let exit_block = function_builder
+                .create_block()
+                .with_param(types::I32);
+            let open_block = function_builder.create_block();
+
+            let one = entry_block.ins().i32const(1);
+            let two = entry_block.ins().u64const(2);
+            entry_block.ins().iadd(one, two);
+            entry_block.ins().sext(one, types::I16);
+            entry_block.ins().jmp(exit_block.call(&[]));
+
+            exit_block.ins().ret(ValueID(99, types::I32));
+            open_block.ins().i32const(3);
+
+            function_builder.eat_block(entry_block);
+            function_builder.eat_block(exit_block);
+            function_builder.eat_block(open_block);
+            builder.eat_function(function_builder.build());
+            builder.build();
+        }
+
+        let errors: Vec<String> = Verifier::new()
+            .verify_module(module)
+            .unwrap_err()
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(errors, vec![
+            "@broken u0: `%3 = iadd %1 %2` mixes `i32` and `u64`",
+            "@broken u0: `%4 = sext i16 %1` cannot convert `i32` to `i16`",
+            "@broken u0: `u1()` passes 0 argument(s) to a block taking 1",
+            "@broken u1: use of undefined value `%99`",
+            "@broken u2: block does not end in a terminator",
+        ]);
+    }
+
     // -- Optimization Tests --
+    /// ## Control flow analysis
+    /// 
+    /// * Edges, dominators, dominance frontiers and nested loops of
+    ///   `tests/ir/loops.kir`
+    /// * Cached results are recomputed once the edges change
+    #[test]
+    fn control_flow_analysis() {
+        use crate::backend::ir::{entities::BlockID, inst::Inst};
+
+        let mut context = Context::new();
+        let module = context
+            .parse_module("loops", include_str!("../../../tests/ir/loops.kir"), "loops.kir")
+            .unwrap();
+        let u = BlockID;
+        let sorted = |blocks: &[BlockID]| {
+            let mut blocks = blocks.to_vec();
+            blocks.sort_by_key(|b| b.0);
+            blocks
+        };
+
+        let function = &module.functions[0];
+        let cfg = ControlFlowGraph::new(function);
+        assert_eq!(cfg.successors(u(1)), &[u(2), u(6)]);
+        assert_eq!(sorted(cfg.predecessors(u(2))), vec![u(1), u(5), u(7)]);
+        assert_eq!(cfg.reverse_postorder()[..2], [u(0), u(1)]);
+        assert!(!cfg.is_reachable(u(7)));
+
+        let dominators = DominatorTree::new(&cfg);
+        assert_eq!(dominators.idom(u(5)), Some(u(2)));
+        assert_eq!(dominators.idom(u(6)), Some(u(1)));
+        assert_eq!(dominators.idom(u(7)), None);
+        assert!(dominators.dominates(u(1), u(4)));
+        assert!(!dominators.dominates(u(3), u(5)));
+        assert_eq!(sorted(dominators.children(u(2))), vec![u(3), u(4), u(5)]);
+        assert_eq!(dominators.frontier(u(3)), &[u(5)]);
+        assert_eq!(sorted(dominators.frontier(u(5))), vec![u(1), u(2)]);
+        assert_eq!(dominators.frontier(u(1)), &[u(1)]);
+
+        let loops = LoopAnalysis::new(&cfg, &dominators);
+        let headers: Vec<BlockID> = loops.loops().iter().map(|l| l.header).collect();
+        assert_eq!(headers, vec![u(1), u(2)]);
+        assert_eq!(loops.loops()[1].latches, vec![u(5)]);
+        assert_eq!(loops.loops()[1].parent, Some(0));
+        assert_eq!((loops.depth(u(3)), loops.depth(u(1)), loops.depth(u(6))), (2, 1, 0));
+
+        let mut analyses = AnalysisCache::new();
+        assert_eq!(analyses.loops(&module.functions[0]).loops().len(), 2);
+        let exit = module.functions[0].blocks[1].insts.pop().unwrap();
+        if let Inst::Branch { false_path, .. } = exit {
+            module.functions[0].blocks[1].insts.push(Inst::Jmp(false_path));
+        }
+        assert_eq!(analyses.cfg(&module.functions[0]).successors(u(1)), &[u(6)]);
+        assert!(analyses.loops(&module.functions[0]).loops().is_empty());
+    }
+
     /// ## Constant Folding Test
     /// 
     /// * Evaluate `(5 * 6 - 3 * 3) * 2` and fold into:
@@ -105,7 +978,8 @@ mod tests {
         {
             let mut builder = module.builder();
 
-            let sig = FunctionSignature::new();
+            let sig = FunctionSignature::new()
+                .with_return_ty(types::I32);
 
             let mut function_builder = builder.create_function("const_folding", sig);
             
@@ -140,4 +1014,280 @@ mod tests {
 
         eprintln!("Optimized:\n{}", module.display());
     }
+
+    /// ## Constant Folding Fixture
+    /// 
+    /// * Same as `constant_folding`, read from `tests/ir/const_fold.kir`
+    #[test]
+    fn constant_folding_fixture() {
+        let mut context = Context::new();
+        let module = context
+            .parse_module("const_fold", include_str!("../../../tests/ir/const_fold.kir"), "const_fold.kir")
+            .unwrap();
+
+        Optimizer::new(module)
+            .with_constant_folder()
+            .run();
+
+        assert!(module.display().contains("%7 = const i32 : 42\n  ret %7"));
+    }
+
+    /// ## Wrapping Folding
+    /// 
+    /// * Integer arithmetic wraps in two's complement, as in `tests/ir/wrapping.kir`
+    /// * A division by a constant zero or an overflowing `sdiv` is kept and reported
+    #[test]
+    fn wrapping_folding() {
+        let mut context = Context::new();
+        let module = context
+            .parse_module("wrapping", include_str!("../../../tests/ir/wrapping.kir"), "wrapping.kir")
+            .unwrap();
+
+        let mut optimizer = Optimizer::new(module)
+            .with_constant_folder();
+        optimizer.run();
+        let diagnostics: Vec<String> = optimizer.diagnostics().iter().map(|d| d.to_string()).collect();
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains("%2 = const i8 : -128\n  %3 = const i8 : -1\n  %4 = const i8 : -128\n  %5 = const i8 : -128"));
+        assert!(ir.contains("%6 = sdiv %2 %3\n  %7 = const i8 : 0\n  %10 = const u8 : 255"));
+        assert!(ir.contains("%13 = sdiv %11 %12\n  ret %13"));
+        assert_eq!(diagnostics, vec![
+            "@wrapping u0: `%6 = sdiv %2 %3` overflows",
+            "@wrapping u0: `%13 = sdiv %11 %12` divides by zero"
+        ]);
+    }
+
+    /// ## Comparison Folding
+    /// 
+    /// * `icmp` folds for signed and unsigned predicates, `fcmp ne` holds on NaN
+    /// * A `br` on a folded condition becomes a `jmp` to the taken path
+    #[test]
+    fn comparison_folding() {
+        let mut context = Context::new();
+        let module = context
+            .parse_module("compare", include_str!("../../../tests/ir/compare.kir"), "compare.kir")
+            .unwrap();
+
+        Optimizer::new(module)
+            .with_constant_folder()
+            .run();
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains("%2 = const bool : 1\n  %3 = const bool : 0"));
+        assert!(ir.contains("%6 = const bool : 0\n  %7 = const bool : 1\n  jmp u2()"));
+    }
+
+    /// ## Dead Code Elimination
+    /// 
+    /// * Unreachable blocks go, as do values and block parameters that only
+    ///   feed themselves, as in `tests/ir/dead_code.kir`
+    /// * Unused divisions are kept when their divisor may trap
+    /// * After folding a branch, the path not taken is dropped
+    #[test]
+    fn dead_code_elimination() {
+        let mut context = Context::new();
+        let module = context
+            .parse_module("dead_code", include_str!("../../../tests/ir/dead_code.kir"), "dead_code.kir")
+            .unwrap();
+
+        Optimizer::new(module)
+            .with_dead_code_eliminator()
+            .run();
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains(
+            "u0():\n  %2 = const i32 : 7\n  jmp u1(%2)\nu1(i32 %4):\n  br %0 u1(%4) u2()\nu2():\n  ret %4\n}"
+        ));
+        assert!(ir.contains(
+            "u0():\n  %1 = const i32 : 0\n  %2 = sdiv %0 %1\n  %3 = const i32 : -1\n  %4 = sdiv %0 %3\n  %6 = srem %0 %0\n  ret %0"
+        ));
+
+        let mut context = Context::new();
+        let module = context
+            .parse_module("compare", include_str!("../../../tests/ir/compare.kir"), "compare.kir")
+            .unwrap();
+
+        Optimizer::new(module)
+            .with_constant_folder()
+            .with_dead_code_eliminator()
+            .run();
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains("u0():\n  jmp u2()\nu2():\n  %9 = const i32 : 5\n  jmp u3(%9)\nu3(i32 %10):\n  ret %10"));
+    }
+
+    /// ## Global Value Numbering
+    /// 
+    /// * Repeated operations reuse the first dominating definition, as in
+    ///   `tests/ir/gvn.kir`
+    /// * Block call arguments are rewritten as well
+    #[test]
+    fn global_value_numbering() {
+        let mut context = Context::new();
+        let module = context
+            .parse_module("gvn", include_str!("../../../tests/ir/gvn.kir"), "gvn.kir")
+            .unwrap();
+
+        Optimizer::new(module)
+            .with_global_value_numbering()
+            .run();
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains("u0():\n  %3 = iadd %0 %1\n  br %2 u1() u2()"));
+        assert!(ir.contains("u1():\n  %6 = imul %3 %3\n  jmp u3(%6)"));
+        assert!(ir.contains("u3(i32 %8):\n  %9 = isub %0 %1"));
+
+        let mut module = lower("mut x := 3;\ny := x * 2 + 1;\nif y > 0 { x = x * 2; };\nz := x * 2;");
+        let ir = module.display();
+        Optimizer::new(&mut module)
+            .with_global_value_numbering()
+            .run();
+        let optimized = module.display();
+        eprintln!("{}", optimized);
+
+        assert!(ir.contains("u1():\n  %7 = const i32 : 2\n  %8 = imul %0 %7\n  jmp u3(%8)"));
+        assert!(optimized.contains("u1():\n  jmp u3(%2)"));
+        assert!(optimized.contains("%11 = imul %9 %1\n  ret %5"));
+    }
+
+    /// ## Optimization pipelines
+    /// 
+    /// * Passes are deduplicated by their own name
+    /// * `-O2` repeats its pipeline until nothing changes and counts what
+    ///   every pass did
+    #[test]
+    fn optimization_pipelines() {
+        let mut module = lower("x := 3;\ny := x * 2;");
+        let optimizer = Optimizer::new(&mut module)
+            .with_constant_folder()
+            .with_dead_code_eliminator()
+            .with_constant_folder();
+        let names: Vec<String> = optimizer.passes().map(|p| p.name()).collect();
+        assert_eq!(names, vec!["ConstantFolder", "DeadCodeEliminator"]);
+
+        let mut module = lower("mut x := 3;\ny := x * 2 + 1;\nif y > 0 { x = x * 2; };\nz := x * 2 / 0;");
+        let mut optimizer = Optimizer::new(&mut module).with_level(OptLevel::O2);
+        optimizer.run();
+        let statistics: Vec<(String, usize, usize, isize)> = optimizer
+            .statistics()
+            .iter()
+            .map(|s| (s.name.clone(), s.runs, s.changes, s.instructions_removed))
+            .collect();
+        let diagnostics: Vec<String> = optimizer.diagnostics().iter().map(|d| d.to_string()).collect();
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert_eq!(statistics, vec![
+            ("MemToReg".to_string(), 2, 0, 0),
+            ("ConstantFolder".to_string(), 2, 1, 4),
+            ("GlobalValueNumbering".to_string(), 2, 1, 2),
+            ("DeadCodeEliminator".to_string(), 2, 1, 4)
+        ]);
+        assert_eq!(diagnostics, vec!["@main u3: `%13 = sdiv %11 %12` divides by zero"]);
+        assert!(ir.contains("%12 = const i32 : 0\n  %13 = sdiv %11 %12\n  ret %12"));
+    }
+
+    /// ## Stack slot promotion
+    /// 
+    /// * Slots only loaded from and stored to become block parameters placed
+    ///   on the dominance frontier of their stores, as in `tests/ir/mem2reg.kir`
+    /// * Reads before any store see zero, slots whose address escapes stay
+    ///   in memory and are renumbered
+    /// * `mut` variables kept in stack slots by the AST compiler come out of
+    ///   `-O1` as they would have without them
+    #[test]
+    fn stack_slot_promotion() {
+        let mut context = Context::new();
+        let module = context
+            .parse_module("mem2reg", include_str!("../../../tests/ir/mem2reg.kir"), "mem2reg.kir")
+            .unwrap();
+
+        Optimizer::new(module)
+            .with_mem_to_reg()
+            .run();
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains("func i32 : @count(i32 %0) {\n  ss0 = stack_slot 4 align 4\n  ss1 = stack_slot 8 align 8\nu0():"));
+        assert!(ir.contains("u0():\n  %13 = const i32 : 0\n  %14 = const i32 : 0\n  %3 = stack_addr ss0\n  %4 = stack_addr ss1"));
+        assert!(ir.contains("store %3 %4 align 8\n  jmp u1(%5)\nu1(i32 %15):\n  %7 = icmp slt %15 %0"));
+        assert!(ir.contains("%9 = iadd %15 %8\n  store %9 %3 align 4\n  jmp u1(%9)"));
+        assert!(ir.contains("u3():\n  %12 = iadd %14 %15\n  ret %12"));
+
+        let src = "func f(n: i32) -> i32 {\n    mut x := 1;\n    mut i := 0;\n    while i < n { x = x * 2; i = i + 1; }\n    x\n}";
+        let mut registers = lower(src);
+        Optimizer::new(&mut registers).with_level(OptLevel::O1).run();
+
+        let path = "test.kese".to_string();
+        let src = src.to_string();
+        let tokens = crate::frontend::tokenize(&src);
+        let (ast, _) = crate::frontend::Parser::new(tokens, &src, &path).parse_program();
+        let mut module = crate::backend::ASTCompiler::new(ast, src, path)
+            .with_stack_locals()
+            .compile_module()
+            .expect("lowering failed");
+        Optimizer::new(&mut module).with_level(OptLevel::O1).run();
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(!ir.contains("stack_slot") && !ir.contains("load") && !ir.contains("store"));
+        assert!(ir.contains("u1(i32 %16, i32 %17):\n  %6 = icmp slt %17 %0\n  br %6 u2() u3()"));
+        assert_eq!(
+            ir.lines().filter(|l| l.contains(" = ")).count(),
+            registers.display().lines().filter(|l| l.contains(" = ")).count()
+        );
+    }
+
+    /// ## Stack slot promotion with unreachable predecessors
+    /// 
+    /// * Jumps from blocks the dominator walk never reaches still pass one
+    ///   argument per new block parameter, as in `tests/ir/mem2reg_unreachable.kir`
+    #[test]
+    fn stack_slot_promotion_unreachable() {
+        let mut context = Context::new();
+        let module = context
+            .parse_module("mem2reg_unreachable", include_str!("../../../tests/ir/mem2reg_unreachable.kir"), "mem2reg_unreachable.kir")
+            .unwrap();
+
+        Optimizer::new(module)
+            .with_mem_to_reg()
+            .run();
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains("u1(i32 %"));
+        assert!(ir.contains("u4():\n  jmp u1(%"));
+        assert_eq!(Verifier::new().verify_module(module), Ok(()));
+    }
+
+    /// ## Shift Folding
+    /// 
+    /// * Logical and arithmetic right shifts differ on negative values
+    /// * Amounts at or above the bit width wrap around, as in `tests/ir/shifts.kir`
+    #[test]
+    fn shift_folding() {
+        let mut context = Context::new();
+        let module = context
+            .parse_module("shifts", include_str!("../../../tests/ir/shifts.kir"), "shifts.kir")
+            .unwrap();
+
+        Optimizer::new(module)
+            .with_constant_folder()
+            .run();
+        let ir = module.display();
+        eprintln!("{}", ir);
+
+        assert!(ir.contains("%2 = const i8 : 1\n  %3 = const i8 : -1\n  %6 = const u8 : 2\n  %9 = const i32 : 2\n  ret %9"));
+
+        let module = lower("x: u32 := 40;\nn: u32 := 3;\ny := x << n >> 1;\nz := -8 >> 1;");
+        let ir = module.display();
+        assert!(ir.contains("%2 = ishl %0 %1\n  %3 = const u32 : 1\n  %4 = ushr %2 %3"));
+        assert!(ir.contains("%7 = sshr %5 %6"));
+    }
 }
\ No newline at end of file
@@ -1,6 +1,10 @@
 //! # Backend Module
 //! 
-//! * Contains the AST compiler, and the IR codegen
+//! * Contains the AST compiler, the IR codegen and the string runtime
 
+pub mod astcompiler;
 pub mod ir;
-pub mod ir_tests;
\ No newline at end of file
+pub mod ir_tests;
+pub mod runtime;
+
+pub use astcompiler::ASTCompiler;
\ No newline at end of file
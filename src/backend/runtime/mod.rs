@@ -0,0 +1,113 @@
+//! # String Runtime
+//!
+//! * Support functions for strings, written in Kese IR and linked into the
+//!   modules that call them
+//! * A string is passed as its `ptr` address followed by its `u64` length in bytes
+//! * The `len` and `slice` builtins call `@kese_str_len` and `@kese_str_slice`,
+//!   which clamps its bounds to the string
+//! * `@kese_str_concat` gets memory from `@kese_alloc`, which takes it from the
+//!   heap or from a static arena as picked by `StringAlloc`
+
+use std::collections::HashMap;
+
+use super::ir::{codegen::context::{Context, Module}, entities::{FunctionSignature, GlobalData, GlobalInit}, inst::Inst};
+
+pub const STR_LEN: &str = "kese_str_len";
+pub const STR_CONCAT: &str = "kese_str_concat";
+pub const STR_EQ: &str = "kese_str_eq";
+pub const STR_SLICE: &str = "kese_str_slice";
+
+const STRINGS: &str = include_str!("strings.kir");
+const HEAP: &str = include_str!("heap.kir");
+const ARENA: &str = include_str!("arena.kir");
+
+/// Largest arena `--string-arena` accepts, 1 GiB
+pub const MAX_ARENA: u32 = 1 << 30;
+
+/// Where the strings built at runtime get their memory
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum StringAlloc {
+    /// `malloc` from the C library
+    #[default]
+    Heap,
+    /// A static arena of this many bytes, for targets without a heap, at
+    /// most `MAX_ARENA` bytes
+    Arena(u32)
+}
+
+/// The whole runtime, allocating as `alloc` says
+pub fn runtime(alloc: StringAlloc) -> Module {
+    // The arena is sized when compiling, its size is an immediate so the
+    // lowerer encodes it for the target
+    let allocator = match alloc {
+        StringAlloc::Heap => HEAP.to_string(),
+        StringAlloc::Arena(size) => format!(
+            "{}func u64 : @kese_arena_size() {{\nu0():\n  %0 = const u64 : {}\n  ret %0\n}}\n",
+            ARENA, size
+        )
+    };
+    let mut context = Context::new();
+    let mut module = context
+        .parse_module("runtime", &format!("{}{}", STRINGS, allocator), "runtime.kir")
+        .expect("the string runtime is valid IR")
+        .clone();
+
+    if let StringAlloc::Arena(size) = alloc {
+        module.globals.push(GlobalData {
+            alias: "kese_arena".to_string(),
+            init: GlobalInit::Zeroed(size),
+            mutable: true,
+            align: 8
+        });
+    }
+    module
+}
+
+/// The runtime function a builtin of the language calls
+pub fn builtin(name: &str) -> Option<&'static str> {
+    match name {
+        "len" => Some(STR_LEN),
+        "slice" => Some(STR_SLICE),
+        _ => None
+    }
+}
+
+/// Signatures of the runtime functions compiled code calls
+pub fn signatures() -> HashMap<String, FunctionSignature> {
+    runtime(StringAlloc::Heap)
+        .functions
+        .into_iter()
+        .filter(|f| [STR_LEN, STR_CONCAT, STR_EQ, STR_SLICE].contains(&f.alias.as_str()))
+        .map(|f| (f.alias, f.sig))
+        .collect()
+}
+
+/// Adds the runtime to `module` when it calls into it, anything `module`
+/// already defines under the same alias is kept
+pub fn link(module: &mut Module, alloc: StringAlloc) {
+    let runtime = runtime(alloc);
+    let called = module.functions
+        .iter()
+        .flat_map(|f| &f.blocks)
+        .flat_map(|b| &b.insts)
+        .any(|inst| matches!(inst, Inst::Call { callee, .. } if runtime.functions.iter().any(|f| f.alias == *callee)));
+    if !called {
+        return
+    }
+
+    for external in runtime.externs {
+        if module.signature(&external.alias).is_none() {
+            module.externs.push(external);
+        }
+    }
+    for global in runtime.globals {
+        if module.global(&global.alias).is_none() {
+            module.globals.push(global);
+        }
+    }
+    for function in runtime.functions {
+        if module.signature(&function.alias).is_none() {
+            module.functions.push(function);
+        }
+    }
+}
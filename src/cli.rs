@@ -17,5 +17,22 @@ pub struct Cli {
     pub debug: bool,
 
     #[arg(short, long)]
-    pub parse_only: bool
+    pub parse_only: bool,
+
+    /// Optimization level
+    #[arg(short = 'O', default_value = "0", value_parser = ["0", "1", "2"])]
+    pub opt_level: String,
+
+    /// Print what every optimization pass did
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Keep `mut` variables in stack slots
+    #[arg(long)]
+    pub stack_locals: bool,
+
+    /// Allocate strings from a static arena of this many bytes instead of the heap, at most 1 GiB.
+    /// Running out calls `kese_arena_exhausted`, which aborts unless the program defines its own
+    #[arg(long, value_name = "BYTES", value_parser = clap::value_parser!(u32).range(1..=crate::backend::runtime::MAX_ARENA as i64))]
+    pub string_arena: Option<u32>
 }
\ No newline at end of file
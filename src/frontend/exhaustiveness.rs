@@ -0,0 +1,187 @@
+//! # Match exhaustiveness
+//!
+//! * Usefulness over a pattern matrix, as described in Maranget's
+//!   "Warnings for pattern matching"
+//! * Enum variants and `true`/`false` are complete constructor sets,
+//!   integers never are, so integer matches need a catch-all arm
+
+use super::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ctor {
+    Variant(usize),
+    Bool(bool),
+    Int(i128)
+}
+
+/// A checked pattern, bindings are wildcards and variants are resolved
+/// to their index in the enum layout
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pat {
+    Wild,
+    Ctor(Ctor, Vec<Pat>)
+}
+
+impl Pat {
+    /// Lowers a pattern that already passed type checking
+    pub fn from_pattern(pattern: &Pattern, registry: &TypeRegistry) -> Self {
+        match pattern {
+            Pattern::Wildcard(_) | Pattern::Binding(..) => Pat::Wild,
+            Pattern::Int(n, _) => Pat::Ctor(Ctor::Int(*n), Vec::new()),
+            Pattern::Bool(b, _) => Pat::Ctor(Ctor::Bool(*b), Vec::new()),
+            Pattern::Variant { enum_name, variant, fields, .. } => {
+                let (index, _) = registry
+                    .enum_layout(&enum_name.0)
+                    .and_then(|layout| layout.variant(&variant.0))
+                    .expect("pattern variants are resolved by the type checker");
+                Pat::Ctor(
+                    Ctor::Variant(index),
+                    fields.iter().map(|f| Self::from_pattern(f, registry)).collect()
+                )
+            }
+        }
+    }
+
+    /// Renders a witness as source syntax
+    pub fn display(&self, t: &Type, registry: &TypeRegistry) -> String {
+        match self {
+            Pat::Wild => "_".to_string(),
+            Pat::Ctor(Ctor::Int(n), _) => n.to_string(),
+            Pat::Ctor(Ctor::Bool(b), _) => b.to_string(),
+            Pat::Ctor(Ctor::Variant(index), args) => {
+                let Type::Alias(name) = t else { return "_".to_string() };
+                let Some(variant) = registry.enum_layout(name).map(|l| &l.variants[*index]) else {
+                    return "_".to_string()
+                };
+                if variant.fields.is_empty() {
+                    format!("{}::{}", name, variant.name)
+                } else {
+                    let args: Vec<String> = args
+                        .iter()
+                        .zip(&variant.fields)
+                        .map(|(arg, field)| arg.display(&field.type_, registry))
+                        .collect();
+                    format!("{}::{}({})", name, variant.name, args.join(", "))
+                }
+            }
+        }
+    }
+}
+
+pub struct Exhaustiveness<'a> {
+    registry: &'a TypeRegistry
+}
+
+impl<'a> Exhaustiveness<'a> {
+    pub fn new(registry: &'a TypeRegistry) -> Self {
+        Self { registry }
+    }
+
+    /// Every constructor of `t` with its field types, `None` if the set is
+    /// infinite or `t` cannot be matched on
+    fn constructors(&self, t: &Type) -> Option<Vec<(Ctor, Vec<Type>)>> {
+        match t {
+            Type::Boolean => Some(vec![
+                (Ctor::Bool(false), Vec::new()),
+                (Ctor::Bool(true), Vec::new())
+            ]),
+            Type::Alias(name) => self.registry.enum_layout(name).map(|layout| {
+                layout.variants
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| (Ctor::Variant(i), v.fields.iter().map(|f| f.type_.clone()).collect()))
+                    .collect()
+            }),
+            _ => None
+        }
+    }
+
+    fn specialize(rows: &[Vec<Pat>], ctor: &Ctor, arity: usize) -> Vec<Vec<Pat>> {
+        rows.iter()
+            .filter_map(|row| match &row[0] {
+                Pat::Ctor(c, args) if c == ctor => Some(args.iter().chain(&row[1..]).cloned().collect()),
+                Pat::Ctor(..) => None,
+                Pat::Wild => Some(std::iter::repeat_n(Pat::Wild, arity).chain(row[1..].iter().cloned()).collect())
+            })
+            .collect()
+    }
+
+    fn default(rows: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+        rows.iter()
+            .filter(|row| row[0] == Pat::Wild)
+            .map(|row| row[1..].to_vec())
+            .collect()
+    }
+
+    /// Whether the constructors heading `rows` cover all of `t`
+    fn complete(&self, rows: &[Vec<Pat>], t: &Type) -> Option<Vec<(Ctor, Vec<Type>)>> {
+        let ctors = self.constructors(t)?;
+        let all_used = ctors.iter().all(|(ctor, _)| {
+            rows.iter().any(|row| matches!(&row[0], Pat::Ctor(c, _) if c == ctor))
+        });
+        if all_used { Some(ctors) } else { None }
+    }
+
+    /// Whether `row` matches a value that no row of `rows` matches
+    pub fn is_useful(&self, rows: &[Vec<Pat>], row: &[Pat], types: &[Type]) -> bool {
+        let Some(head) = row.first() else { return rows.is_empty() };
+
+        match head {
+            Pat::Ctor(ctor, args) => {
+                let arg_types = self.field_types(&types[0], ctor);
+                let row: Vec<Pat> = args.iter().chain(&row[1..]).cloned().collect();
+                let types: Vec<Type> = arg_types.into_iter().chain(types[1..].iter().cloned()).collect();
+                self.is_useful(&Self::specialize(rows, ctor, args.len()), &row, &types)
+            },
+            Pat::Wild => match self.complete(rows, &types[0]) {
+                Some(ctors) => ctors.into_iter().any(|(ctor, arg_types)| {
+                    let row: Vec<Pat> = std::iter::repeat_n(Pat::Wild, arg_types.len())
+                        .chain(row[1..].iter().cloned())
+                        .collect();
+                    let arity = arg_types.len();
+                    let types: Vec<Type> = arg_types.into_iter().chain(types[1..].iter().cloned()).collect();
+                    self.is_useful(&Self::specialize(rows, &ctor, arity), &row, &types)
+                }),
+                None => self.is_useful(&Self::default(rows), &row[1..], &types[1..])
+            }
+        }
+    }
+
+    /// A tuple of patterns, one per column, that no row of `rows` matches
+    pub fn missing(&self, rows: &[Vec<Pat>], types: &[Type]) -> Option<Vec<Pat>> {
+        let Some(t) = types.first() else {
+            return if rows.is_empty() { Some(Vec::new()) } else { None }
+        };
+
+        if let Some(ctors) = self.complete(rows, t) {
+            for (ctor, arg_types) in ctors {
+                let arity = arg_types.len();
+                let types: Vec<Type> = arg_types.into_iter().chain(types[1..].iter().cloned()).collect();
+                if let Some(mut witness) = self.missing(&Self::specialize(rows, &ctor, arity), &types) {
+                    let rest = witness.split_off(arity);
+                    return Some(std::iter::once(Pat::Ctor(ctor, witness)).chain(rest).collect())
+                }
+            }
+            return None
+        }
+
+        let mut witness = self.missing(&Self::default(rows), &types[1..])?;
+        let head = self.constructors(t)
+            .and_then(|ctors| ctors.into_iter().find(|(ctor, _)| {
+                !rows.iter().any(|row| matches!(&row[0], Pat::Ctor(c, _) if c == ctor))
+            }))
+            .map_or(Pat::Wild, |(ctor, arg_types)| Pat::Ctor(ctor, vec![Pat::Wild; arg_types.len()]));
+        witness.insert(0, head);
+        Some(witness)
+    }
+
+    fn field_types(&self, t: &Type, ctor: &Ctor) -> Vec<Type> {
+        match (t, ctor) {
+            (Type::Alias(name), Ctor::Variant(index)) => self.registry
+                .enum_layout(name)
+                .map(|l| l.variants[*index].fields.iter().map(|f| f.type_.clone()).collect())
+                .unwrap_or_default(),
+            _ => Vec::new()
+        }
+    }
+}
@@ -20,7 +20,12 @@ pub enum TokenType {
 
     Semicolon,
     ColonEquals,
-    Colon
+    Colon,
+    Comma,
+    Arrow,
+    Dot,
+    ColonColon,
+    FatArrow
 }
 
 impl TokenType {
@@ -36,6 +41,11 @@ impl TokenType {
                 TokenType::Colon => "`:`".to_string(),
                 TokenType::ColonEquals => "`:=`".to_string(),
                 TokenType::Equals => "`=`".to_string(),
+                TokenType::Comma => "`,`".to_string(),
+                TokenType::Arrow => "`->`".to_string(),
+                TokenType::Dot => "`.`".to_string(),
+                TokenType::ColonColon => "`::`".to_string(),
+                TokenType::FatArrow => "`=>`".to_string(),
                 _ => unreachable!()
             },
             _ => str
@@ -73,12 +83,17 @@ impl fmt::Display for Token {
     }
 }
 
+/// Digits alone make an integer however wide, its range is checked against its type
+fn is_integer(lexeme: &str) -> bool {
+    !lexeme.is_empty() && lexeme.bytes().all(|b| b.is_ascii_digit())
+}
+
 fn sel_token(lexeme: &String, span: &Span) -> Token {
     Token {
         token_type: match &**lexeme {
             _ if KEYWORDS.contains(&(&**lexeme)) => TokenType::Keyword,
             "+" | "-" | "*" | "/" | "==" | ">" | "<" | ">=" | "<=" | "!=" | "++"
-            | "!" => TokenType::Operator,
+            | "!" | "%" | "&" | "|" | "^" | "<<" | ">>" | "&&" | "||" => TokenType::Operator,
             "=" => TokenType::Equals,
             "(" => TokenType::LParen,
             ")" => TokenType::RParen,
@@ -87,7 +102,12 @@ fn sel_token(lexeme: &String, span: &Span) -> Token {
             ";" => TokenType::Semicolon,
             ":" => TokenType::Colon,
             ":=" => TokenType::ColonEquals,
-            _ => if lexeme.parse::<i64>().is_ok() {
+            "," => TokenType::Comma,
+            "->" => TokenType::Arrow,
+            "." => TokenType::Dot,
+            "::" => TokenType::ColonColon,
+            "=>" => TokenType::FatArrow,
+            _ => if is_integer(lexeme) {
                 TokenType::Int
             } else if lexeme.parse::<f64>().is_ok() {
                 TokenType::Float
@@ -266,9 +286,29 @@ pub fn tokenize(source: &String) -> Vec<Token> {
                         i += 1;
                     },
                     '.' => {
-                        if !current.is_empty() && (current.parse::<i64>().is_ok() || chars[i + 1].is_ascii_digit()) {
-                            current.push(ch)
+                        if is_integer(&current) {
+                            current.push(ch);
+                            column += 1;
+                            i += 1;
+                            continue;
+                        }
+                        if !current.is_empty() {
+                            let span = Span { 
+                                line, 
+                                column: column - current.len(), 
+                                start_pos, 
+                                end_pos: i - 1
+                            };
+                            tokens.push(sel_token(&current, &span));
+                            current.clear();
                         }
+                        let span = Span { 
+                            line, 
+                            column, 
+                            start_pos: i, 
+                            end_pos: i 
+                        };
+                        tokens.push(sel_token(&ch.to_string(), &span));
                         column += 1;
                         i += 1;
                     }
@@ -281,7 +321,7 @@ pub fn tokenize(source: &String) -> Vec<Token> {
                         i += 1;
                     },
                     'a'..='z' | 'A'..='Z' | '_' => {
-                        if !current.is_empty() && (current.parse::<i64>().is_ok() || current.parse::<f64>().is_ok()) {
+                        if is_integer(&current) || (!current.is_empty() && current.parse::<f64>().is_ok()) {
                             let span = Span { 
                                 line, 
                                 column: column - current.len(), 
@@ -1,7 +1,8 @@
 //! # Frontend module
 //! 
-//! * Contains the lexer, parser, and type checker
+//! * Contains the lexer, parser, type checker and match exhaustiveness checks
 
+pub mod exhaustiveness;
 pub mod lexer;
 pub mod parser;
 pub mod typechecker;
@@ -9,6 +10,7 @@ pub mod typeregistry;
 
 #[allow(unused_imports)]
 pub use {
+    exhaustiveness::*,
     lexer::*, 
     parser::*, 
     typechecker::*,
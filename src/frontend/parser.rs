@@ -3,7 +3,7 @@ use super::*;
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub enum ASTNode {
-    IntLit(i64),
+    IntLit(i128),
     FloatLit(f64),
     StringLit(String),
     Bool(bool),
@@ -39,9 +39,104 @@ pub enum ASTNode {
     Mutation {
         name: (String, Span),
         value: Box<Node>
+    },
+    Function {
+        name: (String, Span),
+        params: Vec<Param>,
+        return_type: Option<(String, Span)>,
+        body: Box<Node>
+    },
+    Call {
+        callee: (String, Span),
+        args: Vec<Node>
+    },
+    Return(Option<Box<Node>>),
+    StructDecl {
+        name: (String, Span),
+        fields: Vec<((String, Span), (String, Span))>
+    },
+    StructLit {
+        name: (String, Span),
+        fields: Vec<((String, Span), Node)>
+    },
+    FieldAccess {
+        object: Box<Node>,
+        field: (String, Span)
+    },
+    FieldMutation {
+        name: (String, Span),
+        fields: Vec<(String, Span)>,
+        value: Box<Node>
+    },
+    EnumDecl {
+        name: (String, Span),
+        variants: EnumVariants
+    },
+    EnumVariant {
+        enum_name: (String, Span),
+        variant: (String, Span),
+        args: Vec<Node>
+    },
+    Match {
+        scrutinee: Box<Node>,
+        arms: Vec<MatchArm>
+    },
+    While {
+        condition: Box<Node>,
+        body: Box<Node>
+    },
+    Loop(Box<Node>),
+    Break(Option<Box<Node>>),
+    Continue,
+    Cast {
+        value: Box<Node>,
+        type_: (String, Span)
+    }
+}
+
+/// Variants of an enum declaration with their payload types
+pub type EnumVariants = Vec<((String, Span), Vec<(String, Span)>)>;
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Wildcard(Span),
+    Binding(String, Span),
+    Int(i128, Span),
+    Bool(bool, Span),
+    Variant {
+        enum_name: (String, Span),
+        variant: (String, Span),
+        fields: Vec<Pattern>,
+        span: Span
+    }
+}
+
+impl Pattern {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Wildcard(span) | Self::Binding(_, span)
+            | Self::Int(_, span) | Self::Bool(_, span)
+            | Self::Variant { span, .. } => *span
+        }
     }
 }
 
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Node
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: (String, Span),
+    pub type_: (String, Span),
+    pub mutability: bool
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct Node {
@@ -49,6 +144,21 @@ pub struct Node {
     pub span: Span
 }
 
+impl Node {
+    /// Whether this is a numeric literal, possibly negated or combined with
+    /// other literals, whose type is taken from the surrounding expression
+    pub fn is_literal(&self) -> bool {
+        match &self.ast_repr {
+            ASTNode::IntLit(_) | ASTNode::FloatLit(_) => true,
+            ASTNode::UnaOp { operand, op } => op.0 == "-" && operand.is_literal(),
+            ASTNode::BinOp { lhs, rhs, op } => {
+                is_arithmetic(&op.0) && lhs.is_literal() && rhs.is_literal()
+            },
+            _ => false
+        }
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct Module(pub Vec<Node>);
@@ -59,7 +169,9 @@ pub struct Parser {
     pub(crate) pos: usize,
     pub(crate) tokens: Vec<Token>,
     pub(crate) src: String,
-    pub(crate) path: String
+    pub(crate) path: String,
+    /// Cleared while parsing an `if` condition, where `name {` opens the body
+    pub(crate) struct_literals: bool
 }
 
 #[allow(dead_code)]
@@ -69,10 +181,18 @@ impl Parser {
             pos: 0,
             tokens,
             src: src.clone(),
-            path: path.clone()
+            path: path.clone(),
+            struct_literals: true
         }
     }
 
+    fn with_struct_literals<T>(&mut self, allowed: bool, f: impl FnOnce(&mut Self) -> Result<T, Error>) -> Result<T, Error> {
+        let outer = std::mem::replace(&mut self.struct_literals, allowed);
+        let result = f(self);
+        self.struct_literals = outer;
+        result
+    }
+
     
     fn get(&self, offset: i32) -> Option<&Token> {
         let i = self.pos as i32 + offset;
@@ -124,22 +244,45 @@ impl Parser {
         let mut l = self.nud()?;
 
         while let Some(current_token) = self.get(0).cloned() {
-            let ((lbp, rbp), op, s) = {
-                if let TokenType::ColonEquals = current_token.token_type {
-                    (prec(&current_token.lexeme).unwrap(), current_token.lexeme.clone(), current_token.span)
-                } else {
-                    if let TokenType::Operator = current_token.token_type {
-                        (prec(&current_token.lexeme).unwrap(), current_token.lexeme.clone(), current_token.span)
-                    } else {
-                        break
-                    }
-                }
+            let infix = match current_token.token_type {
+                TokenType::ColonEquals | TokenType::Operator => true,
+                TokenType::Keyword => current_token.lexeme == "as",
+                _ => false
+            };
+            if !infix {
+                break
+            }
+            let Some((lbp, rbp)) = prec(&current_token.lexeme) else {
+                return Err(Error {
+                    code: ECode::UnexpectedToken,
+                    details: format!("`{}` is not a binary operator", current_token.lexeme),
+                    span: current_token.span,
+                    src: self.src.clone(),
+                    path: self.path.clone(),
+                    note: None,
+                    help: None
+                })
             };
+            let (op, s) = (current_token.lexeme.clone(), current_token.span);
             if lbp < min_bp {
                 break
             }
 
             self.pos += 1;
+            if op == "as" {
+                let t = self.expect_and_take(&TokenType::Identifier)?;
+                let built_span = Span {
+                    start_pos: span.start_pos,
+                    end_pos: t.span.end_pos,
+                    line: span.line,
+                    column: span.column
+                };
+                l = Node {
+                    ast_repr: ASTNode::Cast { value: Box::new(l), type_: (t.lexeme, t.span) },
+                    span: built_span
+                };
+                continue
+            }
             let r = self.parse_expression(rbp)?;
             let built_span = Span {
                 start_pos: span.start_pos,
@@ -162,6 +305,28 @@ impl Parser {
 
     
     pub fn nud(&mut self) -> Result<Node, Error> {
+        let mut node = self.primary()?;
+        while let Some(Token { token_type: TokenType::Dot, .. }) = self.get(0) {
+            self.pos += 1;
+            let t = self.expect_and_take(&TokenType::Identifier)?;
+            let built_span = Span {
+                line: node.span.line,
+                column: node.span.column,
+                start_pos: node.span.start_pos,
+                end_pos: t.span.end_pos
+            };
+            node = Node {
+                ast_repr: ASTNode::FieldAccess {
+                    object: Box::new(node),
+                    field: (t.lexeme, t.span)
+                },
+                span: built_span
+            };
+        }
+        Ok(node)
+    }
+
+    fn primary(&mut self) -> Result<Node, Error> {
         if let Ok(v) = self.try_mutation() {
             self.pos = v.1.pos;
             return Ok(v.0)
@@ -174,8 +339,8 @@ impl Parser {
             let value = current_token.lexeme.clone();
             match current_token.token_type {
                 TokenType::Int => {
+                    let i = ASTNode::IntLit(self.int_value(&current_token)?);
                     self.pos += 1;
-                    let i = ASTNode::IntLit(value.parse::<i64>().unwrap());
                     Ok( Node{ ast_repr: i, span: current_token.span } )
                 },
                 TokenType::Float => {
@@ -195,12 +360,21 @@ impl Parser {
                 },
                 TokenType::Identifier => {
                     self.pos += 1;
+                    if let Some(Token { token_type: TokenType::LParen, .. }) = self.get(0) {
+                        return self.parse_call((value, current_token.span))
+                    }
+                    if let Some(Token { token_type: TokenType::ColonColon, .. }) = self.get(0) {
+                        return self.parse_enum_variant((value, current_token.span))
+                    }
+                    if self.struct_literals && self.at_struct_literal() {
+                        return self.parse_struct_literal((value, current_token.span))
+                    }
                     let i = ASTNode::Identifier(value.clone());
                     Ok( Node{ ast_repr: i, span: current_token.span } )
                 },
                 TokenType::LParen => {
                     self.pos += 1;
-                    let expr = self.parse_expression(0)?;
+                    let expr = self.with_struct_literals(true, |p| p.parse_expression(0))?;
                     self.expect(&TokenType::RParen)?;
                     Ok(expr)
                 },
@@ -225,6 +399,42 @@ impl Parser {
                             let i = self.parse_if()?;
                             Ok(i)
                         },
+                        "func" => {
+                            let f = self.parse_function()?;
+                            Ok(f)
+                        },
+                        "return" => {
+                            let r = self.parse_return()?;
+                            Ok(r)
+                        },
+                        "struct" => {
+                            let s = self.parse_struct()?;
+                            Ok(s)
+                        },
+                        "enum" => {
+                            let e = self.parse_enum()?;
+                            Ok(e)
+                        },
+                        "match" => {
+                            let m = self.parse_match()?;
+                            Ok(m)
+                        },
+                        "while" => {
+                            let w = self.parse_while()?;
+                            Ok(w)
+                        },
+                        "loop" => {
+                            let l = self.parse_loop()?;
+                            Ok(l)
+                        },
+                        "break" => {
+                            let b = self.parse_break()?;
+                            Ok(b)
+                        },
+                        "continue" => {
+                            self.pos += 1;
+                            Ok(Node { ast_repr: ASTNode::Continue, span: current_token.span })
+                        },
                         _ => return Err(Error {
                             code: ECode::UnexpectedToken,
                             details: format!("invalid keyword `{}`", value),
@@ -282,13 +492,13 @@ impl Parser {
             })
         };
 
-        let condition = self.parse_expression(0)?;
+        let condition = self.with_struct_literals(false, |p| p.parse_expression(0))?;
         let then_body = self.parse_expression(0)?;
         let else_body = self.parse_else()?;
 
         let if_span = Span {
             start_pos: p_if_span.start_pos,
-            line: p_if_span.start_pos,
+            line: p_if_span.line,
             column: p_if_span.column,
             end_pos: condition.span.end_pos
         };
@@ -353,6 +563,10 @@ impl Parser {
 
     
     fn parse_block(&mut self) -> Result<Node, Error> {
+        self.with_struct_literals(true, Self::parse_block_inner)
+    }
+
+    fn parse_block_inner(&mut self) -> Result<Node, Error> {
         self.pos += 1;
         let mut block: Vec<Node> = Vec::new();
         let mut span = self.eof();
@@ -381,15 +595,465 @@ impl Parser {
         Ok(Node { ast_repr: ASTNode::Block(block), span })
     }
 
+    fn parse_function(&mut self) -> Result<Node, Error> {
+        let func_span = self.get(0).unwrap().span;
+        self.pos += 1;
+
+        let t = self.expect_and_take(&TokenType::Identifier)?;
+        let name = (t.lexeme, t.span);
+
+        self.expect(&TokenType::LParen)?;
+        let mut params: Vec<Param> = Vec::new();
+        while let Some(current_token) = self.get(0).cloned() {
+            if current_token.token_type == TokenType::RParen {
+                break
+            }
+            let mutability = if current_token.token_type == TokenType::Keyword && current_token.lexeme == "mut" {
+                self.pos += 1;
+                true
+            } else {
+                false
+            };
+            let t = self.expect_and_take(&TokenType::Identifier)?;
+            let param_name = (t.lexeme, t.span);
+            self.expect(&TokenType::Colon)?;
+            let t = self.expect_and_take(&TokenType::Identifier)?;
+            params.push(Param { name: param_name, type_: (t.lexeme, t.span), mutability });
+
+            if let Some(Token { token_type: TokenType::Comma, .. }) = self.get(0) {
+                self.pos += 1;
+            } else {
+                break
+            }
+        }
+        self.expect(&TokenType::RParen)?;
+
+        let return_type = if let Some(Token { token_type: TokenType::Arrow, .. }) = self.get(0) {
+            self.pos += 1;
+            let t = self.expect_and_take(&TokenType::Identifier)?;
+            Some((t.lexeme, t.span))
+        } else {
+            None
+        };
+
+        if !matches!(self.get(0), Some(Token { token_type: TokenType::LBrace, .. })) {
+            self.expect(&TokenType::LBrace)?;
+        }
+        let body = self.parse_block()?;
+
+        let built_span = Span {
+            line: func_span.line,
+            column: func_span.column,
+            start_pos: func_span.start_pos,
+            end_pos: name.1.end_pos
+        };
+        Ok(Node {
+            ast_repr: ASTNode::Function {
+                name,
+                params,
+                return_type,
+                body: Box::new(body)
+            },
+            span: built_span
+        })
+    }
+
+    fn parse_call(&mut self, callee: (String, Span)) -> Result<Node, Error> {
+        self.pos += 1;
+        let mut args: Vec<Node> = Vec::new();
+        while let Some(current_token) = self.get(0) {
+            if current_token.token_type == TokenType::RParen {
+                break
+            }
+            args.push(self.with_struct_literals(true, |p| p.parse_expression(0))?);
+
+            if let Some(Token { token_type: TokenType::Comma, .. }) = self.get(0) {
+                self.pos += 1;
+            } else {
+                break
+            }
+        }
+        let end = self.expect_and_take(&TokenType::RParen)?.span;
+
+        let built_span = Span {
+            line: callee.1.line,
+            column: callee.1.column,
+            start_pos: callee.1.start_pos,
+            end_pos: end.end_pos
+        };
+        Ok(Node { ast_repr: ASTNode::Call { callee, args }, span: built_span })
+    }
+
+    fn parse_return(&mut self) -> Result<Node, Error> {
+        let return_span = self.get(0).unwrap().span;
+        self.pos += 1;
+
+        let value = match self.get(0) {
+            None | Some(Token { token_type: TokenType::Semicolon | TokenType::RBrace, .. }) => None,
+            _ => Some(self.parse_expression(0)?)
+        };
+
+        let built_span = Span {
+            line: return_span.line,
+            column: return_span.column,
+            start_pos: return_span.start_pos,
+            end_pos: value.as_ref().map_or(return_span.end_pos, |v| v.span.end_pos)
+        };
+        Ok(Node { ast_repr: ASTNode::Return(value.map(Box::new)), span: built_span })
+    }
+
+    fn parse_while(&mut self) -> Result<Node, Error> {
+        let while_span = self.get(0).unwrap().span;
+        self.pos += 1;
+
+        let condition = self.with_struct_literals(false, |p| p.parse_expression(0))?;
+        if !matches!(self.get(0), Some(Token { token_type: TokenType::LBrace, .. })) {
+            self.expect(&TokenType::LBrace)?;
+        }
+        let body = self.parse_block()?;
+
+        let built_span = Span {
+            line: while_span.line,
+            column: while_span.column,
+            start_pos: while_span.start_pos,
+            end_pos: condition.span.end_pos
+        };
+        Ok(Node {
+            ast_repr: ASTNode::While { condition: Box::new(condition), body: Box::new(body) },
+            span: built_span
+        })
+    }
+
+    fn parse_loop(&mut self) -> Result<Node, Error> {
+        let loop_span = self.get(0).unwrap().span;
+        self.pos += 1;
+
+        if !matches!(self.get(0), Some(Token { token_type: TokenType::LBrace, .. })) {
+            self.expect(&TokenType::LBrace)?;
+        }
+        let body = self.parse_block()?;
+        Ok(Node { ast_repr: ASTNode::Loop(Box::new(body)), span: loop_span })
+    }
+
+    fn parse_break(&mut self) -> Result<Node, Error> {
+        let break_span = self.get(0).unwrap().span;
+        self.pos += 1;
+
+        let value = match self.get(0) {
+            None | Some(Token { token_type: TokenType::Semicolon | TokenType::RBrace | TokenType::Comma, .. }) => None,
+            _ => Some(self.parse_expression(0)?)
+        };
+
+        let built_span = Span {
+            line: break_span.line,
+            column: break_span.column,
+            start_pos: break_span.start_pos,
+            end_pos: value.as_ref().map_or(break_span.end_pos, |v| v.span.end_pos)
+        };
+        Ok(Node { ast_repr: ASTNode::Break(value.map(Box::new)), span: built_span })
+    }
+
+    fn parse_struct(&mut self) -> Result<Node, Error> {
+        let struct_span = self.get(0).unwrap().span;
+        self.pos += 1;
+
+        let t = self.expect_and_take(&TokenType::Identifier)?;
+        let name = (t.lexeme, t.span);
+
+        self.expect(&TokenType::LBrace)?;
+        let mut fields: Vec<((String, Span), (String, Span))> = Vec::new();
+        while let Some(current_token) = self.get(0) {
+            if current_token.token_type == TokenType::RBrace {
+                break
+            }
+            let t = self.expect_and_take(&TokenType::Identifier)?;
+            let field_name = (t.lexeme, t.span);
+            self.expect(&TokenType::Colon)?;
+            let t = self.expect_and_take(&TokenType::Identifier)?;
+            fields.push((field_name, (t.lexeme, t.span)));
+
+            if let Some(Token { token_type: TokenType::Comma, .. }) = self.get(0) {
+                self.pos += 1;
+            } else {
+                break
+            }
+        }
+        self.expect(&TokenType::RBrace)?;
+
+        let built_span = Span {
+            line: struct_span.line,
+            column: struct_span.column,
+            start_pos: struct_span.start_pos,
+            end_pos: name.1.end_pos
+        };
+        Ok(Node { ast_repr: ASTNode::StructDecl { name, fields }, span: built_span })
+    }
+
+    fn parse_enum(&mut self) -> Result<Node, Error> {
+        let enum_span = self.get(0).unwrap().span;
+        self.pos += 1;
+
+        let t = self.expect_and_take(&TokenType::Identifier)?;
+        let name = (t.lexeme, t.span);
+
+        self.expect(&TokenType::LBrace)?;
+        let mut variants: EnumVariants = Vec::new();
+        while let Some(current_token) = self.get(0) {
+            if current_token.token_type == TokenType::RBrace {
+                break
+            }
+            let t = self.expect_and_take(&TokenType::Identifier)?;
+            let variant = (t.lexeme, t.span);
+
+            let mut fields: Vec<(String, Span)> = Vec::new();
+            if let Some(Token { token_type: TokenType::LParen, .. }) = self.get(0) {
+                self.pos += 1;
+                while let Some(current_token) = self.get(0) {
+                    if current_token.token_type == TokenType::RParen {
+                        break
+                    }
+                    let t = self.expect_and_take(&TokenType::Identifier)?;
+                    fields.push((t.lexeme, t.span));
+
+                    if let Some(Token { token_type: TokenType::Comma, .. }) = self.get(0) {
+                        self.pos += 1;
+                    } else {
+                        break
+                    }
+                }
+                self.expect(&TokenType::RParen)?;
+            }
+            variants.push((variant, fields));
+
+            if let Some(Token { token_type: TokenType::Comma, .. }) = self.get(0) {
+                self.pos += 1;
+            } else {
+                break
+            }
+        }
+        self.expect(&TokenType::RBrace)?;
+
+        let built_span = Span {
+            line: enum_span.line,
+            column: enum_span.column,
+            start_pos: enum_span.start_pos,
+            end_pos: name.1.end_pos
+        };
+        Ok(Node { ast_repr: ASTNode::EnumDecl { name, variants }, span: built_span })
+    }
+
+    fn parse_enum_variant(&mut self, enum_name: (String, Span)) -> Result<Node, Error> {
+        self.pos += 1;
+        let t = self.expect_and_take(&TokenType::Identifier)?;
+        let variant = (t.lexeme, t.span);
+
+        let mut args: Vec<Node> = Vec::new();
+        let mut end = variant.1;
+        if let Some(Token { token_type: TokenType::LParen, .. }) = self.get(0) {
+            self.pos += 1;
+            while let Some(current_token) = self.get(0) {
+                if current_token.token_type == TokenType::RParen {
+                    break
+                }
+                args.push(self.with_struct_literals(true, |p| p.parse_expression(0))?);
+
+                if let Some(Token { token_type: TokenType::Comma, .. }) = self.get(0) {
+                    self.pos += 1;
+                } else {
+                    break
+                }
+            }
+            end = self.expect_and_take(&TokenType::RParen)?.span;
+        }
+
+        let built_span = Span {
+            line: enum_name.1.line,
+            column: enum_name.1.column,
+            start_pos: enum_name.1.start_pos,
+            end_pos: end.end_pos
+        };
+        Ok(Node { ast_repr: ASTNode::EnumVariant { enum_name, variant, args }, span: built_span })
+    }
+
+    fn parse_match(&mut self) -> Result<Node, Error> {
+        let match_span = self.get(0).unwrap().span;
+        self.pos += 1;
+
+        let scrutinee = self.with_struct_literals(false, |p| p.parse_expression(0))?;
+        self.expect(&TokenType::LBrace)?;
+
+        let mut arms: Vec<MatchArm> = Vec::new();
+        while let Some(current_token) = self.get(0) {
+            if current_token.token_type == TokenType::RBrace {
+                break
+            }
+            let pattern = self.parse_pattern()?;
+            self.expect(&TokenType::FatArrow)?;
+            let body = self.with_struct_literals(true, |p| p.parse_expression(0))?;
+            let is_block = matches!(body.ast_repr, ASTNode::Block(_));
+            arms.push(MatchArm { pattern, body });
+
+            if let Some(Token { token_type: TokenType::Comma, .. }) = self.get(0) {
+                self.pos += 1;
+            } else if !is_block {
+                break
+            }
+        }
+        self.expect(&TokenType::RBrace)?;
+
+        let built_span = Span {
+            line: match_span.line,
+            column: match_span.column,
+            start_pos: match_span.start_pos,
+            end_pos: scrutinee.span.end_pos
+        };
+        Ok(Node {
+            ast_repr: ASTNode::Match { scrutinee: Box::new(scrutinee), arms },
+            span: built_span
+        })
+    }
+
+    fn parse_pattern(&mut self) -> Result<Pattern, Error> {
+        let Some(current_token) = self.get(0).cloned() else {
+            return Err(Error {
+                code: ECode::UnexpectedEOF,
+                details: String::from("unexpected end of input, expected pattern"),
+                span: self.eof(),
+                src: self.src.clone(),
+                path: self.path.clone(),
+                note: None,
+                help: None
+            })
+        };
+        self.pos += 1;
+
+        match current_token.token_type {
+            TokenType::Identifier if current_token.lexeme == "_" => Ok(Pattern::Wildcard(current_token.span)),
+            TokenType::Identifier => {
+                if let Some(Token { token_type: TokenType::ColonColon, .. }) = self.get(0) {
+                    self.pos += 1;
+                    let t = self.expect_and_take(&TokenType::Identifier)?;
+
+                    let mut end = t.span;
+                    let mut fields: Vec<Pattern> = Vec::new();
+                    if let Some(Token { token_type: TokenType::LParen, .. }) = self.get(0) {
+                        self.pos += 1;
+                        while let Some(current_token) = self.get(0) {
+                            if current_token.token_type == TokenType::RParen {
+                                break
+                            }
+                            fields.push(self.parse_pattern()?);
+
+                            if let Some(Token { token_type: TokenType::Comma, .. }) = self.get(0) {
+                                self.pos += 1;
+                            } else {
+                                break
+                            }
+                        }
+                        end = self.expect_and_take(&TokenType::RParen)?.span;
+                    }
+                    let span = Span {
+                        line: current_token.span.line,
+                        column: current_token.span.column,
+                        start_pos: current_token.span.start_pos,
+                        end_pos: end.end_pos
+                    };
+                    Ok(Pattern::Variant {
+                        enum_name: (current_token.lexeme, current_token.span),
+                        variant: (t.lexeme, t.span),
+                        fields,
+                        span
+                    })
+                } else {
+                    Ok(Pattern::Binding(current_token.lexeme, current_token.span))
+                }
+            },
+            TokenType::Int => Ok(Pattern::Int(self.int_value(&current_token)?, current_token.span)),
+            TokenType::Bool => Ok(Pattern::Bool(current_token.lexeme.parse::<bool>().unwrap(), current_token.span)),
+            TokenType::Operator if current_token.lexeme == "-" => {
+                let t = self.expect_and_take(&TokenType::Int)?;
+                let span = Span {
+                    line: current_token.span.line,
+                    column: current_token.span.column,
+                    start_pos: current_token.span.start_pos,
+                    end_pos: t.span.end_pos
+                };
+                Ok(Pattern::Int(-self.int_value(&t)?, span))
+            },
+            _ => Err(Error {
+                code: ECode::UnexpectedToken,
+                details: format!("expected pattern, found {}", current_token),
+                span: current_token.span,
+                src: self.src.clone(),
+                path: self.path.clone(),
+                note: None,
+                help: None
+            })
+        }
+    }
+
+    /// `name {` starts a struct literal when followed by `}` or `field:`
+    fn at_struct_literal(&self) -> bool {
+        matches!(
+            (self.get(0), self.get(1), self.get(2)),
+            (
+                Some(Token { token_type: TokenType::LBrace, .. }),
+                Some(Token { token_type: TokenType::RBrace, .. }),
+                _
+            ) | (
+                Some(Token { token_type: TokenType::LBrace, .. }),
+                Some(Token { token_type: TokenType::Identifier, .. }),
+                Some(Token { token_type: TokenType::Colon, .. })
+            )
+        )
+    }
+
+    fn parse_struct_literal(&mut self, name: (String, Span)) -> Result<Node, Error> {
+        self.pos += 1;
+        let mut fields: Vec<((String, Span), Node)> = Vec::new();
+        while let Some(current_token) = self.get(0) {
+            if current_token.token_type == TokenType::RBrace {
+                break
+            }
+            let t = self.expect_and_take(&TokenType::Identifier)?;
+            self.expect(&TokenType::Colon)?;
+            let value = self.with_struct_literals(true, |p| p.parse_expression(0))?;
+            fields.push(((t.lexeme, t.span), value));
+
+            if let Some(Token { token_type: TokenType::Comma, .. }) = self.get(0) {
+                self.pos += 1;
+            } else {
+                break
+            }
+        }
+        let end = self.expect_and_take(&TokenType::RBrace)?.span;
+
+        let built_span = Span {
+            line: name.1.line,
+            column: name.1.column,
+            start_pos: name.1.start_pos,
+            end_pos: end.end_pos
+        };
+        Ok(Node { ast_repr: ASTNode::StructLit { name, fields }, span: built_span })
+    }
+
     fn try_mutation(&self) -> Result<(Node, Parser), Error> {
         let mut mp = Parser {
             pos: self.pos,
             tokens: self.tokens.clone(),
             src: self.src.clone(),
-            path: self.path.clone()
+            path: self.path.clone(),
+            struct_literals: self.struct_literals
         };
         let t = mp.expect_and_take(&TokenType::Identifier)?;
         let name = (t.lexeme, t.span);
+
+        let mut fields: Vec<(String, Span)> = Vec::new();
+        while let Some(Token { token_type: TokenType::Dot, .. }) = mp.get(0) {
+            mp.pos += 1;
+            let t = mp.expect_and_take(&TokenType::Identifier)?;
+            fields.push((t.lexeme, t.span));
+        }
         
         mp.expect(&TokenType::Equals)?;
 
@@ -401,12 +1065,12 @@ impl Parser {
             start_pos: name.1.start_pos,
             end_pos: value.span.end_pos
         };
-        Ok((Node { 
-            ast_repr: ASTNode::Mutation {
-                name, value: Box::new(value)
-            },
-            span: built_span
-        }, mp))
+        let ast_repr = if fields.is_empty() {
+            ASTNode::Mutation { name, value: Box::new(value) }
+        } else {
+            ASTNode::FieldMutation { name, fields, value: Box::new(value) }
+        };
+        Ok((Node { ast_repr, span: built_span }, mp))
     }
 
     fn try_var_decl(&self) -> Result<(Node, Parser), Error> {
@@ -414,7 +1078,8 @@ impl Parser {
             pos: self.pos,
             tokens: self.tokens.clone(),
             src: self.src.clone(),
-            path: self.path.clone()
+            path: self.path.clone(),
+            struct_literals: self.struct_literals
         };
 
         let (mutability, mut s1) = if let Some(t) = vp.get(0).cloned() {
@@ -457,6 +1122,13 @@ impl Parser {
         } else {
             None
         };
+        // A lone name is an identifier expression, `parse_statement` handles `x;`
+        if !mutability && var_type.0 == ParseType::Inferred && value.is_none() {
+            return Err(Error {
+                code: ECode::UnexpectedToken, details: "Found lone identifier, halting into expression".to_string(),
+                span: n_span, src: vp.src, path: vp.path, note: Some("You shouldn't be seeing this. If you are, please report it".to_string()), help: None
+            })
+        }
         Ok((Node {
             ast_repr: match value {
                 Some(v) => ASTNode::DeclarationWithValue {
@@ -475,6 +1147,26 @@ impl Parser {
     }
 
     fn parse_statement(&mut self) -> Result<Node, Error> {
+        if let (
+            Some(Token { token_type: TokenType::Identifier, lexeme, span }),
+            Some(Token { token_type: TokenType::Semicolon, span: end, .. })
+        ) = (self.get(0).cloned(), self.get(1).cloned()) {
+            self.pos += 2;
+            let declaration = Node {
+                ast_repr: ASTNode::Declaration {
+                    type_: (ParseType::Inferred, None),
+                    mutability: false,
+                    name: (lexeme, span)
+                },
+                span
+            };
+            return Ok(Node { ast_repr: ASTNode::Statement(Box::new(declaration)), span: Span {
+                line: span.line,
+                column: span.column,
+                start_pos: span.start_pos,
+                end_pos: end.end_pos
+            } })
+        }
         let mut r = self.parse_expression(0)?;
         if let Some(Token { token_type, span, .. }) = self.get(0) {
             if *token_type == TokenType::Semicolon {
@@ -501,6 +1193,18 @@ impl Parser {
         self.get(offset).unwrap().span
     }
 
+    /// The value of an integer token, whose range is checked against its type later
+    fn int_value(&self, token: &Token) -> Result<i128, Error> {
+        token.lexeme.parse::<i128>().map_err(|_| Error {
+            code: ECode::UnexpectedToken,
+            details: format!("integer literal `{}` is too large", token.lexeme),
+            span: token.span,
+            src: self.src.clone(),
+            path: self.path.clone(),
+            note: None,
+            help: None
+        })
+    }
     
     fn expect_and_take(&mut self, expected: &TokenType) -> Result<Token, Error> {
         if let Some(current_token) = self.get(0).cloned() {
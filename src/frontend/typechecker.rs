@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use strsim::jaro_winkler;
 use super::*;
 use super::Error;
@@ -8,7 +8,58 @@ pub struct TypeChecker {
     scopes: Vec<HashMap<Symbol, Span>>,
     src: String,
     path: String,
-    type_registry: TypeRegistry
+    type_registry: TypeRegistry,
+    return_type: Option<Type>,
+    type_spans: HashMap<String, Span>,
+    loops: Vec<LoopContext>,
+    init: InitState
+}
+
+/// An enclosing loop, `break` inside a `while` never carries a value.
+/// `entry` holds the assignment state when the loop was entered and
+/// `breaks` the assignment state at each `break`
+struct LoopContext {
+    is_while: bool,
+    break_type: Option<Type>,
+    entry: InitState,
+    breaks: Vec<InitState>
+}
+
+/// Assignment state of the bindings declared without a value, keyed by
+/// the span of their declaration
+#[derive(Debug, Clone, Default)]
+struct InitState {
+    /// Bindings some path reaches without assigning them
+    unassigned: HashSet<Span>,
+    /// Bindings some path has assigned, with the span of an assignment
+    assigned: HashMap<Span, Span>
+}
+
+impl InitState {
+    /// The state where the paths reaching `self` and `other` meet, keeping
+    /// the latest assignment of bindings both paths assigned
+    fn join(mut self, other: InitState) -> Self {
+        self.unassigned.extend(other.unassigned);
+        for (declaration, site) in other.assigned {
+            let latest = self.assigned.entry(declaration).or_insert(site);
+            if site.start_pos > latest.start_pos {
+                *latest = site;
+            }
+        }
+        self
+    }
+
+    fn is_deferred(&self, declaration: &Span) -> bool {
+        self.unassigned.contains(declaration) || self.assigned.contains_key(declaration)
+    }
+}
+
+type StructFields = Vec<((String, Span), (String, Span))>;
+
+/// A top-level struct or enum awaiting registration
+enum TypeDecl {
+    Struct(StructFields),
+    Enum(EnumVariants)
 }
 
 impl TypeChecker {
@@ -18,7 +69,11 @@ impl TypeChecker {
             scopes: vec![HashMap::new()],
             src,
             path,
-            type_registry: TypeRegistry::new()
+            type_registry: TypeRegistry::new(),
+            return_type: None,
+            type_spans: HashMap::new(),
+            loops: Vec::new(),
+            init: InitState::default()
         }
     }
 
@@ -53,56 +108,163 @@ impl TypeChecker {
             src: self.src.clone(), 
             path: self.path.clone(),
             note: None,
-            help: if let Some(name) = top_contender.0 {
-                Some(format!("did you mean: `{}`?", name))
-            } else {
-                None
-            }
+            help: top_contender.0.map(|name| format!("did you mean: `{}`?", name))
         })
     }
 
-    #[allow(irrefutable_let_patterns)]
+    /// Error for giving `i` a value of type `unit`, which has no value to hold
+    fn unit_binding(&self, i: &str, span: Span) -> Error {
+        Error {
+            code: ECode::MismatchedTypes,
+            details: format!("`{}` cannot hold a value of type `unit`", i),
+            span,
+            src: self.src.clone(),
+            path: self.path.clone(),
+            note: None,
+            help: Some("evaluate the expression as a statement instead".to_string())
+        }
+    }
+
+    /// Assigns a value of type `new` to `i`. The first assignment to a
+    /// binding declared without a value gives it its type, and is allowed
+    /// once even if the binding is immutable
     pub fn mutate_var(&mut self, i: &String, span: Span, new: Type) -> Result<(), Error> {
-        let mut reversed_scopes = self.scopes.clone();
-        reversed_scopes.reverse();
+        self.find_identifier(i, span)?;
+        let (depth, symbol, declaration) = self.find_variable(i).expect("`find_identifier` found the variable");
+        let Symbol::Variable { type_, mutability, .. } = &symbol else { unreachable!() };
 
+        let deferred = self.init.is_deferred(&declaration);
+        if !*mutability {
+            let error = |details: String, note: Option<String>| Error {
+                code: ECode::MutationError,
+                details,
+                span,
+                src: self.src.clone(),
+                path: self.path.clone(),
+                note,
+                help: Some(format!("declare `{}` with `mut`", i))
+            };
+            if !deferred {
+                return Err(error(format!("cannot mutate immutable variable `{}`", i), None))
+            }
+            if self.init.assigned.contains_key(&declaration) {
+                return Err(error(
+                    format!("cannot assign twice to immutable variable `{}`", i),
+                    Some(format!("`{}` may already be assigned at this point", i))
+                ))
+            }
+        }
+
+        if new == Type::Unit {
+            return Err(self.unit_binding(i, span))
+        }
+        if *type_ == Type::Undetermined {
+            if new != Type::Never {
+                let Symbol::Variable { name, mutability, .. } = symbol.clone() else { unreachable!() };
+                let scope = &mut self.scopes[depth];
+                scope.remove(&symbol);
+                scope.insert(Symbol::Variable { name, type_: new, mutability }, declaration);
+            }
+        } else if *type_ != new && new != Type::Never {
+            return Err(Error {
+                code: ECode::MutationError,
+                details: format!("`{}` expects type `{}` but found type `{}`", i, type_, new),
+                span,
+                src: self.src.clone(),
+                path: self.path.clone(),
+                note: None,
+                help: None
+            })
+        }
+
+        if deferred {
+            self.init.unassigned.remove(&declaration);
+            self.init.assigned.insert(declaration, span);
+        }
+        Ok(())
+    }
+
+    /// The innermost variable named `i`, with the index of its scope and
+    /// the span of its declaration
+    fn find_variable(&self, i: &str) -> Option<(usize, Symbol, Span)> {
+        self.scopes.iter().enumerate().rev().find_map(|(depth, scope)| {
+            scope.iter().find_map(|(symbol, span)| match symbol {
+                Symbol::Variable { name, .. } if name == i => Some((depth, symbol.clone(), *span)),
+                _ => None
+            })
+        })
+    }
+
+    /// Errors if the path jumping back to the start of the innermost loop
+    /// assigned an immutable binding declared outside of it inside the loop,
+    /// the next iteration would assign it again
+    fn check_back_edge(&self) -> Result<(), Error> {
+        let Some(context) = self.loops.last() else { return Ok(()) };
+        let reassigned = self.init.assigned
+            .iter()
+            .filter(|(declaration, site)| {
+                context.entry.unassigned.contains(*declaration)
+                    && context.entry.assigned.get(*declaration) != Some(*site)
+            })
+            .filter_map(|(declaration, site)| {
+                self.scopes.iter().flatten().find_map(|(symbol, span)| match symbol {
+                    Symbol::Variable { name, mutability: false, .. } if span == declaration => Some((name, *site)),
+                    _ => None
+                })
+            })
+            .min_by_key(|(_, site)| site.start_pos);
+        let Some((name, site)) = reassigned else { return Ok(()) };
+        Err(Error {
+            code: ECode::MutationError,
+            details: format!("cannot assign to immutable variable `{}` inside a loop", name),
+            span: site,
+            src: self.src.clone(),
+            path: self.path.clone(),
+            note: Some(format!("`{}` is declared outside the loop, the next iteration would assign it again", name)),
+            help: Some(format!("declare `{}` with `mut`", name))
+        })
+    }
+
+    /// Errors if `i` is read on a path that has not assigned it
+    fn check_assigned(&self, i: &str, span: Span) -> Result<(), Error> {
+        let Some((_, _, declaration)) = self.find_variable(i) else { return Ok(()) };
+        if !self.init.unassigned.contains(&declaration) {
+            return Ok(())
+        }
+        let details = if self.init.assigned.contains_key(&declaration) {
+            format!("`{}` is possibly used before being assigned", i)
+        } else {
+            format!("`{}` is used before being assigned", i)
+        };
+        Err(Error {
+            code: ECode::Uninitialized,
+            details,
+            span,
+            src: self.src.clone(),
+            path: self.path.clone(),
+            note: Some(format!(
+                "`{}` is declared without a value here: {}:{}:{}",
+                i, self.path, declaration.line + 1, declaration.column
+            )),
+            help: Some(format!("assign to `{}` on every path before reading it", i))
+        })
+    }
+
+    pub fn find_function(&self, i: &String, span: Span) -> Result<(Vec<Type>, Type), Error> {
         let mut available_names: Vec<String> = Vec::new();
         let mut top_contender: (Option<String>, f64) = (None, 0.0);
 
-        for scope in &mut reversed_scopes {
-            for symbol in scope {
-                if let Symbol::Variable { name, type_, mutability } = symbol.0 {
+        for scope in self.scopes.iter().rev() {
+            for symbol in scope.keys() {
+                if let Symbol::Function { name, params, return_type } = symbol {
                     available_names.push(name.clone());
-                    if name == i {
-                        if *mutability {
-                            if *type_ != new {
-                                return Err(Error { 
-                                    code: ECode::MutationError, 
-                                    details: format!("`{}` expects type `{}` but found type `{}`", i, type_, new), 
-                                    span, 
-                                    src: self.src.clone(), 
-                                    path: self.path.clone(),
-                                    note: None,
-                                    help: None
-                                })
-                            } else {
-                                return Ok(())
-                            }
-                        } else {
-                            return Err(Error { 
-                                code: ECode::MutationError, 
-                                details: format!("cannot mutate immutable variable `{}`", i), 
-                                span, 
-                                src: self.src.clone(), 
-                                path: self.path.clone(),
-                                note: None,
-                                help: None
-                            })
-                        }
-                    }
+                    if name == i { return Ok((params.clone(), return_type.clone())) }
                 }
             }
         }
+        if let Some(builtin) = builtin_function(i) {
+            return Ok(builtin)
+        }
 
         for name in available_names {
             let score = jaro_winkler(i, &name);
@@ -113,22 +275,416 @@ impl TypeChecker {
 
         Err(Error { 
             code: ECode::UndefinedIdentifier, 
-            details: format!("cannot find `{}` in scope", i), 
+            details: format!("cannot find function `{}` in scope", i), 
             span, 
             src: self.src.clone(), 
             path: self.path.clone(),
             note: None,
-            help: if let Some(name) = top_contender.0 {
-                Some(format!("did you mean: `{}`?", name))
-            } else {
-                None
+            help: top_contender.0.map(|name| format!("did you mean: `{}`?", name))
+        })
+    }
+
+    /// Span of the declaration of `i` in the innermost scope. Names from
+    /// enclosing scopes may be shadowed, so only this scope conflicts
+    fn declared_in_scope(&self, i: &str) -> Option<Span> {
+        self.scopes.last()?.iter().find_map(|(symbol, span)| match symbol {
+            Symbol::Variable { name, .. } if name == i => Some(*span),
+            _ => None
+        })
+    }
+
+    fn previously_declared(&self, name: &str, span: Span) -> String {
+        format!("`{}` was previously declared here: {}:{}:{}", name, self.path, span.line + 1, span.column)
+    }
+
+    fn resolve_type(&self, t: &(String, Span)) -> Result<Type, Error> {
+        self.type_registry.get(&t.0).ok_or_else(|| Error {
+            code: ECode::MismatchedTypes,
+            details: format!("unregistered type `{}`", t.0),
+            span: t.1,
+            src: self.src.clone(),
+            path: self.path.clone(),
+            note: None,
+            help: None
+        })
+    }
+
+    /// Registers the signature of a top-level function so it can be called
+    /// before its declaration
+    fn declare_function(&mut self, node: &Node) -> Result<(), Error> {
+        let node = match &node.ast_repr {
+            ASTNode::Statement(inner) => inner,
+            _ => node
+        };
+        let ASTNode::Function { name, params, return_type, .. } = &node.ast_repr else { return Ok(()) };
+
+        if name.0 == "main" {
+            return Err(Error {
+                code: ECode::MismatchedTypes,
+                details: "`main` is reserved for the program entry point".to_string(),
+                span: name.1,
+                src: self.src.clone(),
+                path: self.path.clone(),
+                note: Some("top-level statements are compiled into `main`".to_string()),
+                help: None
+            })
+        }
+        let previous = self.scopes[0].iter().find_map(|(symbol, span)| match symbol {
+            Symbol::Function { name: n, .. } if *n == name.0 => Some(*span),
+            _ => None
+        });
+        if let Some(previous) = previous {
+            return Err(Error {
+                code: ECode::MismatchedTypes,
+                details: format!("function `{}` is already declared", name.0),
+                span: name.1,
+                src: self.src.clone(),
+                path: self.path.clone(),
+                note: Some(self.previously_declared(&name.0, previous)),
+                help: None
+            })
+        }
+
+        let mut param_types: Vec<Type> = Vec::new();
+        for (i, param) in params.iter().enumerate() {
+            if params[..i].iter().any(|p| p.name.0 == param.name.0) {
+                return Err(Error {
+                    code: ECode::MismatchedTypes,
+                    details: format!("parameter `{}` is declared more than once", param.name.0),
+                    span: param.name.1,
+                    src: self.src.clone(),
+                    path: self.path.clone(),
+                    note: None,
+                    help: None
+                })
+            }
+            param_types.push(self.resolve_type(&param.type_)?);
+        }
+        let return_type = match return_type {
+            Some(t) => self.resolve_type(t)?,
+            None => Type::Unit
+        };
+
+        self.scopes[0].insert(Symbol::Function {
+            name: name.0.clone(), params: param_types, return_type
+        }, name.1);
+        Ok(())
+    }
+
+    /// Registers a top-level struct or enum, defining the types its fields
+    /// refer to first
+    fn declare_type(
+        &mut self,
+        name: &(String, Span),
+        decls: &HashMap<String, (Span, TypeDecl)>,
+        visiting: &mut Vec<String>
+    ) -> Result<(), Error> {
+        if self.type_registry.layout(&name.0).is_some() || self.type_registry.enum_layout(&name.0).is_some() {
+            return Ok(())
+        }
+        if let Some(start) = visiting.iter().position(|n| *n == name.0) {
+            let kind = match decls[&name.0].1 {
+                TypeDecl::Struct(_) => "struct",
+                TypeDecl::Enum(_) => "enum"
+            };
+            return Err(Error {
+                code: ECode::MismatchedTypes,
+                details: format!("recursive {} `{}` has infinite size", kind, name.0),
+                span: name.1,
+                src: self.src.clone(),
+                path: self.path.clone(),
+                note: Some(format!("`{}` contains itself through `{}`", name.0, visiting[start..].join("` -> `"))),
+                help: None
+            })
+        }
+        visiting.push(name.0.clone());
+
+        let fields = match &decls[&name.0].1 {
+            TypeDecl::Struct(fields) => fields,
+            TypeDecl::Enum(variants) => return self.declare_enum(name, variants, decls, visiting)
+        };
+        let mut resolved: Vec<(String, Type)> = Vec::new();
+        for (i, (field, type_)) in fields.iter().enumerate() {
+            if fields[..i].iter().any(|(f, _)| f.0 == field.0) {
+                return Err(Error {
+                    code: ECode::MismatchedTypes,
+                    details: format!("field `{}` is declared more than once", field.0),
+                    span: field.1,
+                    src: self.src.clone(),
+                    path: self.path.clone(),
+                    note: None,
+                    help: None
+                })
+            }
+            if let Some((span, _)) = decls.get(&type_.0) {
+                self.declare_type(&(type_.0.clone(), *span), decls, visiting)?;
+            }
+            resolved.push((field.0.clone(), self.resolve_type(type_)?));
+        }
+
+        visiting.pop();
+        self.type_registry.define_struct(&name.0, resolved);
+        Ok(())
+    }
+
+    fn declare_enum(
+        &mut self,
+        name: &(String, Span),
+        variants: &EnumVariants,
+        decls: &HashMap<String, (Span, TypeDecl)>,
+        visiting: &mut Vec<String>
+    ) -> Result<(), Error> {
+        let mut resolved: Vec<(String, Vec<Type>)> = Vec::new();
+        for (i, (variant, types)) in variants.iter().enumerate() {
+            if variants[..i].iter().any(|(v, _)| v.0 == variant.0) {
+                return Err(Error {
+                    code: ECode::MismatchedTypes,
+                    details: format!("variant `{}` is declared more than once", variant.0),
+                    span: variant.1,
+                    src: self.src.clone(),
+                    path: self.path.clone(),
+                    note: None,
+                    help: None
+                })
+            }
+            let mut fields: Vec<Type> = Vec::new();
+            for type_ in types {
+                if let Some((span, _)) = decls.get(&type_.0) {
+                    self.declare_type(&(type_.0.clone(), *span), decls, visiting)?;
+                }
+                fields.push(self.resolve_type(type_)?);
+            }
+            resolved.push((variant.0.clone(), fields));
+        }
+
+        visiting.pop();
+        self.type_registry.define_enum(&name.0, resolved);
+        Ok(())
+    }
+
+    /// Variant `variant` of enum `enum_name` as its index and field types
+    fn find_variant(&self, enum_name: &(String, Span), variant: &(String, Span)) -> Result<(usize, Vec<Type>), Error> {
+        let Some(layout) = self.type_registry.enum_layout(&enum_name.0) else {
+            return Err(Error {
+                code: ECode::UndefinedIdentifier,
+                details: format!("cannot find enum `{}` in scope", enum_name.0),
+                span: enum_name.1,
+                src: self.src.clone(),
+                path: self.path.clone(),
+                note: None,
+                help: None
+            })
+        };
+        if let Some((index, v)) = layout.variant(&variant.0) {
+            return Ok((index, v.fields.iter().map(|f| f.type_.clone()).collect()))
+        }
+
+        let suggestion = layout.variants
+            .iter()
+            .map(|v| (v, jaro_winkler(&variant.0, &v.name)))
+            .filter(|(_, score)| *score >= 0.7)
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        Err(Error {
+            code: ECode::UndefinedIdentifier,
+            details: format!("no variant `{}` on enum `{}`", variant.0, enum_name.0),
+            span: variant.1,
+            src: self.src.clone(),
+            path: self.path.clone(),
+            note: None,
+            help: suggestion.map(|(v, _)| format!("did you mean: `{}::{}`?", enum_name.0, v.name))
+        })
+    }
+
+    /// Checks `pattern` against a value of type `expected`, collecting the
+    /// names it binds
+    fn check_pattern(&self, pattern: &Pattern, expected: &Type, bindings: &mut Vec<(String, Type, Span)>) -> Result<(), Error> {
+        let found = match pattern {
+            Pattern::Wildcard(_) => return Ok(()),
+            Pattern::Binding(name, span) => {
+                if bindings.iter().any(|(n, _, _)| n == name) {
+                    return Err(Error {
+                        code: ECode::MismatchedTypes,
+                        details: format!("`{}` is bound more than once in the same pattern", name),
+                        span: *span,
+                        src: self.src.clone(),
+                        path: self.path.clone(),
+                        note: None,
+                        help: None
+                    })
+                }
+                bindings.push((name.clone(), expected.clone(), *span));
+                return Ok(())
+            },
+            Pattern::Int(n, span) => match int_range(expected) {
+                Some(_) => return self.int_literal(*n, Some(expected), *span).map(|_| ()),
+                None => "integer".to_string()
+            },
+            Pattern::Bool(..) => Type::Boolean.to_string(),
+            Pattern::Variant { enum_name, variant, fields, .. } => {
+                let (_, types) = self.find_variant(enum_name, variant)?;
+                if fields.len() != types.len() {
+                    return Err(Error {
+                        code: ECode::MismatchedTypes,
+                        details: format!(
+                            "variant `{}::{}` has {} field(s) but the pattern has {}",
+                            enum_name.0, variant.0, types.len(), fields.len()
+                        ),
+                        span: pattern.span(),
+                        src: self.src.clone(),
+                        path: self.path.clone(),
+                        note: None,
+                        help: None
+                    })
+                }
+                if Type::Alias(enum_name.0.clone()) == *expected {
+                    for (field, type_) in fields.iter().zip(&types) {
+                        self.check_pattern(field, type_, bindings)?;
+                    }
+                    return Ok(())
+                }
+                enum_name.0.clone()
             }
+        };
+        if let Pattern::Bool(..) = pattern {
+            if *expected == Type::Boolean {
+                return Ok(())
+            }
+        }
+        Err(Error {
+            code: ECode::MismatchedTypes,
+            details: format!("expected `{}`, found `{}`", expected, found),
+            span: pattern.span(),
+            src: self.src.clone(),
+            path: self.path.clone(),
+            note: Some("the pattern's type must match the type of the matched value".to_string()),
+            help: None
         })
     }
 
+    /// Types an integer literal as `expected` if that is an integer type and
+    /// `i32` otherwise, the value must fit in the chosen type
+    fn int_literal(&self, n: i128, expected: Option<&Type>, span: Span) -> Result<Type, Error> {
+        let t = expected.filter(|t| int_range(t).is_some()).cloned().unwrap_or(Type::Int32);
+        let (min, max) = int_range(&t).unwrap();
+        if n < min || n > max {
+            return Err(Error {
+                code: ECode::MismatchedTypes,
+                details: format!("integer literal `{}` is out of range for `{}`", n, t),
+                span,
+                src: self.src.clone(),
+                path: self.path.clone(),
+                note: Some(format!("`{}` ranges from {} to {}", t, min, max)),
+                help: None
+            })
+        }
+        Ok(t)
+    }
+
+    /// Type of `field` on a value of type `t`
+    fn field_type(&self, t: &Type, field: &(String, Span)) -> Result<Type, Error> {
+        let layout = match t {
+            Type::Alias(name) => self.type_registry.layout(name),
+            _ => None
+        };
+        let Some(layout) = layout else {
+            return Err(Error {
+                code: ECode::MismatchedTypes,
+                details: format!("type `{}` has no fields", t),
+                span: field.1,
+                src: self.src.clone(),
+                path: self.path.clone(),
+                note: None,
+                help: None
+            })
+        };
+        if let Some((_, f)) = layout.field(&field.0) {
+            return Ok(f.type_.clone())
+        }
+
+        let suggestion = layout.fields
+            .iter()
+            .map(|f| (f, jaro_winkler(&field.0, &f.name)))
+            .filter(|(_, score)| *score >= 0.7)
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        Err(Error {
+            code: ECode::UndefinedIdentifier,
+            details: format!("no field `{}` on type `{}`", field.0, t),
+            span: field.1,
+            src: self.src.clone(),
+            path: self.path.clone(),
+            note: None,
+            help: suggestion.map(|(f, _)| format!("did you mean: `{}`?", f.name))
+        })
+    }
+
+    fn is_mutable(&self, i: &str) -> bool {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.keys())
+            .find_map(|symbol| match symbol {
+                Symbol::Variable { name, mutability, .. } if name == i => Some(*mutability),
+                _ => None
+            })
+            .unwrap_or(false)
+    }
+
     pub fn check(&mut self) -> Vec<Error> {
         let mut errors: Vec<Error> = Vec::new();
-        for node in self.module.0.clone() {
+        let mut declared: Vec<bool> = vec![true; self.module.0.len()];
+
+        let mut decls: HashMap<String, (Span, TypeDecl)> = HashMap::new();
+        let mut indices: HashMap<String, usize> = HashMap::new();
+        for (i, node) in self.module.0.iter().enumerate() {
+            let node = match &node.ast_repr {
+                ASTNode::Statement(inner) => inner,
+                _ => node
+            };
+            let (name, decl) = match &node.ast_repr {
+                ASTNode::StructDecl { name, fields } => (name, TypeDecl::Struct(fields.clone())),
+                ASTNode::EnumDecl { name, variants } => (name, TypeDecl::Enum(variants.clone())),
+                _ => continue
+            };
+            if self.type_registry.is_registered(&name.0) || decls.contains_key(&name.0) {
+                errors.push(Error {
+                    code: ECode::MismatchedTypes,
+                    details: format!("`{}` is already registed as a type", name.0),
+                    span: name.1,
+                    src: self.src.clone(),
+                    path: self.path.clone(),
+                    note: None,
+                    help: None
+                });
+                declared[i] = false;
+                continue
+            }
+            decls.insert(name.0.clone(), (name.1, decl));
+            indices.insert(name.0.clone(), i);
+            self.type_spans.insert(name.0.clone(), name.1);
+        }
+        let mut names: Vec<(String, Span)> = decls.iter().map(|(n, (s, _))| (n.clone(), *s)).collect();
+        names.sort_by_key(|(_, s)| s.start_pos);
+        for name in names {
+            if let Err(e) = self.declare_type(&name, &decls, &mut Vec::new()) {
+                // Types containing a broken type report the same error
+                if !errors.contains(&e) {
+                    errors.push(e);
+                }
+                declared[indices[&name.0]] = false;
+            }
+        }
+
+        for (i, node) in self.module.0.clone().iter().enumerate() {
+            let result = self.declare_function(node);
+            if let Err(s) = result {
+                declared[i] = false;
+                errors.push(s)
+            }
+        }
+        for (node, declared) in self.module.0.clone().into_iter().zip(declared) {
+            if !declared {
+                continue
+            }
             let result = self.check_node(node.clone());
             if let Err(s) = result{
                 errors.push(s)
@@ -138,19 +694,76 @@ impl TypeChecker {
     }
 
     pub fn check_node(&mut self, node: Node) -> Result<Type, Error> {
+        self.check_expecting(node, None)
+    }
+
+    /// Checks `node` where a value of type `expected` is wanted, numeric
+    /// literals take that type when they can hold it
+    pub fn check_expecting(&mut self, node: Node, expected: Option<&Type>) -> Result<Type, Error> {
         match node.ast_repr {
-            ASTNode::IntLit(_) => Ok(Type::Int32),
-            ASTNode::FloatLit(_) => Ok(Type::Float64),
+            ASTNode::IntLit(n) => self.int_literal(n, expected, node.span),
+            ASTNode::FloatLit(f) => {
+                if expected != Some(&Type::Float32) {
+                    return Ok(Type::Float64)
+                }
+                if f.abs() > f32::MAX as f64 {
+                    return Err(Error {
+                        code: ECode::MismatchedTypes,
+                        details: format!("float literal `{}` is out of range for `f32`", f),
+                        span: node.span,
+                        src: self.src.clone(),
+                        path: self.path.clone(),
+                        note: Some(format!("`f32` ranges from {:e} to {:e}", f32::MIN, f32::MAX)),
+                        help: Some("use `f64` for larger values".to_string())
+                    })
+                }
+                Ok(Type::Float32)
+            },
             ASTNode::StringLit(_) => Ok(Type::String),
             ASTNode::Bool(_) => Ok(Type::Boolean),
-            ASTNode::Identifier(s) => self.find_identifier(&s, node.span),
+            ASTNode::Identifier(s) => {
+                let type_ = self.find_identifier(&s, node.span)?;
+                self.check_assigned(&s, node.span)?;
+                Ok(type_)
+            },
             ASTNode::BinOp {
                 op, lhs, rhs
-            } => {
-                let left = self.check_node(*lhs.clone())?;
+            } if matches!(&*op.0, "&&" | "||") => {
+                let left = self.check_node(*lhs)?;
+                // The right operand only runs when the left one does not decide
+                let entry = self.init.clone();
                 let right = self.check_node(*rhs)?;
+                self.init = entry.join(std::mem::take(&mut self.init));
+
+                if left == Type::Boolean && right == Type::Boolean {
+                    Ok(Type::Boolean)
+                } else {
+                    Err(Error {
+                        code: ECode::MismatchedTypes,
+                        details: format!("cannot do `{}` operation on types `{}`, `{}`", op.0, left, right),
+                        span: node.span,
+                        src: self.src.clone(),
+                        path: self.path.clone(),
+                        note: Some(format!("`{}` only takes `bool` operands", op.0)),
+                        help: None
+                    })
+                }
+            },
+            ASTNode::BinOp {
+                op, lhs, rhs
+            } => {
+                // Literals take the type of the other operand
+                let hint = if is_arithmetic(&op.0) { expected } else { None };
+                let (left, right) = if lhs.is_literal() && !rhs.is_literal() {
+                    let right = self.check_expecting(*rhs, hint)?;
+                    (self.check_expecting(*lhs, Some(&right))?, right)
+                } else {
+                    let left = self.check_expecting(*lhs, hint)?;
+                    let right = self.check_expecting(*rhs, Some(&left))?;
+                    (left, right)
+                };
                 match &*op.0 {
-                    "+" | "-" | "*" | "/" | ">" | "<" | ">=" | "<=" => match (left.clone(), right.clone()) {
+                    "+" | "-" | "*" | "/" | "%" => match (left.clone(), right.clone()) {
                         (Type::Int8, Type::Int8) => Ok(Type::Int8),
                         (Type::Int16, Type::Int16) => Ok(Type::Int16),
                         (Type::Int32, Type::Int32) => Ok(Type::Int32),
@@ -171,7 +784,39 @@ impl TypeChecker {
                             help: None
                         })
                     },
-                    "==" | "!=" => if left == right { Ok(left) } else {
+                    ">" | "<" | ">=" | "<=" => match (left.clone(), right.clone()) {
+                        (Type::Int8, Type::Int8) | (Type::Int16, Type::Int16)
+                        | (Type::Int32, Type::Int32) | (Type::Int64, Type::Int64)
+                        | (Type::Float32, Type::Float32) | (Type::Float64, Type::Float64)
+                        | (Type::UInt8, Type::UInt8) | (Type::UInt16, Type::UInt16)
+                        | (Type::UInt32, Type::UInt32) | (Type::UInt64, Type::UInt64) => Ok(Type::Boolean),
+                        _ => Err(Error {
+                            code: ECode::MismatchedTypes,
+                            details: format!("cannot do `{}` operation on types `{}`, `{}`", op.0, left, right),
+                            span: node.span,
+                            src: self.src.clone(),
+                            path: self.path.clone(),
+                            note: None,
+                            help: None
+                        })
+                    },
+                    "&" | "|" | "^" | "<<" | ">>" => {
+                        let bitwise = int_range(&left).is_some() || (left == Type::Boolean && !matches!(&*op.0, "<<" | ">>"));
+                        if left == right && bitwise {
+                            Ok(left)
+                        } else {
+                            Err(Error {
+                                code: ECode::MismatchedTypes,
+                                details: format!("cannot do `{}` operation on types `{}`, `{}`", op.0, left, right),
+                                span: node.span,
+                                src: self.src.clone(),
+                                path: self.path.clone(),
+                                note: None,
+                                help: None
+                            })
+                        }
+                    },
+                    "==" | "!=" => if left == right { Ok(Type::Boolean) } else {
                         Err(Error {
                             code: ECode::MismatchedTypes,
                             details: format!("cannot do `{}` operation on types `{}`, `{}`", op.0, left, right),
@@ -208,7 +853,13 @@ impl TypeChecker {
             ASTNode::UnaOp {
                 operand, op
             } => {
-                let operand_type = self.check_node(*operand)?;
+                // Negative literals are checked against the range as a whole
+                if let ("-", ASTNode::IntLit(n)) = (&*op.0, &operand.ast_repr) {
+                    if !expected.is_some_and(|t| matches!(int_range(t), Some((0, _)))) {
+                        return self.int_literal(-*n, expected, node.span)
+                    }
+                }
+                let operand_type = self.check_expecting(*operand, expected)?;
                 match &*op.0 {
                     "+" => match operand_type {
                         Type::Int8 => Ok(Type::Int8),
@@ -292,11 +943,25 @@ impl TypeChecker {
             ASTNode::If {
                 condition, then_body, else_body
             } => {
-                let then_type = self.check_node(*then_body.clone())?;
-                let else_type = self.check_node(*else_body.clone())?;
                 let condition_type = self.check_node(*condition)?;
+                let entry = self.init.clone();
+                let then_type = self.check_expecting(*then_body.clone(), expected)?;
+                let then_init = std::mem::replace(&mut self.init, entry);
+                let else_type = self.check_expecting(*else_body.clone(), expected.or(Some(&then_type)))?;
+
+                // Only the branches that fall through reach the code after the `if`
+                if then_type != Type::Never {
+                    let else_init = std::mem::take(&mut self.init);
+                    self.init = if else_type == Type::Never { then_init } else { then_init.join(else_init) };
+                }
 
                 if let Type::Boolean = condition_type {
+                    if then_type == Type::Never {
+                        return Ok(else_type)
+                    }
+                    if else_type == Type::Never {
+                        return Ok(then_type)
+                    }
                     if let ASTNode::Block(v) = (*then_body).ast_repr {
                         if v.is_empty() {
                             return Ok(else_type)
@@ -335,15 +1000,15 @@ impl TypeChecker {
             ASTNode::Declaration {
                 type_, mutability, name
             } => {
-                if self.find_identifier(&name.0, node.span).is_ok() {
+                if let Some(previous) = self.declared_in_scope(&name.0) {
                     return Err(Error {
                         code: ECode::MismatchedTypes,
-                        details: format!("`{}` is already declared", name.0),
+                        details: format!("`{}` is already declared in this scope", name.0),
                         span: name.1,
                         src: self.src.clone(),
                         path: self.path.clone(),
-                        note: None,
-                        help: None
+                        note: Some(self.previously_declared(&name.0, previous)),
+                        help: Some("shadowing is only allowed in a nested block".to_string())
                     })
                 }
                 let scope = self.scopes.last_mut();
@@ -393,24 +1058,45 @@ impl TypeChecker {
                         }, node.span);
                     }
                 }
+                self.init.assigned.remove(&node.span);
+                self.init.unassigned.insert(node.span);
 
                 Ok(Type::Unit)
             },
             ASTNode::DeclarationWithValue {
                 type_, mutability, name, value
             } => {
-                let value_type = self.check_node(*value)?;
-                if self.find_identifier(&name.0, node.span).is_ok() {
+                let declared = match &type_.0 {
+                    ParseType::Determined(t) => Some(self.resolve_type(&(t.clone(), type_.1.unwrap()))?),
+                    ParseType::Inferred => None
+                };
+                let value_span = value.span;
+                let value_type = self.check_expecting(*value, declared.as_ref())?;
+                if value_type == Type::Unit {
+                    return Err(self.unit_binding(&name.0, value_span))
+                }
+                if let Some(declared) = declared.as_ref().filter(|t| **t != value_type && value_type != Type::Never) {
                     return Err(Error {
                         code: ECode::MismatchedTypes,
-                        details: format!("`{}` is already declared", name.0),
-                        span: name.1,
+                        details: format!("expected `{}`, found `{}`", declared, value_type),
+                        span: value_span,
                         src: self.src.clone(),
                         path: self.path.clone(),
-                        note: None,
+                        note: Some(format!("`{}` is declared with type `{}`", name.0, declared)),
                         help: None
                     })
                 }
+                if let Some(previous) = self.declared_in_scope(&name.0) {
+                    return Err(Error {
+                        code: ECode::MismatchedTypes,
+                        details: format!("`{}` is already declared in this scope", name.0),
+                        span: name.1,
+                        src: self.src.clone(),
+                        path: self.path.clone(),
+                        note: Some(self.previously_declared(&name.0, previous)),
+                        help: Some("shadowing is only allowed in a nested block".to_string())
+                    })
+                }
                 let scope = self.scopes.last_mut();
                 if let Some(s) = scope {
                     if let ParseType::Determined(t) = type_.0 {
@@ -462,23 +1148,554 @@ impl TypeChecker {
                 Ok(Type::Unit)
             },
             ASTNode::Block(stmts) => {
-                let mut type_ = Type::Unit;
-                for node in stmts {
-                    type_ = self.check_node(node)?;
+                self.scopes.push(HashMap::new());
+                let mut type_ = Ok(Type::Unit);
+                let mut diverges = false;
+                let last = stmts.len().saturating_sub(1);
+                for (i, node) in stmts.into_iter().enumerate() {
+                    type_ = self.check_expecting(node, if i == last { expected } else { None });
+                    match &type_ {
+                        Ok(t) => diverges |= *t == Type::Never,
+                        Err(_) => break
+                    }
                 }
-                Ok(type_)
+                self.scopes.pop();
+                Ok(if diverges { Type::Never } else { type_? })
             },
             ASTNode::Statement(s) => {
-                self.check_node(*s)?;
-                Ok(Type::Unit)
+                if self.check_node(*s)? == Type::Never {
+                    Ok(Type::Never)
+                } else {
+                    Ok(Type::Unit)
+                }
             },
             ASTNode::Mutation {
                 name, value
             } => {
-                let value_type = self.check_node(*value)?;
+                let target = self.find_identifier(&name.0, name.1).ok().filter(|t| *t != Type::Undetermined);
+                let value_type = self.check_expecting(*value, target.as_ref())?;
                 self.mutate_var(&name.0, node.span, value_type)?;
                 Ok(Type::Unit)
+            },
+            ASTNode::Function {
+                name, params, body, ..
+            } => {
+                let hoisted = self.scopes[0]
+                    .iter()
+                    .find(|(symbol, span)| matches!(symbol, Symbol::Function { name: n, .. } if *n == name.0) && **span == name.1)
+                    .map(|(symbol, _)| symbol.clone());
+                let Some(Symbol::Function { params: param_types, return_type, .. }) = hoisted else {
+                    return Err(Error {
+                        code: ECode::UnexpectedToken,
+                        details: "functions can only be declared at the top level".to_string(),
+                        span: name.1,
+                        src: self.src.clone(),
+                        path: self.path.clone(),
+                        note: None,
+                        help: None
+                    })
+                };
+
+                // Function bodies see the other functions and their own parameters only
+                let functions: HashMap<Symbol, Span> = self.scopes[0]
+                    .iter()
+                    .filter(|(symbol, _)| matches!(symbol, Symbol::Function { .. }))
+                    .map(|(symbol, span)| (symbol.clone(), *span))
+                    .collect();
+                let parameters: HashMap<Symbol, Span> = params
+                    .iter()
+                    .zip(param_types)
+                    .map(|(param, type_)| (Symbol::Variable {
+                        name: param.name.0.clone(), type_, mutability: param.mutability
+                    }, param.name.1))
+                    .collect();
+
+                let scopes = std::mem::replace(&mut self.scopes, vec![functions, parameters]);
+                let outer_return = self.return_type.replace(return_type.clone());
+                let outer_loops = std::mem::take(&mut self.loops);
+                let outer_init = std::mem::take(&mut self.init);
+                let body_span = body.span;
+                let result = self.check_expecting(*body, Some(&return_type));
+                self.scopes = scopes;
+                self.return_type = outer_return;
+                self.loops = outer_loops;
+                self.init = outer_init;
+
+                let body_type = result?;
+                if body_type != Type::Never && body_type != return_type {
+                    return Err(Error {
+                        code: ECode::MismatchedTypes,
+                        details: format!("function `{}` returns `{}` but its body has type `{}`", name.0, return_type, body_type),
+                        span: body_span,
+                        src: self.src.clone(),
+                        path: self.path.clone(),
+                        note: None,
+                        help: if return_type == Type::Unit {
+                            Some(format!("add `-> {}` after the parameter list to return the value", body_type))
+                        } else {
+                            None
+                        }
+                    })
+                }
+                Ok(Type::Unit)
+            },
+            ASTNode::Call {
+                callee, args
+            } => {
+                let (params, return_type) = self.find_function(&callee.0, callee.1)?;
+                if args.len() != params.len() {
+                    return Err(Error {
+                        code: ECode::MismatchedTypes,
+                        details: format!(
+                            "function `{}` takes {} argument(s) but {} were supplied",
+                            callee.0, params.len(), args.len()
+                        ),
+                        span: node.span,
+                        src: self.src.clone(),
+                        path: self.path.clone(),
+                        note: None,
+                        help: None
+                    })
+                }
+                for (arg, param) in args.into_iter().zip(params) {
+                    let arg_span = arg.span;
+                    let arg_type = self.check_expecting(arg, Some(&param))?;
+                    if arg_type != param && arg_type != Type::Never {
+                        return Err(Error {
+                            code: ECode::MismatchedTypes,
+                            details: format!("expected `{}`, found `{}`", param, arg_type),
+                            span: arg_span,
+                            src: self.src.clone(),
+                            path: self.path.clone(),
+                            note: Some(format!("in a call to `{}`", callee.0)),
+                            help: None
+                        })
+                    }
+                }
+                Ok(return_type)
+            },
+            ASTNode::StructDecl { ref name, .. } | ASTNode::EnumDecl { ref name, .. } => {
+                if self.type_spans.get(&name.0) != Some(&name.1) {
+                    let kind = if let ASTNode::StructDecl { .. } = node.ast_repr { "structs" } else { "enums" };
+                    return Err(Error {
+                        code: ECode::UnexpectedToken,
+                        details: format!("{} can only be declared at the top level", kind),
+                        span: name.1,
+                        src: self.src.clone(),
+                        path: self.path.clone(),
+                        note: None,
+                        help: None
+                    })
+                }
+                Ok(Type::Unit)
+            },
+            ASTNode::StructLit { name, fields } => {
+                let Some(layout) = self.type_registry.layout(&name.0).cloned() else {
+                    return Err(Error {
+                        code: ECode::UndefinedIdentifier,
+                        details: format!("cannot find struct `{}` in scope", name.0),
+                        span: name.1,
+                        src: self.src.clone(),
+                        path: self.path.clone(),
+                        note: None,
+                        help: None
+                    })
+                };
+                let struct_type = Type::Alias(name.0.clone());
+
+                for (i, (field, value)) in fields.iter().enumerate() {
+                    if fields[..i].iter().any(|(f, _)| f.0 == field.0) {
+                        return Err(Error {
+                            code: ECode::MismatchedTypes,
+                            details: format!("field `{}` is specified more than once", field.0),
+                            span: field.1,
+                            src: self.src.clone(),
+                            path: self.path.clone(),
+                            note: None,
+                            help: None
+                        })
+                    }
+                    let expected = self.field_type(&struct_type, field)?;
+                    let found = self.check_expecting(value.clone(), Some(&expected))?;
+                    if found != expected && found != Type::Never {
+                        return Err(Error {
+                            code: ECode::MismatchedTypes,
+                            details: format!("expected `{}`, found `{}`", expected, found),
+                            span: value.span,
+                            src: self.src.clone(),
+                            path: self.path.clone(),
+                            note: Some(format!("for field `{}` of `{}`", field.0, name.0)),
+                            help: None
+                        })
+                    }
+                }
+
+                let missing: Vec<String> = layout.fields
+                    .iter()
+                    .filter(|f| !fields.iter().any(|(given, _)| given.0 == f.name))
+                    .map(|f| format!("`{}`", f.name))
+                    .collect();
+                if !missing.is_empty() {
+                    return Err(Error {
+                        code: ECode::MismatchedTypes,
+                        details: format!("missing field(s) {} in initializer of `{}`", missing.join(", "), name.0),
+                        span: node.span,
+                        src: self.src.clone(),
+                        path: self.path.clone(),
+                        note: None,
+                        help: None
+                    })
+                }
+                Ok(struct_type)
+            },
+            ASTNode::FieldAccess { object, field } => {
+                let object_type = self.check_node(*object)?;
+                self.field_type(&object_type, &field)
+            },
+            ASTNode::FieldMutation { name, fields, value } => {
+                let mut target = self.find_identifier(&name.0, name.1).ok();
+                for field in &fields {
+                    target = target.and_then(|t| self.field_type(&t, field).ok());
+                }
+                let value_type = self.check_expecting(*value, target.as_ref())?;
+                let mut type_ = self.find_identifier(&name.0, name.1)?;
+                self.check_assigned(&name.0, name.1)?;
+                for field in &fields {
+                    type_ = self.field_type(&type_, field)?;
+                }
+                let place = std::iter::once(name.0.clone())
+                    .chain(fields.iter().map(|f| f.0.clone()))
+                    .collect::<Vec<_>>()
+                    .join(".");
+
+                if !self.is_mutable(&name.0) {
+                    return Err(Error { 
+                        code: ECode::MutationError, 
+                        details: format!("cannot mutate `{}` of immutable variable `{}`", place, name.0), 
+                        span: node.span, 
+                        src: self.src.clone(), 
+                        path: self.path.clone(),
+                        note: None,
+                        help: Some(format!("declare `{}` with `mut`", name.0))
+                    })
+                }
+                if type_ != value_type {
+                    return Err(Error { 
+                        code: ECode::MutationError, 
+                        details: format!("`{}` expects type `{}` but found type `{}`", place, type_, value_type), 
+                        span: node.span, 
+                        src: self.src.clone(), 
+                        path: self.path.clone(),
+                        note: None,
+                        help: None
+                    })
+                }
+                Ok(Type::Unit)
+            },
+            ASTNode::Return(value) => {
+                let Some(expected) = self.return_type.clone() else {
+                    return Err(Error {
+                        code: ECode::UnexpectedToken,
+                        details: "`return` outside of a function".to_string(),
+                        span: node.span,
+                        src: self.src.clone(),
+                        path: self.path.clone(),
+                        note: None,
+                        help: None
+                    })
+                };
+                let found = match value {
+                    Some(v) => self.check_expecting(*v, Some(&expected))?,
+                    None => Type::Unit
+                };
+                if found != expected && found != Type::Never {
+                    return Err(Error {
+                        code: ECode::MismatchedTypes,
+                        details: format!("expected `{}`, found `{}`", expected, found),
+                        span: node.span,
+                        src: self.src.clone(),
+                        path: self.path.clone(),
+                        note: Some("the function's return type is declared in its signature".to_string()),
+                        help: None
+                    })
+                }
+                Ok(Type::Never)
+            },
+            ASTNode::EnumVariant { enum_name, variant, args } => {
+                let (_, types) = self.find_variant(&enum_name, &variant)?;
+                if args.len() != types.len() {
+                    return Err(Error {
+                        code: ECode::MismatchedTypes,
+                        details: format!(
+                            "variant `{}::{}` takes {} argument(s) but {} were supplied",
+                            enum_name.0, variant.0, types.len(), args.len()
+                        ),
+                        span: node.span,
+                        src: self.src.clone(),
+                        path: self.path.clone(),
+                        note: None,
+                        help: None
+                    })
+                }
+                for (arg, expected) in args.into_iter().zip(types) {
+                    let arg_span = arg.span;
+                    let found = self.check_expecting(arg, Some(&expected))?;
+                    if found != expected && found != Type::Never {
+                        return Err(Error {
+                            code: ECode::MismatchedTypes,
+                            details: format!("expected `{}`, found `{}`", expected, found),
+                            span: arg_span,
+                            src: self.src.clone(),
+                            path: self.path.clone(),
+                            note: Some(format!("in the payload of `{}::{}`", enum_name.0, variant.0)),
+                            help: None
+                        })
+                    }
+                }
+                Ok(Type::Alias(enum_name.0))
+            },
+            ASTNode::Match { scrutinee, arms } => {
+                let scrutinee_span = scrutinee.span;
+                let scrutinee_type = self.check_node(*scrutinee)?;
+                let types = [scrutinee_type.clone()];
+
+                let mut rows: Vec<Vec<Pat>> = Vec::new();
+                let mut result: Option<Type> = None;
+                let entry = self.init.clone();
+                let mut exit: Option<InitState> = None;
+                for arm in arms {
+                    self.init = entry.clone();
+                    let mut bindings: Vec<(String, Type, Span)> = Vec::new();
+                    self.check_pattern(&arm.pattern, &scrutinee_type, &mut bindings)?;
+
+                    let row = vec![Pat::from_pattern(&arm.pattern, &self.type_registry)];
+                    if !Exhaustiveness::new(&self.type_registry).is_useful(&rows, &row, &types) {
+                        return Err(Error {
+                            code: ECode::UnreachablePattern,
+                            details: "unreachable pattern".to_string(),
+                            span: arm.pattern.span(),
+                            src: self.src.clone(),
+                            path: self.path.clone(),
+                            note: Some("every value it matches is matched by an earlier arm".to_string()),
+                            help: Some("remove this arm or move it before the arms that cover it".to_string())
+                        })
+                    }
+                    rows.push(row);
+
+                    self.scopes.push(bindings
+                        .into_iter()
+                        .map(|(name, type_, span)| (Symbol::Variable { name, type_, mutability: false }, span))
+                        .collect());
+                    let body_span = arm.body.span;
+                    let body_type = self.check_expecting(arm.body, expected.or(result.as_ref()));
+                    self.scopes.pop();
+                    let body_type = body_type?;
+
+                    // Diverging arms take the type of the others
+                    if body_type != Type::Never {
+                        let init = std::mem::take(&mut self.init);
+                        exit = Some(match exit.take() {
+                            Some(exit) => exit.join(init),
+                            None => init
+                        });
+                        if let Some(t) = result.as_ref().filter(|t| **t != body_type) {
+                            return Err(Error {
+                                code: ECode::MismatchedTypes,
+                                details: format!("`match` arms have mismatched types: `{}`, `{}`", t, body_type),
+                                span: body_span,
+                                src: self.src.clone(),
+                                path: self.path.clone(),
+                                note: None,
+                                help: None
+                            })
+                        }
+                        result = Some(body_type);
+                    }
+                }
+
+                if let Some(witness) = Exhaustiveness::new(&self.type_registry).missing(&rows, &types) {
+                    let witness = witness[0].display(&scrutinee_type, &self.type_registry);
+                    return Err(Error {
+                        code: ECode::NonExhaustive,
+                        details: format!("pattern `{}` not covered", witness),
+                        span: scrutinee_span,
+                        src: self.src.clone(),
+                        path: self.path.clone(),
+                        note: Some(format!("the matched value has type `{}`", scrutinee_type)),
+                        help: Some(if witness == "_" {
+                            "add a `_` arm to match the remaining values".to_string()
+                        } else {
+                            format!("add an arm for `{}` or a `_` arm", witness)
+                        })
+                    })
+                }
+                self.init = exit.unwrap_or(entry);
+                Ok(result.unwrap_or(Type::Never))
+            },
+            ASTNode::While { condition, body } => {
+                let condition_span = condition.span;
+                let condition_type = self.check_node(*condition)?;
+                if condition_type != Type::Boolean && condition_type != Type::Never {
+                    return Err(Error {
+                        code: ECode::MismatchedTypes,
+                        details: format!("expected `bool`, found `{}`", condition_type),
+                        span: condition_span,
+                        src: self.src.clone(),
+                        path: self.path.clone(),
+                        note: None,
+                        help: None
+                    })
+                }
+
+                // The body may not run at all
+                let entry = self.init.clone();
+                self.loops.push(LoopContext {
+                    is_while: true, break_type: None, entry: entry.clone(), breaks: Vec::new()
+                });
+                let result = self.check_node(*body).and_then(|body_type| {
+                    if body_type != Type::Never { self.check_back_edge()? }
+                    Ok(body_type)
+                });
+                let context = self.loops.pop().unwrap();
+                let body_type = result?;
+
+                let mut exit = entry;
+                if body_type != Type::Never {
+                    exit = exit.join(std::mem::take(&mut self.init));
+                }
+                self.init = context.breaks.into_iter().fold(exit, InitState::join);
+                Ok(Type::Unit)
+            },
+            ASTNode::Loop(body) => {
+                self.loops.push(LoopContext {
+                    is_while: false, break_type: None, entry: self.init.clone(), breaks: Vec::new()
+                });
+                let result = self.check_node(*body).and_then(|body_type| {
+                    if body_type != Type::Never { self.check_back_edge()? }
+                    Ok(body_type)
+                });
+                let context = self.loops.pop().unwrap();
+                result?;
+
+                // Only a `break` leaves the loop
+                let mut breaks = context.breaks.into_iter();
+                if let Some(first) = breaks.next() {
+                    self.init = breaks.fold(first, InitState::join);
+                }
+
+                // A loop that is never broken out of never finishes
+                Ok(context.break_type.unwrap_or(Type::Never))
+            },
+            ASTNode::Break(value) => {
+                if self.loops.is_empty() {
+                    return Err(Error {
+                        code: ECode::UnexpectedToken,
+                        details: "`break` outside of a loop".to_string(),
+                        span: node.span,
+                        src: self.src.clone(),
+                        path: self.path.clone(),
+                        note: None,
+                        help: None
+                    })
+                }
+                let hint = self.loops.last().unwrap().break_type.clone();
+                let found = match value {
+                    Some(v) => self.check_expecting(*v, hint.as_ref())?,
+                    None => Type::Unit
+                };
+
+                let context = self.loops.last_mut().unwrap();
+                if context.is_while && found != Type::Unit {
+                    return Err(Error {
+                        code: ECode::MismatchedTypes,
+                        details: "`break` with a value inside a `while` loop".to_string(),
+                        span: node.span,
+                        src: self.src.clone(),
+                        path: self.path.clone(),
+                        note: Some("a `while` loop may end without breaking, so it always has type `unit`".to_string()),
+                        help: Some("use `loop` to produce a value with `break`".to_string())
+                    })
+                }
+                if found != Type::Never {
+                    if let Some(expected) = context.break_type.as_ref().filter(|t| **t != found) {
+                        return Err(Error {
+                            code: ECode::MismatchedTypes,
+                            details: format!("expected `{}`, found `{}`", expected, found),
+                            span: node.span,
+                            src: self.src.clone(),
+                            path: self.path.clone(),
+                            note: Some("every `break` of a loop must carry the same type".to_string()),
+                            help: None
+                        })
+                    }
+                    context.break_type = Some(found);
+                }
+                context.breaks.push(self.init.clone());
+                Ok(Type::Never)
+            },
+            ASTNode::Continue => {
+                if self.loops.is_empty() {
+                    return Err(Error {
+                        code: ECode::UnexpectedToken,
+                        details: "`continue` outside of a loop".to_string(),
+                        span: node.span,
+                        src: self.src.clone(),
+                        path: self.path.clone(),
+                        note: None,
+                        help: None
+                    })
+                }
+                self.check_back_edge()?;
+                Ok(Type::Never)
+            },
+            ASTNode::Cast { value, type_ } => {
+                let target = self.resolve_type(&type_)?;
+                let source = self.check_node(*value)?;
+                if source == Type::Never {
+                    return Ok(Type::Never)
+                }
+                if !is_castable(&source, &target) {
+                    return Err(Error {
+                        code: ECode::MismatchedTypes,
+                        details: format!("cannot cast `{}` as `{}`", source, target),
+                        span: node.span,
+                        src: self.src.clone(),
+                        path: self.path.clone(),
+                        note: Some("`as` only converts between numeric types, and from `bool` to integers".to_string()),
+                        help: (source == Type::Boolean || target == Type::Boolean).then(|| if target == Type::Boolean {
+                            "compare with zero instead, e.g. `x != 0`".to_string()
+                        } else {
+                            format!("cast to an integer first, e.g. `b as u8 as {}`", target)
+                        })
+                    })
+                }
+                Ok(target)
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Whether `as` converts values of type `from` to `to`. Integers and
+/// floats convert between each other, booleans only become integers
+fn is_castable(from: &Type, to: &Type) -> bool {
+    let numeric = |t: &Type| int_range(t).is_some() || matches!(t, Type::Float32 | Type::Float64);
+    match from {
+        Type::Boolean => int_range(to).is_some() || *to == Type::Boolean,
+        _ => numeric(from) && numeric(to)
+    }
+}
+
+/// Smallest and largest value of an integer type
+fn int_range(t: &Type) -> Option<(i128, i128)> {
+    Some(match t {
+        Type::Int8 => (i8::MIN as i128, i8::MAX as i128),
+        Type::Int16 => (i16::MIN as i128, i16::MAX as i128),
+        Type::Int32 => (i32::MIN as i128, i32::MAX as i128),
+        Type::Int64 => (i64::MIN as i128, i64::MAX as i128),
+        Type::UInt8 => (0, u8::MAX as i128),
+        Type::UInt16 => (0, u16::MAX as i128),
+        Type::UInt32 => (0, u32::MAX as i128),
+        Type::UInt64 => (0, u64::MAX as i128),
+        _ => return None
+    })
+}
@@ -1,8 +1,58 @@
 use std::collections::HashMap;
 use super::*;
 
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructField {
+    pub name: String,
+    pub type_: Type,
+    pub offset: usize
+}
+
+/// Memory layout of a struct, fields are laid out in declaration order
+/// and padded to their alignment like a C struct
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructLayout {
+    pub fields: Vec<StructField>,
+    pub size: usize,
+    pub align: usize
+}
+
+impl StructLayout {
+    pub fn field(&self, name: &str) -> Option<(usize, &StructField)> {
+        self.fields.iter().enumerate().find(|(_, f)| f.name == name)
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumVariant {
+    pub name: String,
+    pub fields: Vec<StructField>
+}
+
+/// Memory layout of an enum, a `u32` tag at offset 0 followed by the
+/// payloads of all variants, which overlap each other. Field offsets are
+/// from the start of the enum
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumLayout {
+    pub variants: Vec<EnumVariant>,
+    pub size: usize,
+    pub align: usize
+}
+
+impl EnumLayout {
+    pub fn variant(&self, name: &str) -> Option<(usize, &EnumVariant)> {
+        self.variants.iter().enumerate().find(|(_, v)| v.name == name)
+    }
+}
+
 pub struct TypeRegistry {
-    registry: HashMap<String, Type>
+    registry: HashMap<String, Type>,
+    structs: HashMap<String, StructLayout>,
+    enums: HashMap<String, EnumLayout>
 }
 
 #[allow(dead_code)]
@@ -23,7 +73,9 @@ impl TypeRegistry {
         registry.insert("bool".to_string(), Type::Boolean);
         registry.insert("unit".to_string(), Type::Unit);
         Self {
-            registry
+            registry,
+            structs: HashMap::new(),
+            enums: HashMap::new()
         }
     }
 
@@ -42,4 +94,69 @@ impl TypeRegistry {
     pub fn remove(&mut self, s: &str) {
         self.registry.remove(s);
     }
-}
\ No newline at end of file
+
+    /// Computes the layout of `name` from its fields and registers it as
+    /// `Type::Alias(name)`. Struct-typed fields must already be defined
+    pub fn define_struct(&mut self, name: &str, fields: Vec<(String, Type)>) -> &StructLayout {
+        let mut layout = StructLayout { fields: Vec::new(), size: 0, align: 1 };
+        for (field, type_) in fields {
+            let (size, align) = self.size_align(&type_).unwrap_or((0, 1));
+            let offset = layout.size.next_multiple_of(align);
+            layout.size = offset + size;
+            layout.align = layout.align.max(align);
+            layout.fields.push(StructField { name: field, type_, offset });
+        }
+        layout.size = layout.size.next_multiple_of(layout.align);
+
+        self.register(name, Type::Alias(name.to_string()));
+        self.structs.insert(name.to_string(), layout);
+        &self.structs[name]
+    }
+
+    pub fn layout(&self, name: &str) -> Option<&StructLayout> {
+        self.structs.get(name)
+    }
+
+    /// Computes the layout of `name` from its variants and registers it as
+    /// `Type::Alias(name)`. Payload types must already be defined
+    pub fn define_enum(&mut self, name: &str, variants: Vec<(String, Vec<Type>)>) -> &EnumLayout {
+        let mut layout = EnumLayout { variants: Vec::new(), size: 4, align: 4 };
+        for (variant, types) in variants {
+            let mut fields = Vec::new();
+            let mut end: usize = 4;
+            for (i, type_) in types.into_iter().enumerate() {
+                let (size, align) = self.size_align(&type_).unwrap_or((0, 1));
+                let offset = end.next_multiple_of(align);
+                end = offset + size;
+                layout.align = layout.align.max(align);
+                fields.push(StructField { name: i.to_string(), type_, offset });
+            }
+            layout.size = layout.size.max(end);
+            layout.variants.push(EnumVariant { name: variant, fields });
+        }
+        layout.size = layout.size.next_multiple_of(layout.align);
+
+        self.register(name, Type::Alias(name.to_string()));
+        self.enums.insert(name.to_string(), layout);
+        &self.enums[name]
+    }
+
+    pub fn enum_layout(&self, name: &str) -> Option<&EnumLayout> {
+        self.enums.get(name)
+    }
+
+    /// Size and alignment in bytes, strings are a pointer and a length
+    pub fn size_align(&self, t: &Type) -> Option<(usize, usize)> {
+        match t {
+            Type::Int8 | Type::UInt8 | Type::Boolean => Some((1, 1)),
+            Type::Int16 | Type::UInt16 => Some((2, 2)),
+            Type::Int32 | Type::UInt32 | Type::Float32 | Type::Char => Some((4, 4)),
+            Type::Int64 | Type::UInt64 | Type::Float64 => Some((8, 8)),
+            Type::String => Some((16, 8)),
+            Type::Unit | Type::Void => Some((0, 1)),
+            Type::Alias(name) => self.layout(name).map(|l| (l.size, l.align))
+                .or_else(|| self.enum_layout(name).map(|l| (l.size, l.align))),
+            Type::Never | Type::Undetermined => None
+        }
+    }
+}
@@ -2,21 +2,43 @@ use std::fmt;
 use std::collections::HashMap;
 use colored::Colorize;
 use cranelift::prelude::Value;
-use once_cell::unsync::Lazy;
+use once_cell::sync::Lazy;
 pub fn prec(op: &String) -> Option<(i32, i32)> {
     match &**op {
-        "+" | "-" => Some((20, 21)),
-        "*" | "/" => Some((30, 31)),
+        "||" => Some((3, 4)),
+        "&&" => Some((5, 6)),
         "==" | ">" | "<"
         | ">=" | "<=" | "!=" => Some((10, 11)),
+        "|" => Some((12, 13)),
+        "^" => Some((14, 15)),
+        "&" => Some((16, 17)),
+        "<<" | ">>" => Some((18, 19)),
+        "+" | "-" => Some((20, 21)),
+        "*" | "/" | "%" => Some((30, 31)),
         "++" => Some((40, 41)),
         ":=" => Some((50, 51)),
+        "as" => Some((60, 61)),
+        _ => None
+    }
+}
+
+/// Binary operators producing a value of their operands' type
+pub fn is_arithmetic(op: &str) -> bool {
+    matches!(op, "+" | "-" | "*" | "/" | "%" | "&" | "|" | "^" | "<<" | ">>")
+}
+
+/// Parameter and return types of the functions every program can call
+/// without declaring them, a `func` of the same name takes precedence
+pub fn builtin_function(name: &str) -> Option<(Vec<Type>, Type)> {
+    match name {
+        "len" => Some((vec![Type::String], Type::UInt64)),
+        "slice" => Some((vec![Type::String, Type::UInt64, Type::UInt64], Type::String)),
         _ => None
     }
 }
 
 #[allow(dead_code)]
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Span {
     pub line: usize,
     pub column: usize,
@@ -34,16 +56,24 @@ pub enum ECode {
 
     MismatchedTypes, // E1004
     MutationError, // E1005
+    Unsupported, // E1006
+    NonExhaustive, // E1007
+    UnreachablePattern, // E1008
+    Uninitialized, // E1009
 }
 
-pub const ERR_MAP: Lazy<HashMap<ECode, String>> = Lazy::new(|| {
+pub static ERR_MAP: Lazy<HashMap<ECode, String>> = Lazy::new(|| {
     [
         (ECode::UnexpectedEOF, "E1000".to_string()),
         (ECode::UnexpectedToken, "E1001".to_string()),
         (ECode::ExpectedToken, "E1002".to_string()),
         (ECode::UndefinedIdentifier, "E1003".to_string()),
         (ECode::MismatchedTypes, "E1004".to_string()),
-        (ECode::MutationError, "E1005".to_string())
+        (ECode::MutationError, "E1005".to_string()),
+        (ECode::Unsupported, "E1006".to_string()),
+        (ECode::NonExhaustive, "E1007".to_string()),
+        (ECode::UnreachablePattern, "E1008".to_string()),
+        (ECode::Uninitialized, "E1009".to_string())
     ]
     .into_iter()
     .collect::<HashMap<ECode, String>>()
@@ -55,8 +85,8 @@ impl fmt::Display for ECode {
     }
 }
 
-pub const COMBINED_SYMBOLS: &[&str] = &["==", ">=", "<=", "++", ":="];
-pub const KEYWORDS: &[&str] = &["if", "else", "mut", "struct", "enum", "func"];
+pub const COMBINED_SYMBOLS: &[&str] = &["==", "!=", ">=", "<=", "++", ":=", "->", "::", "=>", "&&", "||", "<<", ">>"];
+pub const KEYWORDS: &[&str] = &["if", "else", "mut", "struct", "enum", "func", "return", "match", "while", "loop", "break", "continue", "as"];
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ParseType {
@@ -76,6 +106,7 @@ pub enum Type {
     Alias(String),
     Void,
     Unit,
+    Never,
     Undetermined
 }
 
@@ -98,6 +129,7 @@ impl fmt::Display for Type {
             Self::Alias(s) => write!(f, "{}", s),
             Self::Void => write!(f, "void"),
             Self::Unit => write!(f, "unit"),
+            Self::Never => write!(f, "never"),
             Self::Undetermined => write!(f, "{{undetermined}}")
         }
     }
@@ -106,7 +138,8 @@ impl fmt::Display for Type {
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 #[allow(dead_code)]
 pub enum Symbol {
-    Variable { name: String, type_: Type, mutability: bool }
+    Variable { name: String, type_: Type, mutability: bool },
+    Function { name: String, params: Vec<Type>, return_type: Type }
 }
 
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
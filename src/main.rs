@@ -93,12 +93,17 @@ const COMPILED_CLANG: usize = 3;
 
 fn main() {
     let cli = cli::Cli::parse();
-
-    run(cli.input, cli.output, cli.debug, cli.parse_only)
+    run(&cli)
 }
 
-fn run(input: String, output: Option<String>, debug: bool, parse_only: bool) {
-    let path = &*input;
+fn run(cli: &cli::Cli) {
+    let path = &*cli.input;
+    let debug = cli.debug;
+    let level = backend::ir::optimization::OptLevel::from_flag(&cli.opt_level)
+        .expect("clap only accepts known levels");
+    let string_alloc = cli.string_arena
+        .map(backend::runtime::StringAlloc::Arena)
+        .unwrap_or_default();
     let width = longest_string_length(&MSGS) + 5;
 
     println!("{:>width$} `{}`", MSGS[COMPILING].green().bold(), path);
@@ -157,32 +162,56 @@ fn run(input: String, output: Option<String>, debug: bool, parse_only: bool) {
         exit(1)
     }
 
-    if parse_only { exit(0) }
+    if cli.parse_only { exit(0) }
 
-    /*
-    let mut compiler = match backend::ASTCompiler::new(
-        debug,
-        parsed,
-        contents,
-        path.to_string()
-    ) {
-        Ok(compiler) => compiler,
+    let mut compiler = backend::ASTCompiler::new(parsed, contents, path.to_string())
+        .with_string_alloc(string_alloc);
+    if cli.stack_locals {
+        compiler = compiler.with_stack_locals();
+    }
+    let mut module = match compiler.compile_module() {
+        Ok(module) => module,
         Err(e) => {
-            eprintln!("Could not create compiler due to:\n{}", e);
+            eprintln!("{:>width$}\n{}", MSGS[ERROR].red().bold(), e);
             exit(1)
         }
     };
-    
-    // Step 5: Compile to object file
-    match compiler.compile_module() {
-        Ok(()) => {},
+    if debug {
+        println!();
+        println!("{}\n{}", "IR:".cyan().bold(), module.display())
+    }
+
+    let mut optimizer = backend::ir::optimization::Optimizer::new(&mut module).with_level(level);
+    optimizer.run();
+    for diagnostic in optimizer.diagnostics() {
+        eprintln!("{} {}", "WARNING:".yellow().bold(), diagnostic);
+    }
+    if cli.stats {
+        println!("{}", "Optimization statistics:".cyan().bold());
+        for statistics in optimizer.statistics() {
+            println!("  {}", statistics);
+        }
+    }
+    if debug && level != backend::ir::optimization::OptLevel::O0 {
+        println!();
+        println!("{}\n{}", "Optimized IR:".cyan().bold(), module.display())
+    }
+
+    let mut lowerer = match backend::ir::lower::Lowerer::new(module.name()) {
+        Ok(lowerer) => lowerer,
         Err(e) => {
-            eprintln!("Could not compiler due to:\n{}", e);
+            eprintln!("Could not create compiler due to:\n{}", e);
             exit(1)
         }
+    };
+
+    // Compile to object file
+    if let Err(e) = lowerer.lower_module(&module).and_then(|()| lowerer.write("output.o")) {
+        eprintln!("Could not compile due to:\n{}", e);
+        exit(1)
     }
-    let output_name = if let Some(output) = output {
-        if output.ends_with(".exe") { output } else { format!("{}.exe", output) }
+    let output_name = if let Some(output) = &cli.output {
+        if output.ends_with(".exe") { output.clone() } else { format!("{}.exe", output) }
     } else {
         "main.exe".to_string()
     };
@@ -204,7 +233,7 @@ fn run(input: String, output: Option<String>, debug: bool, parse_only: bool) {
     }
     if std::path::Path::new("output.o").exists() {
         let _ = std::fs::remove_file("output.o");
-    }*/
+    }
 }
 
 fn link_with_system_linker(object_files: &[&str], output: &str) -> Result<(), Box<dyn std::error::Error>> {